@@ -0,0 +1,76 @@
+//! 权限请求描述生成
+//!
+//! 把工具调用转换为给用户看的一行描述（`ApprovalRequestData.description`）。
+
+use serde_json::Value;
+
+/// 工具调用描述生成器
+pub trait DescriptionFormatter: Send + Sync {
+    /// 生成工具调用的描述
+    fn format(&self, tool_name: &str, input: &Value) -> String;
+}
+
+/// 默认描述生成器（覆盖 Claude Code 内置工具）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultDescriptionFormatter;
+
+impl DescriptionFormatter for DefaultDescriptionFormatter {
+    fn format(&self, tool_name: &str, input: &Value) -> String {
+        let field = |key: &str| input.get(key).and_then(|v| v.as_str()).unwrap_or("unknown");
+
+        match tool_name {
+            "Bash" => format!("Execute: {}", field("command")),
+            "BashOutput" => format!("Read shell output: {}", field("bash_id")),
+            "KillShell" => format!("Kill shell: {}", field("shell_id")),
+            "Read" => format!("Read file: {}", field("file_path")),
+            "Write" => format!("Write file: {}", field("file_path")),
+            "Edit" | "MultiEdit" => format!("Edit file: {}", field("file_path")),
+            "NotebookEdit" => format!("Edit notebook: {}", field("notebook_path")),
+            "Delete" => format!("Delete file: {}", field("file_path")),
+            "Glob" => format!("Find files: {}", field("pattern")),
+            "Grep" => match input.get("path").and_then(|v| v.as_str()) {
+                Some(path) => format!("Search: {} in {}", field("pattern"), path),
+                None => format!("Search: {}", field("pattern")),
+            },
+            "LS" => format!("List directory: {}", field("path")),
+            "WebFetch" => format!("Fetch URL: {}", field("url")),
+            "WebSearch" => format!("Web search: {}", field("query")),
+            "Task" => format!("Run agent: {}", field("description")),
+            "TodoWrite" => "Update todo list".to_string(),
+            "ExitPlanMode" => "Exit plan mode".to_string(),
+            "SlashCommand" => format!("Run command: {}", field("command")),
+            "Skill" => format!("Use skill: {}", field("command")),
+            _ => match tool_name.strip_prefix("mcp__") {
+                // MCP 工具名格式：mcp__<server>__<tool>
+                Some(rest) => match rest.split_once("__") {
+                    Some((server, tool)) => format!("Call MCP tool: {} ({})", tool, server),
+                    None => format!("Call MCP tool: {}", rest),
+                },
+                None => format!("Call tool: {}", tool_name),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_formatter() {
+        let f = DefaultDescriptionFormatter;
+
+        assert_eq!(f.format("Bash", &json!({"command": "ls -la"})), "Execute: ls -la");
+        assert_eq!(f.format("Write", &json!({"file_path": "/a.rs"})), "Write file: /a.rs");
+        assert_eq!(f.format("MultiEdit", &json!({"file_path": "/a.rs"})), "Edit file: /a.rs");
+        assert_eq!(f.format("WebSearch", &json!({"query": "rust"})), "Web search: rust");
+        assert_eq!(f.format("Grep", &json!({"pattern": "fn", "path": "src"})), "Search: fn in src");
+        assert_eq!(f.format("Read", &json!({})), "Read file: unknown");
+        assert_eq!(
+            f.format("mcp__github__create_issue", &json!({})),
+            "Call MCP tool: create_issue (github)"
+        );
+        assert_eq!(f.format("Screenshot", &json!({})), "Call tool: Screenshot");
+    }
+}
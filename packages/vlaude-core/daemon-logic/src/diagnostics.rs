@@ -0,0 +1,405 @@
+//! 自诊断报告
+//!
+//! 汇总 daemon 当前状态，便于用户提交问题时附带。
+
+use claude_session_db::coordination::WriterHealth;
+use serde::Serialize;
+use session_reader::ClaudeReader;
+use socket_client::UnknownEvent;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::SharedDbAdapter;
+
+/// Daemon 诊断报告
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticReport {
+    /// Daemon 版本
+    pub version: String,
+    /// 设备名
+    pub hostname: String,
+    /// 共享数据库角色（未启用共享数据库时为 None）
+    pub shared_db_role: Option<String>,
+    /// 读取器已知的项目数
+    pub project_count: usize,
+    /// 读取器已知的会话数
+    pub session_count: usize,
+    /// 运行中 daemon 的状态（本地诊断不查询运行中的 daemon，为 None）
+    #[serde(flatten)]
+    pub runtime: Option<RuntimeDiagnostics>,
+}
+
+/// 运行中 daemon 的状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeDiagnostics {
+    /// 是否已连接 Server
+    pub connected: bool,
+    /// 当前 Server 地址
+    pub server_url: Option<String>,
+    /// 正在监听的会话数
+    pub watching_session_count: usize,
+    /// 等待中的权限请求数
+    pub pending_approval_count: usize,
+    /// 最后一次收到 Server 事件的时间（RFC 3339）
+    pub last_event_at: Option<String>,
+    /// Redis 连接状态（未启用服务发现时为 None）
+    pub redis_connected: Option<bool>,
+    /// 会话消息请求的数据来源统计
+    pub session_message_source: MessageSourceStats,
+    /// 最近收到的无法识别的 Server 事件（最多 100 条）
+    pub unknown_events: Vec<UnknownEvent>,
+    /// 各事件处理器的耗时统计（按事件名）
+    pub handler_metrics: BTreeMap<String, HandlerStats>,
+}
+
+impl DiagnosticReport {
+    /// 本地诊断报告（不启动 daemon）
+    ///
+    /// 只读取会话文件和共享数据库，不连接 Redis 和 Server；
+    /// 不查询运行中的 daemon，运行时状态（连接、监听、事件统计）为 None，显示为 n/a。
+    pub async fn local(
+        hostname: &str,
+        reader: &mut ClaudeReader,
+        shared_db: Option<&SharedDbAdapter>,
+    ) -> Self {
+        let (project_count, session_count) = count_projects_and_sessions(reader);
+        let shared_db_role = match shared_db {
+            Some(db) => Some(match db.check_writer_health().await {
+                Ok(WriterHealth::Alive) => "writer alive".to_string(),
+                Ok(_) => "no live writer".to_string(),
+                Err(e) => format!("unknown ({})", e),
+            }),
+            None => None,
+        };
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: hostname.to_string(),
+            shared_db_role,
+            project_count,
+            session_count,
+            runtime: None,
+        }
+    }
+}
+
+/// 读取器已知的项目数和会话数（读取失败时记为 0）
+pub(crate) fn count_projects_and_sessions(reader: &mut ClaudeReader) -> (usize, usize) {
+    let projects = reader.list_projects(None, 0).map(|p| p.len()).unwrap_or_else(|e| {
+        warn!("[Diagnostics] Failed to list projects: {:?}", e);
+        0
+    });
+    let sessions = reader.list_sessions(None, false, None).map(|s| s.len()).unwrap_or_else(|e| {
+        warn!("[Diagnostics] Failed to list sessions: {:?}", e);
+        0
+    });
+    (projects, sessions)
+}
+
+/// 会话消息请求的数据来源统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSourceStats {
+    /// 命中共享数据库（数据新鲜）
+    pub db_hit: u64,
+    /// 数据库中有数据但已过期，回退 JSONL
+    pub db_miss_stale: u64,
+    /// 数据库中没有该会话，回退 JSONL
+    pub db_miss_absent: u64,
+}
+
+/// 会话消息数据来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageSource {
+    DbHit,
+    DbMissStale,
+    DbMissAbsent,
+}
+
+/// 会话消息数据来源计数器（线程安全）
+#[derive(Debug, Default)]
+pub struct MessageSourceCounters {
+    db_hit: AtomicU64,
+    db_miss_stale: AtomicU64,
+    db_miss_absent: AtomicU64,
+}
+
+impl MessageSourceCounters {
+    /// 记录一次数据来源
+    pub fn record(&self, source: MessageSource) {
+        let counter = match source {
+            MessageSource::DbHit => &self.db_hit,
+            MessageSource::DbMissStale => &self.db_miss_stale,
+            MessageSource::DbMissAbsent => &self.db_miss_absent,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 获取当前计数快照
+    pub fn snapshot(&self) -> MessageSourceStats {
+        MessageSourceStats {
+            db_hit: self.db_hit.load(Ordering::Relaxed),
+            db_miss_stale: self.db_miss_stale.load(Ordering::Relaxed),
+            db_miss_absent: self.db_miss_absent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 耗时直方图分桶上限（微秒），最后一个桶收集超出上限的部分
+pub const HANDLER_DURATION_BUCKETS_US: [u64; 5] = [1_000, 10_000, 100_000, 1_000_000, 5_000_000];
+
+/// 单个事件处理器的耗时统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandlerStats {
+    /// 调用次数
+    pub count: u64,
+    /// 失败次数
+    pub failures: u64,
+    /// 累计耗时（微秒）
+    pub total_us: u64,
+    /// 最长耗时（微秒）
+    pub max_us: u64,
+    /// 各分桶的调用次数（比 `HANDLER_DURATION_BUCKETS_US` 多一个溢出桶）
+    pub buckets: Vec<u64>,
+}
+
+impl HandlerStats {
+    fn record(&mut self, duration_us: u64, success: bool) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; HANDLER_DURATION_BUCKETS_US.len() + 1];
+        }
+        let bucket = HANDLER_DURATION_BUCKETS_US
+            .iter()
+            .position(|&limit| duration_us <= limit)
+            .unwrap_or(HANDLER_DURATION_BUCKETS_US.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        if !success {
+            self.failures += 1;
+        }
+        self.total_us = self.total_us.saturating_add(duration_us);
+        self.max_us = self.max_us.max(duration_us);
+    }
+}
+
+/// 事件处理器耗时统计（线程安全）
+#[derive(Debug, Default)]
+pub struct HandlerMetrics {
+    stats: Mutex<HashMap<String, HandlerStats>>,
+}
+
+impl HandlerMetrics {
+    /// 记录一次处理
+    pub fn record(&self, event: &str, duration: Duration, success: bool) {
+        let duration_us = duration.as_micros().min(u64::MAX as u128) as u64;
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        match stats.get_mut(event) {
+            Some(entry) => entry.record(duration_us, success),
+            None => {
+                let mut entry = HandlerStats::default();
+                entry.record(duration_us, success);
+                stats.insert(event.to_string(), entry);
+            }
+        }
+    }
+
+    /// 获取当前统计快照（按事件名排序）
+    pub fn snapshot(&self) -> BTreeMap<String, HandlerStats> {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+}
+
+impl RuntimeDiagnostics {
+    /// 报告中连接状态之后的运行时行（事件、Redis、统计）
+    fn rows(&self) -> [(&'static str, String); 5] {
+        let redis = match self.redis_connected {
+            Some(true) => "connected",
+            Some(false) => "disconnected",
+            None => "disabled",
+        };
+
+        let unknown_events = if self.unknown_events.is_empty() {
+            "none".to_string()
+        } else {
+            let mut names: Vec<&str> = self.unknown_events.iter().map(|e| e.name.as_str()).collect();
+            names.sort_unstable();
+            names.dedup();
+            format!("{} ({})", self.unknown_events.len(), names.join(", "))
+        };
+
+        let slowest_handlers = if self.handler_metrics.is_empty() {
+            "none".to_string()
+        } else {
+            let mut handlers: Vec<(&String, &HandlerStats)> = self.handler_metrics.iter().collect();
+            handlers.sort_by(|a, b| b.1.max_us.cmp(&a.1.max_us));
+            handlers
+                .iter()
+                .take(3)
+                .map(|(name, stats)| format!("{} max={}ms", name, stats.max_us / 1000))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        [
+            ("Last event", self.last_event_at.as_deref().unwrap_or("n/a").to_string()),
+            ("Redis", redis.to_string()),
+            (
+                "Message source",
+                format!(
+                    "db_hit={} db_miss_stale={} db_miss_absent={}",
+                    self.session_message_source.db_hit,
+                    self.session_message_source.db_miss_stale,
+                    self.session_message_source.db_miss_absent,
+                ),
+            ),
+            ("Unknown events", unknown_events),
+            ("Slowest handlers", slowest_handlers),
+        ]
+    }
+}
+
+impl fmt::Display for DiagnosticReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_na(value: Option<&str>) -> &str {
+            value.unwrap_or("n/a")
+        }
+
+        let mut rows = vec![
+            ("Version", self.version.clone()),
+            ("Hostname", self.hostname.clone()),
+        ];
+        match &self.runtime {
+            Some(runtime) => {
+                rows.extend([
+                    ("Connected", runtime.connected.to_string()),
+                    ("Server URL", or_na(runtime.server_url.as_deref()).to_string()),
+                    ("Watching sessions", runtime.watching_session_count.to_string()),
+                    ("Pending approvals", runtime.pending_approval_count.to_string()),
+                ]);
+            }
+            None => {
+                rows.extend(
+                    ["Connected", "Server URL", "Watching sessions", "Pending approvals"]
+                        .map(|key| (key, "n/a".to_string())),
+                );
+            }
+        }
+        rows.extend([
+            ("Shared DB role", or_na(self.shared_db_role.as_deref()).to_string()),
+            ("Projects", self.project_count.to_string()),
+            ("Sessions", self.session_count.to_string()),
+        ]);
+        match &self.runtime {
+            Some(runtime) => rows.extend(runtime.rows()),
+            None => rows.extend(
+                ["Last event", "Redis", "Message source", "Unknown events", "Slowest handlers"]
+                    .map(|key| (key, "n/a".to_string())),
+            ),
+        }
+
+        let width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        for (key, value) in rows {
+            writeln!(f, "{:<width$}  {}", key, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_serialize_and_display() {
+        let mut report = DiagnosticReport {
+            version: "0.1.0".to_string(),
+            hostname: "mac".to_string(),
+            shared_db_role: None,
+            project_count: 3,
+            session_count: 10,
+            runtime: Some(RuntimeDiagnostics {
+                connected: true,
+                server_url: Some("https://localhost:10005".to_string()),
+                watching_session_count: 2,
+                pending_approval_count: 0,
+                last_event_at: None,
+                redis_connected: None,
+                session_message_source: MessageSourceStats::default(),
+                unknown_events: vec![
+                    UnknownEvent { name: "server:b".to_string(), data: serde_json::Value::Null },
+                    UnknownEvent { name: "server:a".to_string(), data: serde_json::Value::Null },
+                    UnknownEvent { name: "server:b".to_string(), data: serde_json::Value::Null },
+                ],
+                handler_metrics: BTreeMap::new(),
+            }),
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["watchingSessionCount"], 2);
+        assert_eq!(json["serverUrl"], "https://localhost:10005");
+        assert!(json["sharedDbRole"].is_null());
+
+        let table = report.to_string();
+        assert!(table.contains("Server URL         https://localhost:10005"));
+        assert!(table.contains("Shared DB role     n/a"));
+        assert!(table.contains("Redis              disabled"));
+        assert!(table.contains("Message source     db_hit=0 db_miss_stale=0 db_miss_absent=0"));
+        assert!(table.contains("Unknown events     3 (server:a, server:b)"));
+        assert_eq!(json["unknownEvents"][0]["name"], "server:b");
+        assert!(table.contains("Slowest handlers   none"));
+
+        // 没有运行时状态时不报告虚假的值
+        report.runtime = None;
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json.get("connected").is_none());
+        assert_eq!(json["projectCount"], 3);
+        let table = report.to_string();
+        assert!(table.contains("Connected          n/a"));
+        assert!(table.contains("Watching sessions  n/a"));
+        assert!(table.contains("Unknown events     n/a"));
+    }
+
+    #[test]
+    fn test_handler_metrics() {
+        let metrics = HandlerMetrics::default();
+        metrics.record("server:sendMessage", Duration::from_micros(500), true);
+        metrics.record("server:sendMessage", Duration::from_millis(20), false);
+        metrics.record("server:sendMessage", Duration::from_secs(6), true);
+
+        let snapshot = metrics.snapshot();
+        let stats = &snapshot["server:sendMessage"];
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.max_us, 6_000_000);
+        assert_eq!(stats.buckets, vec![1, 0, 1, 0, 0, 1]);
+
+        let json = serde_json::to_value(stats).unwrap();
+        assert_eq!(json["maxUs"], 6_000_000);
+    }
+
+    #[test]
+    fn test_message_source_counters() {
+        let counters = MessageSourceCounters::default();
+        counters.record(MessageSource::DbHit);
+        counters.record(MessageSource::DbHit);
+        counters.record(MessageSource::DbMissAbsent);
+
+        let stats = counters.snapshot();
+        assert_eq!(
+            stats,
+            MessageSourceStats { db_hit: 2, db_miss_stale: 0, db_miss_absent: 1 }
+        );
+
+        let json = serde_json::to_value(stats).unwrap();
+        assert_eq!(json["dbHit"], 2);
+        assert_eq!(json["dbMissStale"], 0);
+    }
+}
@@ -0,0 +1,166 @@
+//! 会话导出（可移植备份）
+//!
+//! 所有会话写入同一个 JSONL 文件：每个会话先写一行元数据（`"type":"vlaude-session"`），
+//! 紧跟该会话的原始消息行。会话按文件修改时间排序，消息保持文件中的顺序，输出可以直接 diff。
+
+use anyhow::Result;
+use serde::Serialize;
+use session_reader::{ClaudeReader, Order};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// 会话元数据行的 `type`
+pub(crate) const SESSION_HEADER_TYPE: &str = "vlaude-session";
+
+/// 会话导出结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    /// 导出的会话数
+    pub sessions: usize,
+    /// 导出的消息数
+    pub messages: usize,
+    /// 写入的字节数
+    pub bytes_written: u64,
+}
+
+/// 会话元数据行
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionHeader<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    session_id: &'a str,
+    project_path: &'a str,
+    file_mtime: Option<u64>,
+    message_count: usize,
+}
+
+/// 导出会话到单个 JSONL 文件（`project_path` 为 None 时导出所有项目，目标文件已存在时覆盖）
+pub fn export_sessions(
+    reader: &mut ClaudeReader,
+    project_path: Option<&str>,
+    out_path: &Path,
+) -> Result<ExportReport> {
+    let mut sessions = reader.list_sessions(project_path, false, None)?;
+    sessions.sort_by(|a, b| a.file_mtime.cmp(&b.file_mtime).then_with(|| a.id.cmp(&b.id)));
+
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::create(out_path)
+        .map_err(|e| anyhow::anyhow!("无法创建 {:?}: {}", out_path, e))?;
+    let mut writer = BufWriter::new(file);
+    let mut report = ExportReport::default();
+    let mut write_line = |line: &str| -> Result<()> {
+        writeln!(writer, "{}", line)?;
+        report.bytes_written += line.len() as u64 + 1;
+        Ok(())
+    };
+
+    let mut sessions_written = 0;
+    let mut messages_written = 0;
+    for meta in &sessions {
+        let Some(path) = reader.get_session_path(&meta.id) else {
+            continue;
+        };
+        let messages: Vec<String> = reader.iter_messages(&path, Order::Asc)?.collect();
+
+        let header = SessionHeader {
+            kind: SESSION_HEADER_TYPE,
+            session_id: &meta.id,
+            project_path: &meta.project_path,
+            file_mtime: meta.file_mtime,
+            message_count: messages.len(),
+        };
+        write_line(&serde_json::to_string(&header)?)?;
+        for message in &messages {
+            write_line(message)?;
+        }
+        sessions_written += 1;
+        messages_written += messages.len();
+    }
+    writer.flush()?;
+
+    report.sessions = sessions_written;
+    report.messages = messages_written;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_session(project_dir: &Path, session_id: &str, mtime_ms: u64, lines: &[&str]) {
+        let mut file = std::fs::File::create(project_dir.join(format!("{}.jsonl", session_id)))
+            .unwrap();
+        file.write_all((lines.join("\n") + "\n").as_bytes()).unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_millis(mtime_ms))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        write_session(
+            &project_dir,
+            "newer",
+            2_000_000,
+            &[
+                r#"{"type":"user","uuid":"n1","sessionId":"newer","cwd":"/tmp/demo"}"#,
+                r#"{"type":"summary","summary":"skipped"}"#,
+                r#"{"type":"assistant","uuid":"n2","sessionId":"newer","cwd":"/tmp/demo"}"#,
+            ],
+        );
+        write_session(
+            &project_dir,
+            "older",
+            1_000_000,
+            &[r#"{"type":"user","uuid":"o1","sessionId":"older","cwd":"/tmp/demo"}"#],
+        );
+
+        let mut reader = ClaudeReader::new(dir.path().join("projects"));
+        let out_path = dir.path().join("backup/export.jsonl");
+        let report = export_sessions(&mut reader, None, &out_path).unwrap();
+        let content = std::fs::read_to_string(&out_path).unwrap();
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.messages, 3);
+        assert_eq!(report.bytes_written, content.len() as u64);
+
+        // 输出是确定的
+        let again = export_sessions(&mut reader, Some("/tmp/demo"), &out_path).unwrap();
+        assert_eq!(again, report);
+        assert_eq!(std::fs::read_to_string(&out_path).unwrap(), content);
+
+        // 按元数据行拆回会话文件，再用 ClaudeReader 读取
+        let restored_dir = dir.path().join("restored/-tmp-demo");
+        std::fs::create_dir_all(&restored_dir).unwrap();
+        let mut order = Vec::new();
+        let mut current: Option<std::fs::File> = None;
+        for line in content.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            if value["type"] == SESSION_HEADER_TYPE {
+                let session_id = value["sessionId"].as_str().unwrap();
+                order.push(session_id.to_string());
+                current = Some(
+                    std::fs::File::create(restored_dir.join(format!("{}.jsonl", session_id)))
+                        .unwrap(),
+                );
+            } else {
+                writeln!(current.as_mut().unwrap(), "{}", line).unwrap();
+            }
+        }
+        assert_eq!(order, ["older", "newer"]);
+
+        let mut restored = ClaudeReader::new(dir.path().join("restored"));
+        for session_id in &order {
+            let original = reader.get_session_path(session_id).unwrap();
+            let copy = restored.get_session_path(session_id).unwrap();
+            let original: Vec<_> = reader.iter_messages(&original, Order::Asc).unwrap().collect();
+            let copy: Vec<_> = restored.iter_messages(&copy, Order::Asc).unwrap().collect();
+            assert_eq!(original, copy);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! 索引状态持久化
+//!
+//! 记录每个会话最后一次推送时的文件修改时间（session_id → mtime），
+//! 启动时跳过未变化的会话，减少重复推送。
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// 会话索引状态
+pub(crate) type IndexState = HashMap<String, u64>;
+
+/// 默认状态文件路径：<Claude 配置目录>/.vlaude-index-state.json
+///
+/// 与会话读取使用同一个配置目录（`CLAUDE_CONFIG_DIR` 或 `~/.claude`）。
+pub(crate) fn default_state_path() -> Option<PathBuf> {
+    session_reader::claude_config_dir()
+        .map(|dir| dir.join(".vlaude-index-state.json"))
+        .map_err(|e| warn!("[IndexState] 无法确定状态文件路径: {}", e))
+        .ok()
+}
+
+/// 加载状态（文件不存在或损坏时返回空状态）
+pub(crate) fn load(path: &Path) -> IndexState {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return IndexState::new(),
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        warn!("[IndexState] 状态文件损坏，忽略: {:?}: {}", path, e);
+        IndexState::new()
+    })
+}
+
+/// 保存状态（先写临时文件再重命名，避免写入中断导致文件损坏）
+pub(crate) fn save(path: &Path, state: &IndexState) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec(state)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+
+        assert!(load(&path).is_empty());
+
+        let mut state = IndexState::new();
+        state.insert("session-a".to_string(), 1_700_000_000_000);
+        save(&path, &state).unwrap();
+
+        assert_eq!(load(&path), state);
+
+        std::fs::write(&path, "{corrupt").unwrap();
+        assert!(load(&path).is_empty());
+    }
+}
@@ -2,13 +2,26 @@
 //!
 //! 整合 session-reader 和 socket-client，实现完整的 daemon 功能
 
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
 mod service;
 mod watcher;
 mod shared_db;
+mod index_state;
+mod diagnostics;
+mod description;
+mod export;
+mod process;
+mod resume;
+mod telemetry;
 
 pub use service::{
     DaemonService,
     ApprovalResult,
+    BulkSyncReport,
+    BulkSyncProgressCallback,
+    AsyncMobileViewingCallback,
     MobileViewingCallback,
     ResumeLocalCallback,
     WatchNewSessionCallback,
@@ -17,5 +30,14 @@ pub use service::{
     ServerCommandCallback,
 };
 
-pub use watcher::{SessionWatcher, SessionWatchEvent};
-pub use shared_db::SharedDbAdapter;
+// sync_callback! 宏内部使用
+#[doc(hidden)]
+pub use futures as __futures;
+
+pub use watcher::{ProjectWatcher, SessionWatcher, SessionWatchEvent};
+pub use shared_db::{DbFreshness, SharedDbAdapter};
+pub use diagnostics::{DiagnosticReport, HandlerStats, MessageSourceStats, RuntimeDiagnostics};
+pub use export::{export_sessions, ExportReport};
+pub use process::ClaudeProcessDetector;
+pub use description::{DefaultDescriptionFormatter, DescriptionFormatter};
+pub use telemetry::{describe_metrics, MetricsMiddleware};
@@ -0,0 +1,146 @@
+//! Claude Code 进程检测
+//!
+//! 用于在 Claude Code 运行时加快会话文件轮询，空闲时放慢轮询以降低 CPU 占用。
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::System;
+
+/// 检测结果缓存时间（刷新进程列表开销较大）
+const DETECTION_CACHE_TTL: Duration = Duration::from_secs(2);
+
+/// Claude Code 进程检测器
+pub struct ClaudeProcessDetector {
+    state: Arc<DetectorState>,
+}
+
+struct DetectorState {
+    system: Mutex<System>,
+    /// 最近一次检测结果（检测前视为运行中）
+    running: AtomicBool,
+    /// 最近一次检测时间
+    checked_at: Mutex<Option<Instant>>,
+    /// 是否有后台刷新正在进行
+    refreshing: AtomicBool,
+}
+
+impl DetectorState {
+    fn is_stale(&self) -> bool {
+        self.checked_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none_or(|t| t.elapsed() >= DETECTION_CACHE_TTL)
+    }
+
+    /// 刷新进程列表并更新检测结果（阻塞）
+    fn refresh(&self) -> bool {
+        let running = {
+            let mut system = self.system.lock().unwrap_or_else(|e| e.into_inner());
+            system.refresh_processes();
+            system
+                .processes()
+                .values()
+                .any(|p| is_claude_process(p.name(), p.cmd()))
+        };
+        self.running.store(running, Ordering::SeqCst);
+        *self.checked_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+        running
+    }
+}
+
+impl ClaudeProcessDetector {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(DetectorState {
+                system: Mutex::new(System::new()),
+                running: AtomicBool::new(true),
+                checked_at: Mutex::new(None),
+                refreshing: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// 是否有 Claude Code 进程在运行（结果缓存 2 秒）
+    ///
+    /// 缓存过期时同步刷新进程列表，会阻塞当前线程；异步代码中使用 `is_running_cached`。
+    pub fn is_running(&self) -> bool {
+        if self.state.is_stale() {
+            return self.state.refresh();
+        }
+        self.state.running.load(Ordering::SeqCst)
+    }
+
+    /// 最近一次检测结果（不阻塞）
+    ///
+    /// 缓存过期时在 `spawn_blocking` 中刷新，本次返回上一次的结果；
+    /// 不在 tokio 运行时中时同步刷新。
+    pub fn is_running_cached(&self) -> bool {
+        if self.state.is_stale() && !self.state.refreshing.swap(true, Ordering::SeqCst) {
+            let state = self.state.clone();
+            let refresh = move || {
+                state.refresh();
+                state.refreshing.store(false, Ordering::SeqCst);
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => drop(handle.spawn_blocking(refresh)),
+                Err(_) => refresh(),
+            }
+        }
+        self.state.running.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for ClaudeProcessDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 判断进程是否为 Claude Code
+///
+/// Claude Code 可能以 `claude` 二进制运行，也可能是 `node /path/to/claude ...`。
+fn is_claude_process(name: &str, cmd: &[String]) -> bool {
+    let is_claude = |s: &str| {
+        Path::new(s)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n == "claude")
+    };
+
+    is_claude(name) || cmd.iter().take(2).any(|arg| is_claude(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_claude_process() {
+        assert!(is_claude_process("claude", &[]));
+        assert!(is_claude_process(
+            "node",
+            &["node".to_string(), "/usr/local/bin/claude".to_string()]
+        ));
+        assert!(!is_claude_process("claude-helper", &[]));
+        assert!(!is_claude_process(
+            "node",
+            &["node".to_string(), "server.js".to_string(), "claude".to_string()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_is_running_cached_refreshes_in_background() {
+        let detector = ClaudeProcessDetector::new();
+        // 检测前视为运行中，刷新在后台进行
+        assert!(detector.is_running_cached());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while detector.state.is_stale() {
+            assert!(Instant::now() < deadline, "background refresh did not finish");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!detector.state.refreshing.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,170 @@
+//! 通过 Claude CLI 恢复已有会话
+//!
+//! 运行 `claude --resume {session_id}`（工作目录为项目路径），从 stream-json 输出的
+//! 第一行 JSON 中读取会话信息，之后 CLI 继续在后台运行直到本轮对话结束。
+
+use anyhow::{bail, Result};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// 等待 CLI 输出会话信息的超时时间
+const RESUME_OUTPUT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 恢复后的会话信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ResumedSession {
+    pub session_id: String,
+    pub transcript_path: Option<String>,
+    pub encoded_dir_name: Option<String>,
+}
+
+impl ResumedSession {
+    /// 从 CLI 输出的一行 JSON 中解析（没有 session_id 时返回 None）
+    fn from_output_line(line: &str) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        let field = |snake: &str, camel: &str| {
+            value
+                .get(snake)
+                .or_else(|| value.get(camel))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        Some(Self {
+            session_id: field("session_id", "sessionId")?,
+            transcript_path: field("transcript_path", "transcriptPath"),
+            encoded_dir_name: field("encoded_dir_name", "encodedDirName"),
+        })
+    }
+}
+
+/// 启动 `claude --resume` 并等待会话信息
+///
+/// prompt 作为本轮输入（`--print` 模式，放在 `--` 之后避免被当作参数），
+/// CLI 在后台跑完后自行退出。`session_id` 必须是 UUID。
+pub(crate) async fn resume_session(
+    claude_command: &str,
+    session_id: &str,
+    project_path: &str,
+    prompt: &str,
+) -> Result<ResumedSession> {
+    if uuid::Uuid::try_parse(session_id).is_err() {
+        bail!("Invalid session_id: {:?} is not a UUID", session_id);
+    }
+    if prompt.trim().is_empty() {
+        bail!("A prompt is required to resume a session");
+    }
+
+    let mut command = Command::new(claude_command);
+    command
+        .arg("--resume")
+        .arg(session_id)
+        .args(["--print", "--output-format", "stream-json", "--verbose", "--"])
+        .arg(prompt);
+    if !project_path.is_empty() {
+        command.current_dir(project_path);
+    }
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", claude_command, e))?;
+    let Some(stdout) = child.stdout.take() else {
+        bail!("Failed to capture {} output", claude_command);
+    };
+    let mut lines = BufReader::new(stdout).lines();
+
+    let read_session = async {
+        while let Some(line) = lines.next_line().await? {
+            if let Some(session) = ResumedSession::from_output_line(&line) {
+                return Ok(Some(session));
+            }
+            debug!("Skipping resume output line: {}", line);
+        }
+        Ok::<_, std::io::Error>(None)
+    };
+    let session = match tokio::time::timeout(RESUME_OUTPUT_TIMEOUT, read_session).await {
+        Ok(Ok(Some(session))) => session,
+        Ok(Ok(None)) => {
+            let status = child.wait().await?;
+            bail!("{} exited without session info ({})", claude_command, status);
+        }
+        Ok(Err(e)) => bail!("Failed to read {} output: {}", claude_command, e),
+        Err(_) => {
+            let _ = child.kill().await;
+            bail!("Timed out waiting for {} to resume session", claude_command);
+        }
+    };
+
+    // 继续读完输出，避免管道写满阻塞 CLI
+    tokio::spawn(async move {
+        while let Ok(Some(_)) = lines.next_line().await {}
+        if let Err(e) = child.wait().await {
+            warn!("Resumed session process failed: {}", e);
+        }
+    });
+
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: &str = "5f0c6a4e-9d1b-4c1e-8f3a-2b7d9e6c1a40";
+
+    #[test]
+    fn test_from_output_line() {
+        let session = ResumedSession::from_output_line(
+            r#"{"type":"system","subtype":"init","session_id":"abc","cwd":"/tmp/demo"}"#,
+        )
+        .unwrap();
+        assert_eq!(session.session_id, "abc");
+        assert_eq!(session.transcript_path, None);
+
+        let session = ResumedSession::from_output_line(
+            r#"{"sessionId":"abc","transcriptPath":"/p/-tmp-demo/abc.jsonl","encodedDirName":"-tmp-demo"}"#,
+        )
+        .unwrap();
+        assert_eq!(session.transcript_path.as_deref(), Some("/p/-tmp-demo/abc.jsonl"));
+        assert_eq!(session.encoded_dir_name.as_deref(), Some("-tmp-demo"));
+
+        assert!(ResumedSession::from_output_line("Resuming...").is_none());
+        assert!(ResumedSession::from_output_line(r#"{"type":"system"}"#).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_session_rejects_unsafe_args() {
+        let error = resume_session("claude", "--dangerously-skip-permissions", "", "hi")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not a UUID"));
+
+        let error = resume_session("claude", SESSION_ID, "", " ").await.unwrap_err();
+        assert!(error.to_string().contains("prompt"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_resume_session_reads_first_session_line() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("claude");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\necho starting\n[ \"$7\" = \"--\" ] && [ \"$8\" = \"--verbose\" ] || exit 1\necho \"{\\\"type\\\":\\\"system\\\",\\\"session_id\\\":\\\"$2\\\"}\"\n",
+        )
+        .unwrap();
+        let mut permissions = std::fs::metadata(&script).unwrap().permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o755);
+        std::fs::set_permissions(&script, permissions).unwrap();
+
+        let session = resume_session(script.to_str().unwrap(), SESSION_ID, "", "--verbose")
+            .await
+            .unwrap();
+        assert_eq!(session.session_id, SESSION_ID);
+    }
+}
@@ -1,19 +1,36 @@
 //! Daemon 服务实现
 
-use crate::watcher::{SessionWatcher, SessionWatchEvent};
+use crate::description::{DefaultDescriptionFormatter, DescriptionFormatter};
+use crate::diagnostics::{
+    count_projects_and_sessions, DiagnosticReport, HandlerMetrics, MessageSource,
+    MessageSourceCounters, RuntimeDiagnostics,
+};
+use crate::export::{self, ExportReport};
+use crate::index_state;
+use crate::resume;
+use crate::watcher::{ProjectWatcher, SessionWatcher, SessionWatchEvent};
+use crate::shared_db::{read_jsonl_tail, DbFreshness};
+use crate::telemetry::{self, MetricsMiddleware};
 use crate::SharedDbAdapter;
 use anyhow::{bail, Result};
+use claude_session_db::coordination::WriterHealth;
+use futures::future::BoxFuture;
+use serde::Serialize;
 use session_reader::ClaudeReader;
 use socket_client::{
-    RegisterData, ServiceRegistry, ServiceRegistryConfig, SocketClient,
-    SocketConfig, TlsConfig,
+    ContextMetrics, ReconnectPolicy, RegisterData, ServerEvent, ServiceRegistry,
+    ServiceRegistryConfig, SessionMetadataPayload, SocketClient, SocketConfig, SocketError,
+    TlsConfig, UnknownEvent,
 };
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use tokio::sync::{oneshot, RwLock};
-use tokio::time::Duration;
-use tracing::{debug, error, info, warn};
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 /// 验证路径组件是否安全（防止路径穿越）
 fn validate_path_component(s: &str, name: &str) -> Result<()> {
@@ -26,23 +43,54 @@ fn validate_path_component(s: &str, name: &str) -> Result<()> {
     Ok(())
 }
 
-/// Mobile 查看状态回调类型
-pub type MobileViewingCallback = Arc<dyn Fn(&str, bool) + Send + Sync>;
-
-/// 恢复本地模式回调类型
-pub type ResumeLocalCallback = Arc<dyn Fn(&str) + Send + Sync>;
-
-/// 监听新会话回调类型
-pub type WatchNewSessionCallback = Arc<dyn Fn(&str, &str) + Send + Sync>;
+// 回调均为异步：返回的 future 会被 await，同步闭包可用 `sync_callback!` 包装
 
-/// 查找新会话回调类型
-pub type FindNewSessionCallback = Arc<dyn Fn(&str, &str) + Send + Sync>;
+/// Mobile 查看状态回调类型 (session_id, is_viewing)
+///
+/// 返回 true 表示已处理；返回 false 时 Daemon 回复 `daemon:mobileViewingAck`（success: false）。
+pub type AsyncMobileViewingCallback =
+    Arc<dyn Fn(String, bool) -> BoxFuture<'static, bool> + Send + Sync>;
 
-/// 会话发现回调类型
-pub type SessionDiscoveredCallback = Arc<dyn Fn(&str, &str) + Send + Sync>;
-
-/// 服务器命令回调类型
-pub type ServerCommandCallback = Arc<dyn Fn(&str, Option<serde_json::Value>) + Send + Sync>;
+/// Mobile 查看状态回调类型
+pub type MobileViewingCallback = AsyncMobileViewingCallback;
+
+/// 恢复本地模式回调类型 (session_id)
+pub type ResumeLocalCallback = Arc<dyn Fn(String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 监听新会话回调类型 (client_id, project_path)
+pub type WatchNewSessionCallback =
+    Arc<dyn Fn(String, String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 查找新会话回调类型 (client_id, project_path)
+pub type FindNewSessionCallback =
+    Arc<dyn Fn(String, String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 会话发现回调类型 (project_path, session_id)
+pub type SessionDiscoveredCallback =
+    Arc<dyn Fn(String, String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 服务器命令回调类型 (command, data)
+pub type ServerCommandCallback =
+    Arc<dyn Fn(String, Option<serde_json::Value>) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 批量同步进度回调类型 (已处理会话数, 会话总数)
+pub type BulkSyncProgressCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+/// 将同步闭包包装为异步回调（闭包的返回值即回调结果）
+///
+/// 闭包参数需要标注类型，例如：
+/// `sync_callback!(|session_id: String, is_viewing: bool| { println!("{session_id}: {is_viewing}"); true })`
+#[macro_export]
+macro_rules! sync_callback {
+    (move |$($arg:ident : $ty:ty),* $(,)?| $body:expr) => {
+        $crate::sync_callback!(|$($arg: $ty),*| $body)
+    };
+    (|$($arg:ident : $ty:ty),* $(,)?| $body:expr) => {
+        ::std::sync::Arc::new(move |$($arg: $ty),*| {
+            $crate::__futures::FutureExt::boxed($crate::__futures::future::ready($body))
+        })
+    };
+}
 
 /// 权限审批结果
 #[derive(Debug, Clone)]
@@ -51,9 +99,106 @@ pub struct ApprovalResult {
     pub reason: Option<String>,
 }
 
+/// 历史会话批量同步结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkSyncReport {
+    /// 涉及的项目数
+    pub projects: usize,
+    /// 成功同步的会话数
+    pub sessions: usize,
+    /// 新插入的消息数
+    pub messages: usize,
+    /// 同步失败的会话数
+    pub failed: usize,
+    /// 失败原因（最多保留 `BULK_SYNC_MAX_ERRORS` 条）
+    pub errors: Vec<String>,
+}
+
+/// 批量同步报告中保留的失败原因条数
+const BULK_SYNC_MAX_ERRORS: usize = 20;
+
 /// 等待中的权限请求
+///
+/// 重连后重放的同一请求不会重复发送，而是加入等待同一个结果。
 struct PendingApproval {
-    tx: oneshot::Sender<ApprovalResult>,
+    waiters: Vec<oneshot::Sender<ApprovalResult>>,
+}
+
+impl PendingApproval {
+    /// 把结果发给所有等待方
+    fn resolve(self, result: ApprovalResult) {
+        for tx in self.waiters {
+            let _ = tx.send(result.clone());
+        }
+    }
+}
+
+/// 新消息合并窗口：同一会话在窗口内的消息合并为一次 daemon:newMessage
+const NEW_MESSAGE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// 收到 server-shutdown 后等待 Server 重启的默认时间（秒）
+const DEFAULT_SERVER_RESTART_DELAY_SECS: u64 = 5;
+
+/// Reader 检查共享 DB Writer 健康状态的间隔
+const WRITER_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 接管共享 DB Writer 前的默认宽限期（秒）
+const DEFAULT_TAKEOVER_GRACE_PERIOD_SECS: u64 = 15;
+
+/// 已发送权限请求的默认去重窗口（秒）
+const DEFAULT_APPROVAL_DEDUP_WINDOW_SECS: u64 = 120;
+
+/// 保留的无法识别事件数量
+const UNKNOWN_EVENTS_CAPACITY: usize = 100;
+
+/// 注册 ack 超时后的最大尝试次数
+const REGISTER_MAX_ATTEMPTS: u32 = 3;
+
+/// 默认的最大同时监听会话数，超过后新会话以降级模式（只轮询）监听
+const DEFAULT_MAX_WATCHING_SESSIONS: usize = 200;
+
+/// 恢复会话时运行的 Claude CLI
+const CLAUDE_COMMAND: &str = "claude";
+
+/// 事件处理超过该时长时记录警告
+const SLOW_HANDLER_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// 有处理分支的事件名（用作统计标签，其余事件记为 `other`）
+const HANDLED_EVENTS: &[&str] = &[
+    "server:requestProjectData",
+    "server:requestSessionMetadata",
+    "server:requestSessionMessages",
+    "server:startWatching",
+    "server:stopWatching",
+    "server:mobileViewing",
+    "server:resumeLocal",
+    "server:watchNewSession",
+    "server:findNewSession",
+    "server:sessionDiscovered",
+    "server:approvalResponse",
+    "server:command",
+    "server:createSession",
+    "server:checkLoading",
+    "server:sendMessage",
+    "server-shutdown",
+    "__disconnected",
+];
+
+/// 事件的统计标签
+fn event_label(event: &str) -> &'static str {
+    HANDLED_EVENTS
+        .iter()
+        .find(|name| **name == event)
+        .copied()
+        .unwrap_or("other")
+}
+
+/// 待发送的新消息批次
+struct PendingMessages {
+    messages: Vec<serde_json::Value>,
+    /// 批次中第一条消息的时间
+    first_at: Instant,
 }
 
 /// Daemon 服务
@@ -68,8 +213,6 @@ pub struct DaemonService {
     hostname: String,
     /// TLS 配置
     tls_config: TlsConfig,
-    /// 服务注册中心
-    registry: Option<Arc<ServiceRegistry>>,
     /// 当前连接的 Server 地址
     current_server: Arc<RwLock<Option<String>>>,
     /// Mobile 查看状态回调
@@ -86,10 +229,46 @@ pub struct DaemonService {
     server_command_callback: Arc<RwLock<Option<ServerCommandCallback>>>,
     /// 等待中的权限请求
     pending_approvals: Arc<RwLock<HashMap<String, PendingApproval>>>,
+    /// 已发送的权限请求（request_id → 发送时间），避免重连后重复发送
+    sent_approval_requests: Arc<RwLock<HashMap<String, Instant>>>,
+    /// 权限请求去重窗口（秒），重连时清理超过窗口的记录
+    approval_dedup_window_secs: u64,
+    /// 最大同时监听会话数
+    max_watching_sessions: usize,
+    /// 权限请求描述生成器
+    description_formatter: Arc<RwLock<Arc<dyn DescriptionFormatter>>>,
     /// 会话监听器
     session_watcher: Arc<SessionWatcher>,
+    /// 项目监听器（project_path → 监听器），覆盖所有有被监听会话的项目
+    project_watchers: Arc<RwLock<HashMap<String, ProjectWatcher>>>,
     /// 共享数据库适配器
     shared_db: Option<Arc<SharedDbAdapter>>,
+    /// 关闭信号（与事件循环共用）
+    shutdown: CancellationToken,
+    /// 已推送会话的文件修改时间（session_id → mtime）
+    last_indexed_at: Arc<RwLock<HashMap<String, u64>>>,
+    /// 索引状态持久化路径
+    index_state_path: Option<PathBuf>,
+    /// 待合并发送的新消息（session_id → 批次）
+    pending_messages: Arc<RwLock<HashMap<String, PendingMessages>>>,
+    /// 最后一次收到 Server 事件的时间
+    last_event_at: Arc<RwLock<Option<chrono::DateTime<chrono::Utc>>>>,
+    /// 会话消息请求的数据来源统计
+    message_source: Arc<MessageSourceCounters>,
+    /// 收到 server-shutdown 后等待多久再主动重连（Server 可在事件中覆盖）
+    server_restart_delay_secs: u64,
+    /// 计划的主动重连时间（收到 server-shutdown 后设置）
+    reconnect_at: Arc<RwLock<Option<Instant>>>,
+    /// 发现 Writer 失效后等待多久再接管（期间原 Writer 恢复则放弃）
+    takeover_grace_period_secs: u64,
+    /// 最近收到的无法识别的 Server 事件（环形缓冲）
+    unknown_events: Arc<RwLock<VecDeque<UnknownEvent>>>,
+    /// 事件处理器耗时统计
+    handler_metrics: Arc<HandlerMetrics>,
+    /// 断线重连退避策略
+    reconnect_policy: ReconnectPolicy,
+    /// 连续重连失败次数（重连成功后清零）
+    reconnect_attempt: Arc<AtomicU32>,
 }
 
 impl DaemonService {
@@ -100,6 +279,9 @@ impl DaemonService {
 
     /// 创建服务（带 TLS 配置）
     pub fn with_tls(socket_url: &str, hostname: &str, tls: TlsConfig) -> Result<Self> {
+        // 启动时校验证书，避免首次连接时才失败
+        tls.validate()?;
+
         let config = SocketConfig {
             url: socket_url.to_string(),
             tls: tls.clone(),
@@ -118,13 +300,20 @@ impl DaemonService {
             }
         };
 
+        let index_state_path = index_state::default_state_path();
+        let last_indexed_at = index_state_path
+            .as_deref()
+            .map(index_state::load)
+            .unwrap_or_default();
+
         Ok(Self {
-            socket: Arc::new(RwLock::new(SocketClient::new(config))),
+            socket: Arc::new(RwLock::new(
+                SocketClient::new(config).with_middleware(Arc::new(MetricsMiddleware)),
+            )),
             reader: Arc::new(RwLock::new(ClaudeReader::default()?)),
             watching_sessions: Arc::new(RwLock::new(HashSet::new())),
             hostname: hostname.to_string(),
             tls_config: tls,
-            registry: None,
             current_server: Arc::new(RwLock::new(Some(socket_url.to_string()))),
             mobile_viewing_callback: Arc::new(RwLock::new(None)),
             resume_local_callback: Arc::new(RwLock::new(None)),
@@ -133,8 +322,26 @@ impl DaemonService {
             session_discovered_callback: Arc::new(RwLock::new(None)),
             server_command_callback: Arc::new(RwLock::new(None)),
             pending_approvals: Arc::new(RwLock::new(HashMap::new())),
+            sent_approval_requests: Arc::new(RwLock::new(HashMap::new())),
+            approval_dedup_window_secs: DEFAULT_APPROVAL_DEDUP_WINDOW_SECS,
+            max_watching_sessions: DEFAULT_MAX_WATCHING_SESSIONS,
+            description_formatter: Arc::new(RwLock::new(Arc::new(DefaultDescriptionFormatter))),
             session_watcher: Arc::new(SessionWatcher::new()),
+            project_watchers: Arc::new(RwLock::new(HashMap::new())),
             shared_db,
+            shutdown: CancellationToken::new(),
+            last_indexed_at: Arc::new(RwLock::new(last_indexed_at)),
+            index_state_path,
+            pending_messages: Arc::new(RwLock::new(HashMap::new())),
+            last_event_at: Arc::new(RwLock::new(None)),
+            message_source: Arc::new(MessageSourceCounters::default()),
+            server_restart_delay_secs: DEFAULT_SERVER_RESTART_DELAY_SECS,
+            reconnect_at: Arc::new(RwLock::new(None)),
+            takeover_grace_period_secs: DEFAULT_TAKEOVER_GRACE_PERIOD_SECS,
+            unknown_events: Arc::new(RwLock::new(VecDeque::with_capacity(UNKNOWN_EVENTS_CAPACITY))),
+            handler_metrics: Arc::new(HandlerMetrics::default()),
+            reconnect_policy: Self::default_reconnect_policy(),
+            reconnect_attempt: Arc::new(AtomicU32::new(0)),
         })
     }
 
@@ -144,21 +351,24 @@ impl DaemonService {
         tls: TlsConfig,
         redis_config: ServiceRegistryConfig,
     ) -> Result<Self> {
+        // 启动时校验证书，避免首次连接时才失败
+        tls.validate()?;
+
         info!("[ServiceDiscovery] Initializing Redis registry...");
 
         // 创建并连接 ServiceRegistry
-        let registry = ServiceRegistry::new(redis_config)?;
+        // Redis 不可用时降级为本地文件（单机模式）
+        // 设备 ID 用于选举并列时的一致性哈希，首次连接与重连选出同一个 Server
+        let registry = ServiceRegistry::new(redis_config)?
+            .with_file_fallback(registry_fallback_path())
+            .with_device_id(hostname.to_string());
         registry.connect().await?;
 
         // 启动事件监听
         registry.start_listening().await?;
 
-        // 获取可用 Server 列表
-        let servers = registry.get_servers().await?;
-        info!("[ServiceDiscovery] Found {} servers: {:?}", servers.len(), servers);
-
-        // 选择最高优先级的 Server
-        let server_url = if let Some(addr) = servers.first() {
+        // 选举 Server（与 SocketClient::discover_server 一致）
+        let server_url = if let Some(addr) = registry.elect_primary_server().await? {
             format!("https://{}", addr)
         } else {
             // 从环境变量读取 fallback，默认 localhost:10005
@@ -188,13 +398,22 @@ impl DaemonService {
             }
         };
 
+        let index_state_path = index_state::default_state_path();
+        let last_indexed_at = index_state_path
+            .as_deref()
+            .map(index_state::load)
+            .unwrap_or_default();
+
+        // 注册中心交给 Socket 客户端，重连时由 `SocketClient::reconnect` 重新选举
+        let socket = SocketClient::new(config).with_middleware(Arc::new(MetricsMiddleware));
+        socket.set_registry(registry).await;
+
         Ok(Self {
-            socket: Arc::new(RwLock::new(SocketClient::new(config))),
+            socket: Arc::new(RwLock::new(socket)),
             reader: Arc::new(RwLock::new(ClaudeReader::default()?)),
             watching_sessions: Arc::new(RwLock::new(HashSet::new())),
             hostname: hostname.to_string(),
             tls_config: tls,
-            registry: Some(Arc::new(registry)),
             current_server: Arc::new(RwLock::new(Some(server_url))),
             mobile_viewing_callback: Arc::new(RwLock::new(None)),
             resume_local_callback: Arc::new(RwLock::new(None)),
@@ -203,11 +422,167 @@ impl DaemonService {
             session_discovered_callback: Arc::new(RwLock::new(None)),
             server_command_callback: Arc::new(RwLock::new(None)),
             pending_approvals: Arc::new(RwLock::new(HashMap::new())),
+            sent_approval_requests: Arc::new(RwLock::new(HashMap::new())),
+            approval_dedup_window_secs: DEFAULT_APPROVAL_DEDUP_WINDOW_SECS,
+            max_watching_sessions: DEFAULT_MAX_WATCHING_SESSIONS,
+            description_formatter: Arc::new(RwLock::new(Arc::new(DefaultDescriptionFormatter))),
             session_watcher: Arc::new(SessionWatcher::new()),
+            project_watchers: Arc::new(RwLock::new(HashMap::new())),
             shared_db,
+            shutdown: CancellationToken::new(),
+            last_indexed_at: Arc::new(RwLock::new(last_indexed_at)),
+            index_state_path,
+            pending_messages: Arc::new(RwLock::new(HashMap::new())),
+            last_event_at: Arc::new(RwLock::new(None)),
+            message_source: Arc::new(MessageSourceCounters::default()),
+            server_restart_delay_secs: DEFAULT_SERVER_RESTART_DELAY_SECS,
+            reconnect_at: Arc::new(RwLock::new(None)),
+            takeover_grace_period_secs: DEFAULT_TAKEOVER_GRACE_PERIOD_SECS,
+            unknown_events: Arc::new(RwLock::new(VecDeque::with_capacity(UNKNOWN_EVENTS_CAPACITY))),
+            handler_metrics: Arc::new(HandlerMetrics::default()),
+            reconnect_policy: Self::default_reconnect_policy(),
+            reconnect_attempt: Arc::new(AtomicU32::new(0)),
         })
     }
 
+    /// 设置收到 server-shutdown 后的默认重连等待时间
+    pub fn with_server_restart_delay_secs(mut self, secs: u64) -> Self {
+        self.server_restart_delay_secs = secs;
+        self
+    }
+
+    /// 设置接管共享 DB Writer 前的宽限期
+    pub fn with_takeover_grace_period_secs(mut self, secs: u64) -> Self {
+        self.takeover_grace_period_secs = secs;
+        self
+    }
+
+    /// 设置权限请求去重窗口
+    pub fn with_approval_dedup_window_secs(mut self, secs: u64) -> Self {
+        self.approval_dedup_window_secs = secs;
+        self
+    }
+
+    /// 设置最大同时监听会话数
+    pub fn with_max_watching_sessions(mut self, max: usize) -> Self {
+        self.max_watching_sessions = max;
+        self
+    }
+
+    /// 默认的断线重连退避（1 秒起每次翻倍，上限 5 分钟，±20% 抖动）
+    pub fn default_reconnect_policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: None,
+            base_delay_ms: 1_000,
+            max_delay_ms: 300_000,
+            jitter_percent: 20,
+        }
+    }
+
+    /// 设置断线重连退避（`max_attempts` 不生效，daemon 始终重连）
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// 获取关闭信号
+    ///
+    /// 事件循环应在 `select!` 中监听同一个 token，取消后 push_initial_data 等长任务会尽快退出。
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// 重新创建会话读取器
+    ///
+    /// 按当前 `HOME` 重新定位 projects 目录（例如容器内切换用户后），缓存随旧读取器一起丢弃。
+    pub async fn reload_reader(&self) -> Result<()> {
+        let reader = ClaudeReader::default()?;
+        info!("Reloading session reader: {:?}", reader.projects_path());
+        *self.reader.write().await = reader;
+        Ok(())
+    }
+
+    /// 批量同步历史会话到共享数据库
+    ///
+    /// 用于首次启动时回填：逐个会话读取 JSONL 全量写入（已存在的消息由 DB 去重）。
+    /// 需先 `start()` 成为 Writer；`project_path` 为 None 时同步所有项目。
+    /// 单个会话失败不会中断整体同步，失败原因记录在报告中；收到关闭信号时提前返回。
+    pub async fn bulk_sync_sessions(
+        &self,
+        project_path: Option<&str>,
+        progress_callback: Option<BulkSyncProgressCallback>,
+    ) -> Result<BulkSyncReport> {
+        let Some(db) = self.shared_db.as_ref() else {
+            bail!("SharedDB is not configured");
+        };
+        if !db.is_writer().await {
+            bail!("Not the SharedDB writer, bulk sync skipped");
+        }
+
+        let sessions = self.reader.write().await.list_sessions(project_path, false, None)?;
+        let total = sessions.len();
+        info!("[SharedDB] Bulk syncing {} sessions", total);
+
+        let mut report = BulkSyncReport::default();
+        let mut projects = HashSet::new();
+        for (index, session) in sessions.iter().enumerate() {
+            if self.shutdown.is_cancelled() {
+                info!("[SharedDB] Bulk sync cancelled after {}/{} sessions", index, total);
+                break;
+            }
+
+            match self.sync_session(db, &session.id, &session.project_path).await {
+                Ok(inserted) => {
+                    report.sessions += 1;
+                    report.messages += inserted;
+                    projects.insert(session.project_path.as_str());
+                }
+                Err(e) => {
+                    warn!("[SharedDB] Bulk sync failed for {}: {}", session.id, e);
+                    report.failed += 1;
+                    if report.errors.len() < BULK_SYNC_MAX_ERRORS {
+                        report.errors.push(format!("{}: {}", session.id, e));
+                    }
+                }
+            }
+
+            if let Some(callback) = &progress_callback {
+                callback(index + 1, total);
+            }
+        }
+        report.projects = projects.len();
+        db.flush_sync_state().await;
+
+        info!(
+            "[SharedDB] Bulk sync done: {} sessions, {} messages, {} failed",
+            report.sessions, report.messages, report.failed
+        );
+        Ok(report)
+    }
+
+    /// 导出会话到单个 JSONL 文件（可移植备份）
+    ///
+    /// 每个会话一行元数据，后跟其原始消息；会话按修改时间排序，输出可以 diff。
+    /// `project_path` 为 None 时导出所有项目。
+    pub async fn export_sessions_jsonl(
+        &self,
+        project_path: Option<&str>,
+        out_path: &Path,
+    ) -> Result<ExportReport> {
+        let mut reader = self.reader.write().await;
+        let report = export::export_sessions(&mut reader, project_path, out_path)?;
+        info!(
+            "Exported {} sessions ({} messages, {} bytes) to {:?}",
+            report.sessions, report.messages, report.bytes_written, out_path
+        );
+        Ok(report)
+    }
+
+    /// 设置权限请求描述生成器
+    pub async fn set_description_formatter(&self, formatter: Arc<dyn DescriptionFormatter>) {
+        *self.description_formatter.write().await = formatter;
+    }
+
     /// 设置 Mobile 查看状态回调
     pub async fn set_mobile_viewing_callback(&self, callback: MobileViewingCallback) {
         *self.mobile_viewing_callback.write().await = Some(callback);
@@ -242,6 +617,15 @@ impl DaemonService {
     pub async fn start(&self) -> Result<()> {
         info!("Starting daemon service...");
 
+        // 监听整个 projects 目录，失败时（如系统不支持）退回轮询
+        #[cfg(feature = "notify-watcher")]
+        {
+            let projects_root = self.reader.read().await.projects_path().to_path_buf();
+            if let Err(e) = self.session_watcher.watch_all_projects(&projects_root).await {
+                warn!("Failed to watch projects directory, falling back to polling: {:?}", e);
+            }
+        }
+
         // 注册共享数据库 Writer
         if let Some(db) = &self.shared_db {
             match db.register().await {
@@ -252,71 +636,188 @@ impl DaemonService {
                     warn!("[SharedDB] Failed to register: {}", e);
                 }
             }
+            self.spawn_writer_takeover(db.clone());
         }
 
-        // 连接到服务器
+        // 连接到服务器，并确认连接可用后再发送事件
         self.socket.read().await.connect().await?;
+        self.socket.read().await.wait_until_connected(Duration::from_secs(30)).await?;
 
         // 注册
-        let register_data = RegisterData {
-            hostname: self.hostname.clone(),
-            platform: "darwin".to_string(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-        };
-        self.socket.read().await.register(register_data).await?;
+        self.register_with_retry(self.register_data()).await?;
 
         // 上报在线
         self.socket.read().await.report_online().await?;
 
         // 主动推送初始数据（让 Server 缓存到数据库）
+        let push_started = Instant::now();
         if let Err(e) = self.push_initial_data().await {
             warn!("Failed to push initial data: {:?}", e);
         }
+        metrics::histogram!(telemetry::INITIAL_PUSH_DURATION)
+            .record(push_started.elapsed().as_secs_f64());
 
         info!("Daemon service started");
         Ok(())
     }
 
+    /// 作为 Reader 时定期检查共享 DB Writer，失效后经过宽限期接管
+    fn spawn_writer_takeover(&self, db: Arc<SharedDbAdapter>) {
+        let grace_period_secs = self.takeover_grace_period_secs;
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(WRITER_HEALTH_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                if db.is_writer().await {
+                    continue;
+                }
+
+                match db.check_writer_health().await {
+                    Ok(WriterHealth::Alive) => continue,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("[SharedDB] Failed to check writer health: {}", e);
+                        continue;
+                    }
+                }
+
+                info!(
+                    "[SharedDB] Writer is not alive, taking over after {}s grace period",
+                    grace_period_secs
+                );
+                let taken = tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    taken = db.try_takeover(grace_period_secs) => taken,
+                };
+                match taken {
+                    Ok(true) => info!("[SharedDB] Took over writer role"),
+                    Ok(false) => debug!("[SharedDB] Takeover aborted, staying reader"),
+                    Err(e) => warn!("[SharedDB] Failed to take over writer role: {}", e),
+                }
+            }
+        });
+    }
+
+    /// 注册数据
+    fn register_data(&self) -> RegisterData {
+        RegisterData {
+            hostname: self.hostname.clone(),
+            platform: socket_client::current_platform().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// 注册 daemon，ack 超时时重试
+    async fn register_with_retry(&self, data: RegisterData) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.socket.read().await.register(data.clone()).await {
+                Ok(_) => return Ok(()),
+                Err(SocketError::AckTimeout) if attempt < REGISTER_MAX_ATTEMPTS => {
+                    warn!(
+                        "Register ack timed out (attempt {}/{}), retrying",
+                        attempt, REGISTER_MAX_ATTEMPTS
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
     /// 主动推送初始数据到 Server（用于填充数据库缓存）
     async fn push_initial_data(&self) -> Result<()> {
         info!("Pushing initial data to server...");
 
         // 1. 推送项目列表
-        let projects = self.reader.write().await.list_projects(Some(20))?;
-        let projects_json: Vec<serde_json::Value> = projects
+        let projects = self.reader.write().await.list_projects(Some(20), 1)?;
+        let projects_json = projects
             .into_iter()
-            .map(|p| serde_json::to_value(p).unwrap())
-            .collect();
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
 
         info!("Pushing {} projects to server", projects_json.len());
         self.socket.read().await.report_project_data(projects_json, None).await?;
 
         // 2. 推送每个项目的会话列表（限制每个项目最多 50 个会话，避免消息过大）
-        let all_sessions = self.reader.write().await.list_sessions(None, false)?;
+        //    文件修改时间与上次推送一致的会话跳过
+        let all_sessions = self.reader.write().await.list_sessions(None, false, None)?;
         if !all_sessions.is_empty() {
+            let last_indexed = self.last_indexed_at.read().await.clone();
+
             // 按 projectPath 分组
-            let mut sessions_by_project: std::collections::HashMap<String, Vec<serde_json::Value>> =
-                std::collections::HashMap::new();
+            let mut sessions_by_project: HashMap<String, Vec<(String, u64, serde_json::Value)>> =
+                HashMap::new();
+            let mut skipped = 0usize;
 
             for session in all_sessions {
+                let mtime = session.file_mtime.unwrap_or(0);
+                if last_indexed.get(&session.id) == Some(&mtime) {
+                    skipped += 1;
+                    continue;
+                }
+
                 let project_path = session.project_path.clone();
-                let session_json = serde_json::to_value(&session).unwrap();
+                let session_json = serde_json::to_value(&session)?;
                 let sessions = sessions_by_project.entry(project_path).or_default();
                 // 限制每个项目最多 50 个会话
                 if sessions.len() < 50 {
-                    sessions.push(session_json);
+                    sessions.push((session.id.clone(), mtime, session_json));
                 }
             }
 
+            if skipped > 0 {
+                info!("Skipped {} unchanged sessions", skipped);
+            }
+
+            // 所有项目的会话元数据合并发送，避免逐个 emit 造成的延迟
+            let mut pushed = Vec::new();
+            let mut events = Vec::with_capacity(sessions_by_project.len());
             for (project_path, sessions) in sessions_by_project {
                 info!(
                     "Pushing {} sessions for project {} to server",
                     sessions.len(),
                     project_path
                 );
-                self.socket.read().await
-                    .report_session_metadata(sessions, Some(project_path), None)
-                    .await?;
+
+                let total = sessions.len();
+                let mut sessions_json = Vec::with_capacity(total);
+                for (id, mtime, json) in sessions {
+                    pushed.push((id, mtime));
+                    sessions_json.push(json);
+                }
+
+                let payload = SessionMetadataPayload {
+                    sessions: sessions_json,
+                    project_path: Some(project_path),
+                    request_id: None,
+                    total,
+                    has_more: false,
+                };
+                events.push((
+                    "daemon:sessionMetadata".to_string(),
+                    serde_json::to_value(payload)?,
+                ));
+            }
+
+            if self.shutdown.is_cancelled() {
+                info!("Shutdown requested, stop pushing initial data");
+                return Ok(());
+            }
+
+            self.socket.read().await.emit_batch(events).await?;
+            self.last_indexed_at.write().await.extend(pushed);
+
+            self.save_index_state().await;
+
+            if self.shutdown.is_cancelled() {
+                return Ok(());
             }
         }
 
@@ -324,12 +825,79 @@ impl DaemonService {
         Ok(())
     }
 
+    /// 持久化索引状态
+    async fn save_index_state(&self) {
+        if let Some(path) = &self.index_state_path {
+            let state = self.last_indexed_at.read().await;
+            if let Err(e) = index_state::save(path, &state) {
+                warn!("Failed to save index state: {:?}", e);
+            }
+        }
+    }
+
+    /// 仅连接服务器（不注册、不推送数据），用于诊断
+    pub async fn connect(&self) -> Result<()> {
+        self.socket.read().await.connect().await?;
+        Ok(())
+    }
+
+    /// 收集诊断信息
+    pub async fn collect_diagnostics(&self) -> DiagnosticReport {
+        let (project_count, session_count) =
+            count_projects_and_sessions(&mut *self.reader.write().await);
+
+        let shared_db_role = match &self.shared_db {
+            Some(db) => Some(format!("{:?}", db.role().await)),
+            None => None,
+        };
+
+        // 注册中心由 Socket 客户端持有（仅 `with_registry` 创建的服务）
+        let registry = self.socket.read().await.get_registry().await;
+        let redis_connected = match registry {
+            Some(registry) => match registry.read().await.as_ref() {
+                Some(registry) => Some(registry.is_connected().await),
+                None => None,
+            },
+            None => None,
+        };
+
+        DiagnosticReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: self.hostname.clone(),
+            shared_db_role,
+            project_count,
+            session_count,
+            runtime: Some(RuntimeDiagnostics {
+                connected: self.socket.read().await.is_connected(),
+                server_url: self.current_server.read().await.clone(),
+                watching_session_count: self.session_watcher.session_count().await,
+                pending_approval_count: self.pending_approvals.read().await.len(),
+                last_event_at: self.last_event_at.read().await.map(|t| t.to_rfc3339()),
+                redis_connected,
+                session_message_source: self.message_source.snapshot(),
+                unknown_events: self.get_unknown_events().await,
+                handler_metrics: self.handler_metrics.snapshot(),
+            }),
+        }
+    }
+
+    /// 最近收到的无法识别的 Server 事件（旧的在前）
+    pub async fn get_unknown_events(&self) -> Vec<UnknownEvent> {
+        self.unknown_events.read().await.iter().cloned().collect()
+    }
+
     /// 停止服务
     pub async fn stop(&self) {
         info!("Stopping daemon service...");
 
-        // 释放共享数据库 Writer
+        // 发送尚未合并完成的新消息
+        if let Err(e) = self.flush_pending_messages(true).await {
+            warn!("Failed to flush pending messages: {:?}", e);
+        }
+
+        // 保存同步水位并释放共享数据库 Writer
         if let Some(db) = &self.shared_db {
+            db.flush_sync_state().await;
             if let Err(e) = db.release().await {
                 warn!("[SharedDB] Failed to release writer: {}", e);
             } else {
@@ -350,9 +918,79 @@ impl DaemonService {
 
     /// 处理单个事件（非阻塞，带超时）
     pub async fn run_once(&self) -> Result<()> {
+        // Server 重启等待到期后主动重连
+        let reconnect_due = {
+            let mut reconnect_at = self.reconnect_at.write().await;
+            let due = reconnect_at.is_some_and(|at| at <= Instant::now());
+            if due {
+                *reconnect_at = None;
+            }
+            due
+        };
+        if reconnect_due {
+            if let Err(e) = self.reconnect_after_server_shutdown().await {
+                error!("Failed to reconnect after server shutdown: {:?}", e);
+            }
+        }
+
         // 检查会话文件更新
-        if self.session_watcher.has_sessions().await {
-            match self.session_watcher.check_updates().await {
+        // 监听 projects 目录时由下面 select! 中的文件事件驱动，另每 5s 兜底轮询（事件可能丢失）；
+        // 否则轮询，间隔随 Claude Code 是否运行调整（100ms / 5s）
+        let updates = if self.session_watcher.has_sessions().await && self.session_watcher.poll_due() {
+            Some(self.session_watcher.check_updates().await)
+        } else {
+            None
+        };
+        match updates {
+            Some(Ok(events)) => {
+                for event in events {
+                    if let Err(e) = self.handle_watch_event(event).await {
+                        error!("Failed to handle watch event: {:?}", e);
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                warn!("Failed to check session updates: {:?}", e);
+            }
+            None => {}
+        }
+
+        // 检查被监听项目中新建的会话
+        let created: Vec<_> = self
+            .project_watchers
+            .read()
+            .await
+            .values()
+            .flat_map(|watcher| watcher.check_updates())
+            .collect();
+        for event in created {
+            if let Err(e) = self.handle_watch_event(event).await {
+                error!("Failed to handle project watch event: {:?}", e);
+            }
+        }
+
+        // 发送合并窗口已到期的新消息
+        if let Err(e) = self.flush_pending_messages(false).await {
+            error!("Failed to flush pending messages: {:?}", e);
+        }
+
+        // 审批响应、命令等高优先级事件优先于数据请求；超时避免无限阻塞
+        // 会话文件事件与 Socket 事件一起等待，文件变化时立即唤醒
+        let socket = self.socket.read().await;
+        let mut tree_changes = None;
+        let received = tokio::select! {
+            biased;
+            event = socket.recv_high_priority_event() => event,
+            event = socket.recv_low_priority_event() => event,
+            changes = self.session_watcher.next_tree_events() => {
+                tree_changes = Some(changes);
+                None
+            }
+            _ = tokio::time::sleep(Duration::from_millis(100)) => None,
+        };
+        if let Some(changes) = tree_changes {
+            drop(socket); // 释放锁
+            match self.session_watcher.handle_tree_events(changes).await {
                 Ok(events) => {
                     for event in events {
                         if let Err(e) = self.handle_watch_event(event).await {
@@ -360,40 +998,50 @@ impl DaemonService {
                         }
                     }
                 }
-                Err(e) => {
-                    warn!("Failed to check session updates: {:?}", e);
-                }
+                Err(e) => warn!("Failed to check session updates: {:?}", e),
             }
+            return Ok(());
         }
-
-        // 使用 recv_event_timeout 避免无限阻塞
-        let socket = self.socket.read().await;
-        match socket.recv_event_timeout(Duration::from_millis(100)).await {
+        match received {
             Some((event, data)) => {
                 drop(socket); // 释放锁
+                *self.last_event_at.write().await = Some(chrono::Utc::now());
                 if let Err(e) = self.handle_event(&event, data).await {
                     error!("Failed to handle event {}: {:?}", event, e);
                 }
             }
             None => {
-                // 检查连接状态
-                if !socket.is_connected() {
+                // 检查连接状态（Server 重启中时等待计划的主动重连）
+                if !socket.is_connected() && self.reconnect_at.read().await.is_none() {
                     drop(socket); // 释放锁
-                    warn!("Socket disconnected, attempting to reconnect...");
-                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    let attempt = self.reconnect_attempt.fetch_add(1, Ordering::SeqCst) + 1;
+                    let delay = self.reconnect_policy.delay_for(attempt);
+                    warn!(
+                        "Socket disconnected, attempting to reconnect in {:?} (attempt {})...",
+                        delay, attempt
+                    );
+                    tokio::select! {
+                        _ = self.shutdown.cancelled() => return Ok(()),
+                        _ = tokio::time::sleep(delay) => {}
+                    }
 
                     let socket = self.socket.read().await;
                     if let Err(e) = socket.connect().await {
-                        error!("Reconnect failed: {:?}", e);
+                        if !e.is_permanent() {
+                            error!("Reconnect failed: {:?}", e);
+                        } else {
+                            // 永久配置错误（URL 无效、本地证书配置无效），重试无意义，停止事件循环
+                            error!("Reconnect failed with permanent error, stopping: {:?}", e);
+                            self.shutdown.cancel();
+                            return Err(e.into());
+                        }
                     } else {
+                        self.reconnect_attempt.store(0, Ordering::SeqCst);
                         // 重连成功后重新注册
-                        let register_data = RegisterData {
-                            hostname: self.hostname.clone(),
-                            platform: "darwin".to_string(),
-                            version: env!("CARGO_PKG_VERSION").to_string(),
-                        };
-                        let _ = socket.register(register_data).await;
+                        let _ = socket.register(self.register_data()).await;
                         let _ = socket.report_online().await;
+                        metrics::counter!(telemetry::RECONNECTS).increment(1);
+                        self.prune_sent_approval_requests().await;
                         info!("Reconnected and re-registered");
                     }
                 }
@@ -402,6 +1050,113 @@ impl DaemonService {
         Ok(())
     }
 
+    /// 发送合并后的新消息
+    ///
+    /// `force` 为 true 时忽略合并窗口，发送全部待发消息（用于停止服务）。
+    async fn flush_pending_messages(&self, force: bool) -> Result<()> {
+        let ready: Vec<_> = {
+            let mut pending = self.pending_messages.write().await;
+            let ready_ids: Vec<String> = pending
+                .iter()
+                .filter(|(_, batch)| force || batch.first_at.elapsed() >= NEW_MESSAGE_DEBOUNCE)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ready_ids
+                .into_iter()
+                .filter_map(|id| pending.remove(&id).map(|batch| (id, batch.messages)))
+                .collect()
+        };
+
+        if ready.is_empty() {
+            return Ok(());
+        }
+
+        // 发送失败的批次放回队列下次重试，不中断其余批次
+        let mut unsent = Vec::new();
+        let mut first_error = None;
+        let socket = self.socket.read().await;
+        for (session_id, messages) in ready {
+            debug!("Flushing {} messages for session {}", messages.len(), session_id);
+            // 同一批次只推送最新的 Metrics
+            let metrics = messages.iter().rev().find_map(SessionWatcher::extract_metrics);
+            if let Err(e) = socket.notify_new_messages(&session_id, messages.clone()).await {
+                unsent.push((session_id, messages));
+                first_error.get_or_insert(e);
+                continue;
+            }
+            if let Some(metrics) = metrics {
+                if let Err(e) = socket.notify_metrics_update(&session_id, metrics).await {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        drop(socket);
+
+        if !unsent.is_empty() {
+            let mut pending = self.pending_messages.write().await;
+            for (session_id, mut messages) in unsent {
+                // 放在期间新到达的消息之前，保持顺序
+                match pending.get_mut(&session_id) {
+                    Some(batch) => {
+                        messages.append(&mut batch.messages);
+                        batch.messages = messages;
+                    }
+                    None => {
+                        pending.insert(
+                            session_id,
+                            PendingMessages {
+                                messages,
+                                first_at: Instant::now(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// 处理 Server 关闭通知
+    ///
+    /// 记录重连时间（`restartDelaySecs`，默认 5 秒后），由 run_once 到期后主动重连，
+    /// 不必等检测到断开；等待期间继续处理其他事件。
+    async fn handle_server_shutdown(&self, data: &serde_json::Value) -> Result<()> {
+        let delay_secs = data
+            .get("restartDelaySecs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(self.server_restart_delay_secs);
+        warn!("Server is shutting down, reconnecting in {}s", delay_secs);
+        *self.reconnect_at.write().await = Some(Instant::now() + Duration::from_secs(delay_secs));
+        Ok(())
+    }
+
+    /// Server 重启后主动重连
+    ///
+    /// `SocketClient::reconnect` 会重新选举 Server（重启后地址可能变化）。
+    async fn reconnect_after_server_shutdown(&self) -> Result<()> {
+        let socket = self.socket.read().await;
+        if let Err(e) = socket.reconnect().await {
+            // 失败时交给 run_once 的断线重连继续尝试
+            warn!("Reconnect after server shutdown failed: {:?}", e);
+            return Ok(());
+        }
+        let server_url = socket.current_url().await;
+        drop(socket);
+        info!("[ServiceDiscovery] Reconnected to server: {}", server_url);
+        *self.current_server.write().await = Some(server_url);
+
+        self.register_with_retry(self.register_data()).await?;
+        self.socket.read().await.report_online().await?;
+        metrics::counter!(telemetry::RECONNECTS).increment(1);
+        self.prune_sent_approval_requests().await;
+        info!("Reconnected after server restart");
+        Ok(())
+    }
+
     /// 处理会话监听事件
     async fn handle_watch_event(&self, event: SessionWatchEvent) -> Result<()> {
         match event {
@@ -412,21 +1167,30 @@ impl DaemonService {
             } => {
                 debug!("New message in session {}", session_id);
 
-                // 写入共享数据库（仅 Writer 模式）
+                // 写入共享数据库（仅 Writer 模式），从同步水位读取以推进水位
                 if let Some(db) = &self.shared_db {
                     if db.is_writer().await {
-                        if let Err(e) = self.sync_message_to_shared_db(
-                            db,
-                            &session_id,
-                            &project_path,
-                            &message,
-                        ).await {
-                            warn!("[SharedDB] Failed to sync message: {}", e);
+                        match self.sync_session(db, &session_id, &project_path).await {
+                            Ok(inserted) if inserted > 0 => {
+                                debug!("[SharedDB] Synced {} messages to shared database", inserted);
+                            }
+                            Ok(_) => {}
+                            Err(e) => warn!("[SharedDB] Failed to sync message: {}", e),
                         }
                     }
                 }
 
-                self.socket.read().await.notify_new_message(&session_id, message).await?;
+                // 加入合并批次，由 run_once 在窗口到期后统一发送
+                self.pending_messages
+                    .write()
+                    .await
+                    .entry(session_id)
+                    .or_insert_with(|| PendingMessages {
+                        messages: Vec::new(),
+                        first_at: Instant::now(),
+                    })
+                    .messages
+                    .push(message);
             }
             SessionWatchEvent::SessionCreated {
                 session_id,
@@ -442,6 +1206,7 @@ impl DaemonService {
                 project_path,
             } => {
                 info!("Session deleted: {}", session_id);
+                self.sync_project_watchers().await;
                 self.socket.read().await
                     .notify_session_deleted(&session_id, &project_path)
                     .await?;
@@ -460,8 +1225,45 @@ impl DaemonService {
         }
     }
 
-    /// 处理服务器事件
+    /// 处理服务器事件（记录耗时）
     async fn handle_event(&self, event: &str, data: serde_json::Value) -> Result<()> {
+        let span = info_span!(
+            "handle_event",
+            event_name = event,
+            handler_duration_us = tracing::field::Empty,
+            success = tracing::field::Empty,
+        );
+
+        let started = Instant::now();
+        let result = self.dispatch_event(event, &data).instrument(span.clone()).await;
+        let elapsed = started.elapsed();
+        let success = result.is_ok();
+
+        span.record("handler_duration_us", elapsed.as_micros() as u64);
+        span.record("success", success);
+        // 事件名来自 Server，未知事件归入同一标签，避免统计项无限增长
+        let label = event_label(event);
+        self.handler_metrics.record(label, elapsed, success);
+        metrics::histogram!(
+            telemetry::HANDLER_DURATION,
+            "event" => label,
+            "success" => success.to_string()
+        )
+        .record(elapsed.as_secs_f64());
+
+        if elapsed > SLOW_HANDLER_THRESHOLD {
+            let payload_size = serde_json::to_vec(&data).map_or(0, |bytes| bytes.len());
+            warn!(
+                "Slow event handler: {} took {:?} (payload {} bytes)",
+                event, elapsed, payload_size
+            );
+        }
+
+        result
+    }
+
+    /// 按事件名分发到对应的处理器
+    async fn dispatch_event(&self, event: &str, data: &serde_json::Value) -> Result<()> {
         debug!("Handling event: {} with data: {:?}", event, data);
 
         match event {
@@ -512,14 +1314,22 @@ impl DaemonService {
                 self.handle_send_message(data).await?;
             }
             "server-shutdown" => {
-                warn!("Server is shutting down");
+                self.handle_server_shutdown(data).await?;
             }
             "__disconnected" => {
                 warn!("Received disconnect event, will reconnect...");
                 // 不在这里处理，run_once 会检测 is_connected 并重连
             }
             _ => {
-                debug!("Unknown event: {}", event);
+                // 没有处理分支的事件：Server 可能新增了 daemon 尚不支持的事件
+                if let Err(unknown) = ServerEvent::try_parse(event, data.clone()) {
+                    debug!("{}", unknown);
+                    let mut unknown_events = self.unknown_events.write().await;
+                    if unknown_events.len() == UNKNOWN_EVENTS_CAPACITY {
+                        unknown_events.pop_front();
+                    }
+                    unknown_events.push_back(unknown);
+                }
             }
         }
 
@@ -528,19 +1338,21 @@ impl DaemonService {
 
     // ==================== 事件处理器 ====================
 
-    async fn handle_request_project_data(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_request_project_data(&self, data: &serde_json::Value) -> Result<()> {
         let limit = data.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize);
+        // 默认不上报没有会话的项目
+        let min_sessions = data.get("minSessions").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
-        let projects = self.reader.write().await.list_projects(limit)?;
+        let projects = self.reader.write().await.list_projects(limit, min_sessions)?;
 
-        let projects_json: Vec<serde_json::Value> = projects
+        let projects_json = projects
             .into_iter()
-            .map(|p| serde_json::to_value(p).unwrap())
-            .collect();
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
 
         self.socket.read().await
             .report_project_data(projects_json, request_id)
@@ -549,32 +1361,48 @@ impl DaemonService {
         Ok(())
     }
 
-    async fn handle_request_session_metadata(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_request_session_metadata(&self, data: &serde_json::Value) -> Result<()> {
         let project_path = data.get("projectPath").and_then(|v| v.as_str());
+        let limit = data.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize);
+        let offset = data.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
-        let sessions = self.reader.write().await.list_sessions(project_path, false)?;
-
-        let sessions_json: Vec<serde_json::Value> = sessions
+        let mut sessions = self.reader.write().await.list_sessions(project_path, false, None)?;
+        // 按修改时间降序，保证分页稳定
+        sessions.sort_by(|a, b| {
+            b.file_mtime
+                .unwrap_or(0)
+                .cmp(&a.file_mtime.unwrap_or(0))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        let total = sessions.len();
+        let limit = limit.unwrap_or(usize::MAX);
+        let has_more = offset.saturating_add(limit) < total;
+        let sessions_json = sessions
             .into_iter()
-            .map(|s| serde_json::to_value(s).unwrap())
-            .collect();
+            .skip(offset)
+            .take(limit)
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
 
         self.socket.read().await
-            .report_session_metadata(
+            .report_session_metadata_page(
                 sessions_json,
                 project_path.map(|s| s.to_string()),
                 request_id,
+                total,
+                has_more,
             )
             .await?;
 
         Ok(())
     }
 
-    async fn handle_request_session_messages(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_request_session_messages(&self, data: &serde_json::Value) -> Result<()> {
         let session_id = data
             .get("sessionId")
             .and_then(|v| v.as_str())
@@ -601,13 +1429,63 @@ impl DaemonService {
         };
 
         // 获取会话文件路径
-        let mut reader = self.reader.write().await;
-        let session_path = reader
+        let session_path = self
+            .reader
+            .write()
+            .await
             .get_session_path(session_id)
             .ok_or_else(|| anyhow::anyhow!("Session not found: {}", session_id))?;
+        let file_size = std::fs::metadata(&session_path).map_or(0, |m| m.len());
+
+        // 优先从共享 DB 读取（已同步到 JSONL 末尾时）
+        let source = match &self.shared_db {
+            Some(db) => match db.freshness(session_id, file_size).await {
+                Ok(DbFreshness::Fresh) => {
+                    match self.read_messages_from_shared_db(db, session_id, limit, offset, order).await {
+                        Ok(Some(result)) => {
+                            self.message_source.record(MessageSource::DbHit);
+                            let message_count = result.messages.len();
+                            self.socket.read().await
+                                .report_session_messages(
+                                    session_id.to_string(),
+                                    project_path.to_string(),
+                                    result.messages,
+                                    result.total,
+                                    result.has_more,
+                                    request_id,
+                                )
+                                .await?;
+                            debug!(
+                                "Sent {} messages for session {} (shared DB)",
+                                message_count,
+                                session_id
+                            );
+                            return Ok(());
+                        }
+                        Ok(None) => MessageSource::DbMissStale,
+                        Err(e) => {
+                            warn!("[SharedDB] Failed to read messages for {}: {:?}", session_id, e);
+                            MessageSource::DbMissStale
+                        }
+                    }
+                }
+                Ok(DbFreshness::Stale) => MessageSource::DbMissStale,
+                Ok(DbFreshness::Absent) => MessageSource::DbMissAbsent,
+                Err(e) => {
+                    warn!("[SharedDB] Failed to check freshness for {}: {:?}", session_id, e);
+                    MessageSource::DbMissAbsent
+                }
+            },
+            None => MessageSource::DbMissAbsent,
+        };
+        self.message_source.record(source);
 
-        // 使用 read_messages_raw 返回原始 JSONL 格式，不做转换
-        let result = reader.read_messages_raw(&session_path, limit, offset, order)?;
+        // 返回原始 JSONL 格式，不做转换（大文件使用内存映射）
+        let result = self
+            .reader
+            .read()
+            .await
+            .read_messages_mmap(&session_path, limit, offset, order)?;
 
         let message_count = result.messages.len();
 
@@ -628,10 +1506,70 @@ impl DaemonService {
             session_id
         );
 
+        // Writer 从同步水位开始回填共享 DB，后续请求可直接命中
+        if let Some(db) = &self.shared_db {
+            if db.is_writer().await {
+                match self
+                    .sync_session_tail(db, session_id, project_path, Path::new(&session_path))
+                    .await
+                {
+                    Ok(inserted) => {
+                        debug!("[SharedDB] Backfilled {} messages for session {}", inserted, session_id);
+                    }
+                    Err(e) => warn!("[SharedDB] Failed to backfill session {}: {:?}", session_id, e),
+                }
+            }
+        }
+
         Ok(())
     }
 
-    async fn handle_start_watching(&self, data: serde_json::Value) -> Result<()> {
+    /// 从共享 DB 读取一页会话消息（原始 JSONL 格式）
+    ///
+    /// 只查询本页的消息；任一消息缺少可解析的原始 JSON 时返回 None，由调用方回退读取 JSONL。
+    async fn read_messages_from_shared_db(
+        &self,
+        db: &SharedDbAdapter,
+        session_id: &str,
+        limit: usize,
+        offset: usize,
+        order: session_reader::Order,
+    ) -> Result<Option<session_reader::RawMessagesResult>> {
+        let total = db.count_messages(session_id).await?;
+        let page_len = limit.min(total.saturating_sub(offset));
+
+        // DB 按时间升序存储，降序分页换算为升序偏移
+        let db_offset = match order {
+            session_reader::Order::Asc => offset,
+            session_reader::Order::Desc => total.saturating_sub(offset.saturating_add(page_len)),
+        };
+        let stored = if page_len == 0 {
+            Vec::new()
+        } else {
+            db.get_messages(session_id, page_len, db_offset).await?
+        };
+
+        let mut messages = Vec::with_capacity(stored.len());
+        for message in &stored {
+            match message.raw.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+                Some(Ok(value)) => messages.push(value),
+                _ => return Ok(None),
+            }
+        }
+
+        if matches!(order, session_reader::Order::Desc) {
+            messages.reverse();
+        }
+        let has_more = offset.saturating_add(messages.len()) < total;
+
+        Ok(Some(session_reader::RawMessagesResult {
+            messages,
+            total,
+            has_more,
+        }))
+    }
+
+    async fn handle_start_watching(&self, data: &serde_json::Value) -> Result<()> {
         let session_id = data
             .get("sessionId")
             .and_then(|v| v.as_str())
@@ -641,29 +1579,125 @@ impl DaemonService {
             .and_then(|v| v.as_str())
             .unwrap_or_default();
 
+        let from_position = data.get("fromPosition").and_then(|v| v.as_u64());
+
         validate_path_component(session_id, "session_id")?;
 
+        // Server 只给了 session_id 时，按 ID 查找所属项目
+        let project_path = if project_path.is_empty() {
+            self.reader
+                .write()
+                .await
+                .get_session_by_id(session_id)?
+                .map(|meta| meta.project_path)
+                .unwrap_or_default()
+        } else {
+            project_path.to_string()
+        };
+
         info!("Start watching session: {}", session_id);
 
-        self.watching_sessions
+        // 监听整个 projects 目录时不为单个项目创建目录监听，监听上限不适用
+        let tree_watching = self.session_watcher.is_watching_all_projects();
+        let polling_only = {
+            let mut watching = self.watching_sessions.write().await;
+            let over_limit = !tree_watching
+                && !watching.contains(session_id)
+                && watching.len() >= self.max_watching_sessions;
+            watching.insert(session_id.to_string());
+            telemetry::set_sessions_watching(watching.len());
+            over_limit
+        };
+        if polling_only {
+            warn!(
+                "Watching limit ({}) reached, session {} is polled only",
+                self.max_watching_sessions, session_id
+            );
+            self.socket
+                .read()
+                .await
+                .send_watch_limit_reached(session_id, self.max_watching_sessions)
+                .await?;
+        }
+
+        let session_path = self
+            .reader
             .write()
             .await
-            .insert(session_id.to_string());
-
-        let mut reader = self.reader.write().await;
-        let session_path = reader
             .get_session_path(session_id)
             .ok_or_else(|| anyhow::anyhow!("Session not found: {}", session_id))?;
         let session_path = PathBuf::from(session_path);
 
-        self.session_watcher
-            .watch_session(session_id, &session_path, project_path)
-            .await?;
+        if polling_only {
+            self.session_watcher
+                .watch_session_polling_only(session_id, &session_path, &project_path)
+                .await?;
+        } else {
+            self.session_watcher
+                .watch_session(session_id, &session_path, &project_path)
+                .await?;
+        }
+        self.sync_project_watchers().await;
+
+        // 先补发离线期间错过的历史消息，再进入实时监听
+        if let Some(from_position) = from_position {
+            let messages = self
+                .session_watcher
+                .replay_session(session_id, from_position)
+                .await?;
+
+            info!(
+                "Replaying {} messages for session {} from position {}",
+                messages.len(),
+                session_id,
+                from_position
+            );
+
+            if !messages.is_empty() {
+                self.socket
+                    .read()
+                    .await
+                    .notify_new_messages(session_id, messages)
+                    .await?;
+            }
+        }
 
         Ok(())
     }
 
-    async fn handle_stop_watching(&self, data: serde_json::Value) -> Result<()> {
+    /// 让项目监听器与被监听会话所在的项目保持一致
+    async fn sync_project_watchers(&self) {
+        // projects 目录监听已覆盖所有项目的新建会话
+        if self.session_watcher.is_watching_all_projects() {
+            self.project_watchers.write().await.clear();
+            return;
+        }
+
+        let projects = self.session_watcher.watched_projects().await;
+        let mut watchers = self.project_watchers.write().await;
+
+        watchers.retain(|project_path, _| {
+            let keep = projects.contains_key(project_path);
+            if !keep {
+                info!("Stop watching project: {}", project_path);
+            }
+            keep
+        });
+
+        for (project_path, project_dir) in projects {
+            if watchers.contains_key(&project_path) {
+                continue;
+            }
+            match ProjectWatcher::new(&project_dir, &project_path) {
+                Ok(watcher) => {
+                    watchers.insert(project_path, watcher);
+                }
+                Err(e) => warn!("Failed to watch project {}: {:?}", project_path, e),
+            }
+        }
+    }
+
+    async fn handle_stop_watching(&self, data: &serde_json::Value) -> Result<()> {
         let session_id = data
             .get("sessionId")
             .and_then(|v| v.as_str())
@@ -671,13 +1705,62 @@ impl DaemonService {
 
         info!("Stop watching session: {}", session_id);
 
-        self.watching_sessions.write().await.remove(session_id);
+        {
+            let mut watching = self.watching_sessions.write().await;
+            watching.remove(session_id);
+            telemetry::set_sessions_watching(watching.len());
+        }
         self.session_watcher.unwatch_session(session_id).await;
+        self.sync_project_watchers().await;
+
+        // 取消该会话仍在等待的权限请求（request_id 格式：{session_id}-{tool_use_id}）
+        if !session_id.is_empty() {
+            self.cancel_pending_approvals(session_id, "session stopped being watched")
+                .await;
+        }
 
         Ok(())
     }
 
-    async fn handle_mobile_viewing(&self, data: serde_json::Value) -> Result<()> {
+    /// 取消会话的全部等待中权限请求，并通知 Server 请求已失效
+    async fn cancel_pending_approvals(&self, session_id: &str, reason: &str) {
+        let prefix = format!("{}-", session_id);
+        let cancelled: Vec<(String, PendingApproval)> = {
+            let mut pending = self.pending_approvals.write().await;
+            let ids: Vec<String> = pending
+                .keys()
+                .filter(|id| id.starts_with(&prefix))
+                .cloned()
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| pending.remove(&id).map(|approval| (id, approval)))
+                .collect()
+        };
+
+        if cancelled.is_empty() {
+            return;
+        }
+
+        info!(
+            "Cancelling {} pending approvals for session {}",
+            cancelled.len(),
+            session_id
+        );
+
+        // 每个等待方都要收到拒绝结果，单个过期通知发送失败不影响其余请求
+        let socket = self.socket.read().await;
+        for (request_id, approval) in cancelled {
+            approval.resolve(ApprovalResult {
+                approved: false,
+                reason: Some(reason.to_string()),
+            });
+            if let Err(e) = socket.send_approval_expired(&request_id, reason).await {
+                warn!("Failed to report expired approval {}: {:?}", request_id, e);
+            }
+        }
+    }
+
+    async fn handle_mobile_viewing(&self, data: &serde_json::Value) -> Result<()> {
         let session_id = data
             .get("sessionId")
             .and_then(|v| v.as_str())
@@ -689,14 +1772,22 @@ impl DaemonService {
             session_id, is_viewing
         );
 
-        if let Some(callback) = self.mobile_viewing_callback.read().await.as_ref() {
-            callback(session_id, is_viewing);
+        let callback = self.mobile_viewing_callback.read().await.clone();
+        if let Some(callback) = callback {
+            if !callback(session_id.to_string(), is_viewing).await {
+                debug!("Mobile viewing change for {} not handled", session_id);
+                self.socket
+                    .read()
+                    .await
+                    .send_mobile_viewing_ack(session_id, is_viewing, false)
+                    .await?;
+            }
         }
 
         Ok(())
     }
 
-    async fn handle_resume_local(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_resume_local(&self, data: &serde_json::Value) -> Result<()> {
         let session_id = data
             .get("sessionId")
             .and_then(|v| v.as_str())
@@ -704,14 +1795,15 @@ impl DaemonService {
 
         info!("Resume local mode for session: {}", session_id);
 
-        if let Some(callback) = self.resume_local_callback.read().await.as_ref() {
-            callback(session_id);
+        let callback = self.resume_local_callback.read().await.clone();
+        if let Some(callback) = callback {
+            callback(session_id.to_string()).await;
         }
 
         Ok(())
     }
 
-    async fn handle_watch_new_session(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_watch_new_session(&self, data: &serde_json::Value) -> Result<()> {
         let client_id = data
             .get("clientId")
             .and_then(|v| v.as_str())
@@ -723,14 +1815,15 @@ impl DaemonService {
 
         info!("Watch new session request: client={}, project={}", client_id, project_path);
 
-        if let Some(callback) = self.watch_new_session_callback.read().await.as_ref() {
-            callback(client_id, project_path);
+        let callback = self.watch_new_session_callback.read().await.clone();
+        if let Some(callback) = callback {
+            callback(client_id.to_string(), project_path.to_string()).await;
         }
 
         Ok(())
     }
 
-    async fn handle_find_new_session(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_find_new_session(&self, data: &serde_json::Value) -> Result<()> {
         let client_id = data
             .get("clientId")
             .and_then(|v| v.as_str())
@@ -742,14 +1835,15 @@ impl DaemonService {
 
         info!("Find new session request: client={}, project={}", client_id, project_path);
 
-        if let Some(callback) = self.find_new_session_callback.read().await.as_ref() {
-            callback(client_id, project_path);
+        let callback = self.find_new_session_callback.read().await.clone();
+        if let Some(callback) = callback {
+            callback(client_id.to_string(), project_path.to_string()).await;
         }
 
         Ok(())
     }
 
-    async fn handle_session_discovered(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_session_discovered(&self, data: &serde_json::Value) -> Result<()> {
         let project_path = data
             .get("projectPath")
             .and_then(|v| v.as_str())
@@ -761,14 +1855,15 @@ impl DaemonService {
 
         info!("Session discovered: project={}, session={}", project_path, session_id);
 
-        if let Some(callback) = self.session_discovered_callback.read().await.as_ref() {
-            callback(project_path, session_id);
+        let callback = self.session_discovered_callback.read().await.clone();
+        if let Some(callback) = callback {
+            callback(project_path.to_string(), session_id.to_string()).await;
         }
 
         Ok(())
     }
 
-    async fn handle_approval_response(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_approval_response(&self, data: &serde_json::Value) -> Result<()> {
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
@@ -780,7 +1875,7 @@ impl DaemonService {
 
         let mut pending = self.pending_approvals.write().await;
         if let Some(approval) = pending.remove(request_id) {
-            let _ = approval.tx.send(ApprovalResult { approved, reason });
+            approval.resolve(ApprovalResult { approved, reason });
         } else {
             warn!("No pending approval found for request: {}", request_id);
             self.socket.read().await
@@ -791,7 +1886,10 @@ impl DaemonService {
         Ok(())
     }
 
-    async fn handle_server_command(&self, data: serde_json::Value) -> Result<()> {
+    /// 处理服务器命令
+    ///
+    /// 内置 `gc`、`reload`、`status`，其他命令交给 `server_command_callback`。
+    async fn handle_server_command(&self, data: &serde_json::Value) -> Result<()> {
         let command = data
             .get("command")
             .and_then(|v| v.as_str())
@@ -800,18 +1898,39 @@ impl DaemonService {
 
         info!("Server command: {}", command);
 
-        if let Some(callback) = self.server_command_callback.read().await.as_ref() {
-            callback(command, cmd_data);
+        match command {
+            "gc" => self.release_reader_resources().await,
+            "reload" => self.reload_reader().await?,
+            "status" => {
+                let report = serde_json::to_value(self.collect_diagnostics().await)?;
+                self.socket.read().await.send_status_report(report).await?;
+            }
+            _ => {
+                let callback = self.server_command_callback.read().await.clone();
+                if let Some(callback) = callback {
+                    callback(command.to_string(), cmd_data).await;
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// 重建会话读取器（同一 projects 目录），释放缓存和打开的文件句柄
+    async fn release_reader_resources(&self) {
+        let mut reader = self.reader.write().await;
+        let projects_path = reader.projects_path().to_path_buf();
+        *reader = ClaudeReader::new(projects_path);
+        info!("Released session reader resources");
+    }
+
     // ==================== V3: 写操作处理方法 ====================
 
     /// 处理创建会话请求
-    /// 注意：Daemon 本身不创建会话，需要通过 ETerm 或 CLI 创建
-    async fn handle_create_session(&self, data: serde_json::Value) -> Result<()> {
+    ///
+    /// 带 `sessionId` 时通过 `claude --resume` 恢复已有会话；
+    /// 新会话 Daemon 本身不创建，需要通过 ETerm 或 CLI 创建。
+    async fn handle_create_session(&self, data: &serde_json::Value) -> Result<()> {
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
@@ -827,6 +1946,12 @@ impl DaemonService {
             request_id, project_path
         );
 
+        if let Some(session_id) = data.get("sessionId").and_then(|v| v.as_str()) {
+            return self
+                .resume_session(request_id, session_id, project_path, prompt)
+                .await;
+        }
+
         // TODO: 实际的会话创建需要通过 ETerm 或启动 Claude CLI
         // 目前先返回失败，等待 ETerm 集成完成
         self.socket
@@ -839,14 +1964,78 @@ impl DaemonService {
                 None,
                 None,
                 Some("Daemon does not support session creation directly. Please use ETerm."),
+                false,
             )
             .await?;
 
         Ok(())
     }
 
+    /// 通过 `claude --resume` 恢复已有会话并上报结果
+    async fn resume_session(
+        &self,
+        request_id: &str,
+        session_id: &str,
+        project_path: &str,
+        prompt: Option<&str>,
+    ) -> Result<()> {
+        info!("Resuming session {} in {}", session_id, project_path);
+
+        // session_id 与 prompt 在 resume_session 中校验，失败时同样回报给服务器
+        let result = resume::resume_session(
+            CLAUDE_COMMAND,
+            session_id,
+            project_path,
+            prompt.unwrap_or_default(),
+        )
+        .await;
+        let socket = self.socket.read().await;
+        match result {
+            Ok(resumed) => {
+                // CLI 输出中没有路径信息时按项目路径推算
+                let (encoded_dir_name, transcript_path) = {
+                    let mut reader = self.reader.write().await;
+                    let encoded = resumed
+                        .encoded_dir_name
+                        .or_else(|| reader.get_encoded_dir_name(project_path))
+                        .unwrap_or_else(|| ClaudeReader::encode_path(project_path));
+                    let transcript = resumed
+                        .transcript_path
+                        .or_else(|| reader.get_session_path(&resumed.session_id));
+                    (encoded, transcript)
+                };
+                socket
+                    .send_session_created_result(
+                        request_id,
+                        true,
+                        Some(&resumed.session_id),
+                        Some(&encoded_dir_name),
+                        transcript_path.as_deref(),
+                        None,
+                        true,
+                    )
+                    .await?;
+            }
+            Err(e) => {
+                warn!("Failed to resume session {}: {}", session_id, e);
+                socket
+                    .send_session_created_result(
+                        request_id,
+                        false,
+                        Some(session_id),
+                        None,
+                        None,
+                        Some(&e.to_string()),
+                        true,
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     /// 处理检查加载状态请求
-    async fn handle_check_loading(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_check_loading(&self, data: &serde_json::Value) -> Result<()> {
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
@@ -861,12 +2050,22 @@ impl DaemonService {
             request_id, session_id
         );
 
-        // 目前返回 false（未加载），因为 Daemon 不跟踪加载状态
-        // 实际的加载状态需要通过 SDK 或 ETerm 来获取
+        // 最后一条消息没有 stop_reason 说明 Claude 仍在生成
+        let loading = {
+            let mut reader = self.reader.write().await;
+            match reader.get_session_path(session_id) {
+                Some(path) => reader.is_session_loading(&path).unwrap_or_else(|e| {
+                    warn!("Failed to check loading state for {}: {:?}", session_id, e);
+                    false
+                }),
+                None => false,
+            }
+        };
+
         self.socket
             .read()
             .await
-            .send_check_loading_result(request_id, false)
+            .send_check_loading_result(request_id, loading)
             .await?;
 
         Ok(())
@@ -874,7 +2073,7 @@ impl DaemonService {
 
     /// 处理发送消息请求
     /// 注意：Daemon 本身不发送消息，需要通过 ETerm 或 SDK 发送
-    async fn handle_send_message(&self, data: serde_json::Value) -> Result<()> {
+    async fn handle_send_message(&self, data: &serde_json::Value) -> Result<()> {
         let request_id = data
             .get("requestId")
             .and_then(|v| v.as_str())
@@ -1047,7 +2246,7 @@ impl DaemonService {
     pub async fn notify_metrics_update(
         &self,
         session_id: &str,
-        metrics: serde_json::Value,
+        metrics: ContextMetrics,
     ) -> Result<()> {
         self.socket.read().await.notify_metrics_update(session_id, metrics).await?;
         Ok(())
@@ -1066,29 +2265,53 @@ impl DaemonService {
         timeout_ms: u64,
     ) -> Result<ApprovalResult> {
         let request_id = format!("{}-{}", session_id, tool_use_id);
-        let description = format_tool_description(tool_name, &input);
+        let description = self.description_formatter.read().await.format(tool_name, &input);
 
         let (tx, rx) = oneshot::channel();
 
-        self.pending_approvals.write().await.insert(
-            request_id.clone(),
-            PendingApproval { tx },
-        );
-
-        self.socket.read().await
-            .send_approval_request(
-                &request_id,
-                session_id,
-                client_id,
-                tool_name,
-                input,
-                tool_use_id,
-                &description,
-            )
-            .await?;
+        // 同一把锁内判断去重并登记：同一请求已在等待（重连后重放）时只加入等待，不重复发送
+        let already_sent = {
+            let mut pending = self.pending_approvals.write().await;
+            match pending.entry(request_id.clone()) {
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().waiters.push(tx);
+                    true
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(PendingApproval { waiters: vec![tx] });
+                    self.sent_approval_requests
+                        .write()
+                        .await
+                        .insert(request_id.clone(), Instant::now());
+                    false
+                }
+            }
+        };
+        if already_sent {
+            debug!("Approval request {} already pending, waiting for its result", request_id);
+        } else {
+            metrics::counter!(telemetry::APPROVAL_REQUESTS).increment(1);
+
+            let sent = self.socket.read().await
+                .send_approval_request(
+                    &request_id,
+                    session_id,
+                    client_id,
+                    tool_name,
+                    input,
+                    tool_use_id,
+                    &description,
+                )
+                .await;
+            if let Err(e) = sent {
+                self.pending_approvals.write().await.remove(&request_id);
+                self.sent_approval_requests.write().await.remove(&request_id);
+                return Err(e.into());
+            }
+        }
 
         let timeout = Duration::from_millis(timeout_ms);
-        match tokio::time::timeout(timeout, rx).await {
+        let result = match tokio::time::timeout(timeout, rx).await {
             Ok(Ok(result)) => Ok(result),
             Ok(Err(_)) => {
                 self.pending_approvals.write().await.remove(&request_id);
@@ -1098,16 +2321,40 @@ impl DaemonService {
                 })
             }
             Err(_) => {
-                self.pending_approvals.write().await.remove(&request_id);
-                self.socket.read().await
-                    .send_approval_timeout(&request_id, session_id, client_id)
-                    .await?;
-                Ok(ApprovalResult {
+                // 超时结果同样发给一起等待的重放请求
+                let timed_out = ApprovalResult {
                     approved: false,
                     reason: Some("Request timeout".to_string()),
-                })
+                };
+                let Some(approval) = self.pending_approvals.write().await.remove(&request_id)
+                else {
+                    // 其他等待方已先结束该请求
+                    return Ok(timed_out);
+                };
+                approval.resolve(timed_out.clone());
+                metrics::counter!(telemetry::APPROVAL_TIMEOUTS).increment(1);
+                self.socket
+                    .read()
+                    .await
+                    .send_approval_timeout(&request_id, session_id, client_id)
+                    .await
+                    .map(|_| timed_out)
+                    .map_err(Into::into)
             }
-        }
+        };
+
+        // 请求已结束（已响应、超时或失效），不再需要去重记录
+        self.sent_approval_requests.write().await.remove(&request_id);
+        result
+    }
+
+    /// 清理超过去重窗口的已发送权限请求记录（请求结束时记录已移除，这里兜底）
+    async fn prune_sent_approval_requests(&self) {
+        let window = Duration::from_secs(self.approval_dedup_window_secs);
+        self.sent_approval_requests
+            .write()
+            .await
+            .retain(|_, sent_at| sent_at.elapsed() < window);
     }
 
     /// 发送 SDK 错误
@@ -1148,17 +2395,45 @@ impl DaemonService {
 }
 
 impl DaemonService {
-    /// 同步单条消息到共享数据库
-    async fn sync_message_to_shared_db(
+    /// 同步单个会话的 JSONL 到共享数据库（从同步水位开始），返回插入条数
+    async fn sync_session(
         &self,
         db: &Arc<SharedDbAdapter>,
         session_id: &str,
         project_path: &str,
-        message: &serde_json::Value,
-    ) -> Result<()> {
-        use claude_session_db::db::MessageInput;
-        use claude_session_db::MessageType;
+    ) -> Result<usize> {
+        let Some(path) = self.reader.write().await.get_session_path(session_id) else {
+            bail!("Session file not found");
+        };
+        self.sync_session_tail(db, session_id, project_path, Path::new(&path))
+            .await
+    }
 
+    /// 同步会话 JSONL 中同步水位之后的内容，并推进水位，返回插入条数
+    async fn sync_session_tail(
+        &self,
+        db: &Arc<SharedDbAdapter>,
+        session_id: &str,
+        project_path: &str,
+        session_path: &Path,
+    ) -> Result<usize> {
+        let from = db.synced_offset(session_id).await.unwrap_or(0);
+        let (messages, offset) = read_jsonl_tail(session_path, from)?;
+        let inserted = self
+            .sync_messages_to_shared_db(db, session_id, project_path, &messages)
+            .await?;
+        db.mark_synced(session_id, offset).await;
+        Ok(inserted)
+    }
+
+    /// 批量同步消息到共享数据库，返回插入条数
+    async fn sync_messages_to_shared_db(
+        &self,
+        db: &Arc<SharedDbAdapter>,
+        session_id: &str,
+        project_path: &str,
+        messages: &[serde_json::Value],
+    ) -> Result<usize> {
         // 提取项目名（路径最后一段）
         let project_name = project_path
             .split('/')
@@ -1172,101 +2447,97 @@ impl DaemonService {
         // 确保会话存在
         db.upsert_session(session_id, project_id).await?;
 
-        // 从 JSON 提取消息字段
-        let uuid = message.get("uuid")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default()
-            .to_string();
-
-        if uuid.is_empty() {
-            // 没有 uuid 的消息跳过（可能是系统消息）
-            return Ok(());
-        }
-
-        let type_str = message.get("type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("user");
-        let msg_type = match type_str {
-            "assistant" => MessageType::Assistant,
-            _ => MessageType::User,
-        };
-
-        // 提取内容
-        let content = if let Some(content) = message.get("message").and_then(|m| m.get("content")) {
-            // Claude 格式：message.content 可能是数组或字符串
-            if let Some(arr) = content.as_array() {
-                arr.iter()
-                    .filter_map(|item| {
-                        item.get("text").and_then(|t| t.as_str())
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            } else if let Some(s) = content.as_str() {
-                s.to_string()
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
-        };
-
-        // 提取时间戳
-        let timestamp = message.get("timestamp")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or_else(|| {
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map(|d| d.as_millis() as i64)
-                    .unwrap_or(0)
-            });
-
-        // 保存原始 JSON
-        let raw = serde_json::to_string(message).ok();
-
-        let msg_input = MessageInput {
-            uuid,
-            r#type: msg_type,
-            content_text: content.clone(),
-            content_full: content,
-            timestamp,
-            sequence: 0,
-            source: Some("claude".to_string()),
-            channel: Some("code".to_string()),
-            model: None,
-            tool_call_id: None,
-            tool_name: None,
-            tool_args: None,
-            raw,
-        };
-
-        let inserted = db.insert_messages(session_id, &[msg_input]).await?;
-        if inserted > 0 {
-            debug!("[SharedDB] Synced 1 message to shared database");
+        let inputs: Vec<_> = messages.iter().filter_map(message_to_input).collect();
+        if inputs.is_empty() {
+            return Ok(0);
         }
 
-        Ok(())
+        let inserted = db.insert_messages(session_id, &inputs).await?;
+        metrics::counter!(telemetry::DB_SYNC_MESSAGES).increment(inserted as u64);
+        Ok(inserted)
     }
 }
 
-fn format_tool_description(tool_name: &str, input: &serde_json::Value) -> String {
-    match tool_name {
-        "Bash" => format!(
-            "Execute: {}",
-            input.get("command").and_then(|v| v.as_str()).unwrap_or("unknown")
-        ),
-        "Write" => format!(
-            "Write file: {}",
-            input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown")
-        ),
-        "Edit" => format!(
-            "Edit file: {}",
-            input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown")
-        ),
-        "Delete" => format!(
-            "Delete file: {}",
-            input.get("file_path").and_then(|v| v.as_str()).unwrap_or("unknown")
-        ),
-        _ => format!("Call tool: {}", tool_name),
+/// 服务注册本地降级文件路径（`$VIMO_HOME/vlaude/registry.json`）
+fn registry_fallback_path() -> PathBuf {
+    let vimo_root = std::env::var("VIMO_HOME").unwrap_or_else(|_| {
+        let home = std::env::var("HOME").unwrap_or_default();
+        format!("{}/.vimo", home)
+    });
+    PathBuf::from(vimo_root).join("vlaude").join("registry.json")
+}
+
+/// 将 Claude JSONL 消息转换为共享数据库输入
+///
+/// 没有 uuid 的消息（可能是系统消息）返回 None。
+fn message_to_input(message: &serde_json::Value) -> Option<claude_session_db::db::MessageInput> {
+    use claude_session_db::db::MessageInput;
+    use claude_session_db::MessageType;
+
+    // 从 JSON 提取消息字段
+    let uuid = message.get("uuid")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    if uuid.is_empty() {
+        return None;
     }
+
+    let type_str = message.get("type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("user");
+    let msg_type = match type_str {
+        "assistant" => MessageType::Assistant,
+        _ => MessageType::User,
+    };
+
+    // 提取内容
+    let content = if let Some(content) = message.get("message").and_then(|m| m.get("content")) {
+        // Claude 格式：message.content 可能是数组或字符串
+        if let Some(arr) = content.as_array() {
+            arr.iter()
+                .filter_map(|item| {
+                    item.get("text").and_then(|t| t.as_str())
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else if let Some(s) = content.as_str() {
+            s.to_string()
+        } else {
+            String::new()
+        }
+    } else {
+        String::new()
+    };
+
+    // 提取时间戳
+    let timestamp = message.get("timestamp")
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0)
+        });
+
+    // 保存原始 JSON
+    let raw = serde_json::to_string(message).ok();
+
+    Some(MessageInput {
+        uuid,
+        r#type: msg_type,
+        content_text: content.clone(),
+        content_full: content,
+        timestamp,
+        sequence: 0,
+        source: Some("claude".to_string()),
+        channel: Some("code".to_string()),
+        model: None,
+        tool_call_id: None,
+        tool_name: None,
+        tool_args: None,
+        raw,
+    })
 }
@@ -2,8 +2,11 @@
 //!
 //! 可选集成 claude-session-db，实现与 Memex/ETerm 数据共享
 
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
@@ -11,9 +14,31 @@ use tracing::{debug, info, warn};
 use claude_session_db::{
     coordination::{Role, WriterHealth, WriterType},
     db::MessageInput,
-    DbConfig, Message, Project, SearchResult, Session, SessionDB,
+    DbConfig, Message, MessageType, Project, SearchResult, Session, SessionDB,
 };
 
+use crate::index_state::{self, IndexState};
+
+/// 导出会话时每页读取的消息数
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// 同步水位文件名（与数据库文件同目录）
+const SYNC_STATE_FILE: &str = "vlaude-sync-state.json";
+
+/// 同步水位持久化的最小间隔（期间的更新由下一次保存或 `flush_sync_state` 写入）
+const SYNC_STATE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 会话在共享 DB 中的数据新鲜度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbFreshness {
+    /// DB 中没有该会话的消息
+    Absent,
+    /// 有消息，但同步水位落后于 JSONL（需要同步尾部）
+    Stale,
+    /// 有消息，且已同步到 JSONL 末尾
+    Fresh,
+}
+
 /// 共享数据库适配器（Vlaude 版本）
 ///
 /// 与 Memex 共享同一数据库，实现：
@@ -24,6 +49,13 @@ pub struct SharedDbAdapter {
     role: Arc<RwLock<Role>>,
     heartbeat_cancel: Arc<RwLock<bool>>,
     heartbeat_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
+    /// 会话已同步到 DB 的 JSONL 字节偏移（session_id → offset），持久化到 `sync_state_path`
+    sync_offsets: Arc<RwLock<IndexState>>,
+    sync_state_path: PathBuf,
+    /// 上次保存同步水位的时间
+    sync_state_saved_at: Arc<RwLock<Option<Instant>>>,
+    /// 是否有尚未保存的同步水位
+    sync_state_dirty: AtomicBool,
 }
 
 impl SharedDbAdapter {
@@ -45,12 +77,18 @@ impl SharedDbAdapter {
         info!("[SharedDB] 连接共享数据库: {:?}", db_path);
         let config = DbConfig::local(db_path.to_string_lossy().into_owned());
         let db = SessionDB::connect(config)?;
+        let sync_state_path = db_path.with_file_name(SYNC_STATE_FILE);
+        let sync_offsets = index_state::load(&sync_state_path);
 
         Ok(Self {
             db: Arc::new(RwLock::new(db)),
             role: Arc::new(RwLock::new(Role::Reader)),
             heartbeat_cancel: Arc::new(RwLock::new(false)),
             heartbeat_handle: Arc::new(RwLock::new(None)),
+            sync_offsets: Arc::new(RwLock::new(sync_offsets)),
+            sync_state_path,
+            sync_state_saved_at: Arc::new(RwLock::new(None)),
+            sync_state_dirty: AtomicBool::new(false),
         })
     }
 
@@ -87,10 +125,14 @@ impl SharedDbAdapter {
         let cancel = self.heartbeat_cancel.clone();
 
         let handle = tokio::spawn(async move {
-            let interval = tokio::time::Duration::from_secs(10);
+            let period = tokio::time::Duration::from_secs(10);
+            // 系统休眠唤醒后丢弃错过的 tick，避免连续写入
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             loop {
-                tokio::time::sleep(interval).await;
+                interval.tick().await;
 
                 if *cancel.read().await {
                     debug!("[SharedDB] 心跳任务收到取消信号");
@@ -155,7 +197,23 @@ impl SharedDbAdapter {
     }
 
     /// 尝试接管
-    pub async fn try_takeover(&self) -> anyhow::Result<bool> {
+    ///
+    /// 接管前先等待 `takeover_grace_period_secs`，期间每秒检查一次 Writer 健康状态。
+    /// 如果原 Writer 恢复（如 GC 暂停、系统休眠后醒来），放弃接管并保持 Reader，避免双写。
+    pub async fn try_takeover(&self, takeover_grace_period_secs: u64) -> anyhow::Result<bool> {
+        let deadline = tokio::time::Instant::now()
+            + tokio::time::Duration::from_secs(takeover_grace_period_secs);
+        let poll_interval = tokio::time::Duration::from_secs(1);
+
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep_until((tokio::time::Instant::now() + poll_interval).min(deadline)).await;
+
+            if matches!(self.check_writer_health().await?, WriterHealth::Alive) {
+                info!("[SharedDB] 原 Writer 已恢复，放弃接管");
+                return Ok(false);
+            }
+        }
+
         // 重置取消标志
         *self.heartbeat_cancel.write().await = false;
 
@@ -191,6 +249,59 @@ impl SharedDbAdapter {
         Ok(db.insert_messages(session_id, messages)?)
     }
 
+    /// 会话的同步水位（已同步到 DB 的 JSONL 字节偏移）
+    pub async fn synced_offset(&self, session_id: &str) -> Option<u64> {
+        self.sync_offsets.read().await.get(session_id).copied()
+    }
+
+    /// 记录会话已同步到 JSONL 的 `offset` 处
+    ///
+    /// 水位文件每 5 秒最多写一次，其余更新由 `flush_sync_state` 写入；
+    /// 未保存的水位丢失时只会导致重新同步（DB 去重），不会误报新鲜。
+    pub async fn mark_synced(&self, session_id: &str, offset: u64) {
+        self.sync_offsets
+            .write()
+            .await
+            .insert(session_id.to_string(), offset);
+        self.sync_state_dirty.store(true, Ordering::SeqCst);
+
+        let due = self
+            .sync_state_saved_at
+            .read()
+            .await
+            .is_none_or(|at| at.elapsed() >= SYNC_STATE_SAVE_INTERVAL);
+        if due {
+            self.flush_sync_state().await;
+        }
+    }
+
+    /// 保存尚未持久化的同步水位
+    pub async fn flush_sync_state(&self) {
+        if !self.sync_state_dirty.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let sync_offsets = self.sync_offsets.read().await;
+        if let Err(e) = index_state::save(&self.sync_state_path, &sync_offsets) {
+            warn!("[SharedDB] 保存同步水位失败: {:?}: {}", self.sync_state_path, e);
+            self.sync_state_dirty.store(true, Ordering::SeqCst);
+        }
+        *self.sync_state_saved_at.write().await = Some(Instant::now());
+    }
+
+    /// 查询会话在 DB 中的数据新鲜度（`file_size` 为当前 JSONL 文件大小）
+    pub async fn freshness(&self, session_id: &str, file_size: u64) -> anyhow::Result<DbFreshness> {
+        let has_messages = {
+            let db = self.db.read().await;
+            !db.list_messages(session_id, 1, 0)?.is_empty()
+        };
+        if !has_messages {
+            return Ok(DbFreshness::Absent);
+        }
+
+        let fresh = self.synced_offset(session_id).await == Some(file_size);
+        Ok(if fresh { DbFreshness::Fresh } else { DbFreshness::Stale })
+    }
+
     // ==================== 数据查询 API ====================
 
     /// 按会话 ID 获取消息（优先从共享 DB 查询）
@@ -204,6 +315,51 @@ impl SharedDbAdapter {
         Ok(db.list_messages(session_id, limit, offset)?)
     }
 
+    /// 会话在 DB 中的消息数
+    ///
+    /// claude-session-db 没有按会话计数的接口，按偏移探测（每次只取一条），
+    /// 查询次数为 O(log n)，不读取消息内容。
+    pub async fn count_messages(&self, session_id: &str) -> anyhow::Result<usize> {
+        let db = self.db.read().await;
+        let exists = |offset: usize| -> anyhow::Result<bool> {
+            Ok(!db.list_messages(session_id, 1, offset)?.is_empty())
+        };
+        if !exists(0)? {
+            return Ok(0);
+        }
+
+        // 倍增找到上界，再二分：exists(low) 且 !exists(high)
+        let (mut low, mut high) = (0, 1);
+        while exists(high)? {
+            low = high;
+            high *= 2;
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if exists(mid)? {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(high)
+    }
+
+    /// 获取会话的全部消息（分页读取）
+    pub async fn get_all_messages(&self, session_id: &str) -> anyhow::Result<Vec<Message>> {
+        let db = self.db.read().await;
+        let mut messages = Vec::new();
+        loop {
+            let page = db.list_messages(session_id, EXPORT_PAGE_SIZE, messages.len())?;
+            let page_len = page.len();
+            messages.extend(page);
+            if page_len < EXPORT_PAGE_SIZE {
+                break;
+            }
+        }
+        Ok(messages)
+    }
+
     /// 列出项目
     pub async fn list_projects(&self) -> anyhow::Result<Vec<Project>> {
         let db = self.db.read().await;
@@ -227,6 +383,115 @@ impl SharedDbAdapter {
         let db = self.db.read().await;
         Ok(db.get_stats()?)
     }
+
+    // ==================== 数据恢复 API ====================
+
+    /// 从共享 DB 重建会话 JSONL 文件
+    ///
+    /// 优先写入入库时保存的原始 JSON，没有原始 JSON 时按 Claude Code 格式重建
+    /// `type`、`message`、`timestamp`、`uuid` 字段。目标文件已存在时报错，不会覆盖。
+    /// 返回写入的消息数。
+    pub async fn export_session_jsonl(
+        &self,
+        session_id: &str,
+        output_path: &Path,
+    ) -> anyhow::Result<usize> {
+        let messages = self.get_all_messages(session_id).await?;
+
+        if messages.is_empty() {
+            anyhow::bail!("Session not found in shared database: {}", session_id);
+        }
+
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::File::create_new(output_path)
+            .map_err(|e| anyhow::anyhow!("无法创建 {:?}: {}", output_path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        for message in &messages {
+            let line = match message.raw.as_deref() {
+                Some(raw) if serde_json::from_str::<serde_json::Value>(raw).is_ok() => {
+                    raw.to_string()
+                }
+                _ => {
+                    let msg_type = match message.r#type {
+                        MessageType::Assistant => "assistant",
+                        _ => "user",
+                    };
+                    rebuild_jsonl_message(
+                        session_id,
+                        &message.uuid,
+                        msg_type,
+                        &message.content_full,
+                        message.timestamp,
+                    )
+                    .to_string()
+                }
+            };
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+
+        info!(
+            "[SharedDB] Exported {} messages of session {} to {:?}",
+            messages.len(),
+            session_id,
+            output_path
+        );
+        Ok(messages.len())
+    }
+}
+
+/// 从 `from` 开始读取 JSONL 中的完整行，返回解析出的消息和读到的位置
+///
+/// 末尾没有换行的行（可能正在写入）留到下次读取。
+/// `from` 超过文件大小时（文件被重写）从头读取。
+pub(crate) fn read_jsonl_tail(path: &Path, from: u64) -> anyhow::Result<(Vec<serde_json::Value>, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let from = if from > file.metadata()?.len() { 0 } else { from };
+    file.seek(SeekFrom::Start(from))?;
+
+    let mut reader = BufReader::new(file);
+    let mut messages = Vec::new();
+    let mut position = from;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        position += read as u64;
+        if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&line) {
+            messages.push(value);
+        }
+    }
+    Ok((messages, position))
+}
+
+/// 按 Claude Code JSONL 格式重建一条消息
+fn rebuild_jsonl_message(
+    session_id: &str,
+    uuid: &str,
+    msg_type: &str,
+    content: &str,
+    timestamp_ms: i64,
+) -> serde_json::Value {
+    let timestamp = chrono::DateTime::from_timestamp_millis(timestamp_ms)
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default();
+
+    serde_json::json!({
+        "type": msg_type,
+        "uuid": uuid,
+        "sessionId": session_id,
+        "timestamp": timestamp,
+        "message": {
+            "role": msg_type,
+            "content": content,
+        },
+    })
 }
 
 impl Drop for SharedDbAdapter {
@@ -242,3 +507,43 @@ impl Drop for SharedDbAdapter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuild_jsonl_message() {
+        let value = rebuild_jsonl_message("s1", "u1", "assistant", "hello", 1_700_000_000_123);
+        assert_eq!(value["type"], "assistant");
+        assert_eq!(value["uuid"], "u1");
+        assert_eq!(value["sessionId"], "s1");
+        assert_eq!(value["timestamp"], "2023-11-14T22:13:20.123Z");
+        assert_eq!(value["message"]["role"], "assistant");
+        assert_eq!(value["message"]["content"], "hello");
+    }
+
+    #[test]
+    fn test_read_jsonl_tail() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"uuid\":\"u1\"}\n{\"uuid\":\"u2\"}\n{\"uuid\":").unwrap();
+
+        // 未写完的最后一行不计入水位
+        let (messages, offset) = read_jsonl_tail(&path, 0).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(offset, 28);
+
+        // 从水位继续只读取新增的行
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"\"u3\"}\n").unwrap();
+        let (messages, offset) = read_jsonl_tail(&path, offset).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["uuid"], "u3");
+        assert_eq!(offset, std::fs::metadata(&path).unwrap().len());
+
+        // 水位超过文件大小（文件被重写）时从头读取
+        let (messages, _) = read_jsonl_tail(&path, offset + 100).unwrap();
+        assert_eq!(messages.len(), 3);
+    }
+}
@@ -0,0 +1,54 @@
+//! 运行指标
+//!
+//! 通过 `metrics` 门面记录，计数器更新是无锁的原子操作。
+//! 未安装 recorder 时所有记录都是空操作，由可执行程序决定是否导出（如 Prometheus）。
+
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, Unit};
+use socket_client::{EmitMiddleware, SocketError};
+
+pub(crate) const MESSAGES_EMITTED: &str = "vlaude_messages_emitted_total";
+pub(crate) const RECONNECTS: &str = "vlaude_reconnects_total";
+pub(crate) const SESSIONS_WATCHING: &str = "vlaude_sessions_watching";
+pub(crate) const APPROVAL_REQUESTS: &str = "vlaude_approval_requests_total";
+pub(crate) const APPROVAL_TIMEOUTS: &str = "vlaude_approval_timeouts_total";
+pub(crate) const DB_SYNC_MESSAGES: &str = "vlaude_db_sync_messages_total";
+pub(crate) const INITIAL_PUSH_DURATION: &str = "vlaude_initial_push_duration_seconds";
+pub(crate) const HANDLER_DURATION: &str = "vlaude_event_handler_duration_seconds";
+
+/// 注册指标说明（安装 recorder 后调用一次）
+pub fn describe_metrics() {
+    describe_counter!(MESSAGES_EMITTED, "Socket events emitted to the server, by event name");
+    describe_counter!(RECONNECTS, "Successful reconnects to the server");
+    describe_gauge!(SESSIONS_WATCHING, "Sessions currently being watched");
+    describe_counter!(APPROVAL_REQUESTS, "Permission approval requests sent");
+    describe_counter!(APPROVAL_TIMEOUTS, "Permission approval requests that timed out");
+    describe_counter!(DB_SYNC_MESSAGES, "Messages written to the shared database");
+    describe_histogram!(
+        INITIAL_PUSH_DURATION,
+        Unit::Seconds,
+        "Time spent pushing initial project and session data"
+    );
+    describe_histogram!(
+        HANDLER_DURATION,
+        Unit::Seconds,
+        "Time spent handling server events, by event name and outcome"
+    );
+}
+
+/// 更新监听会话数
+pub(crate) fn set_sessions_watching(count: usize) {
+    gauge!(SESSIONS_WATCHING).set(count as f64);
+}
+
+/// 统计发送成功事件数的中间件
+pub struct MetricsMiddleware;
+
+impl EmitMiddleware for MetricsMiddleware {
+    fn before_emit(&self, _event: &str, _data: &mut serde_json::Value) {}
+
+    fn after_emit(&self, event: &str, result: &Result<(), SocketError>) {
+        if result.is_ok() {
+            counter!(MESSAGES_EMITTED, "event" => event.to_string()).increment(1);
+        }
+    }
+}
@@ -3,14 +3,34 @@
 //! 监听会话文件变化并增量解析新消息
 
 use anyhow::Result;
-use std::collections::HashMap;
+#[cfg(feature = "notify-watcher")]
+use session_reader::AsyncFileWatcher;
+use session_reader::{FileWatcher, WatchEvent, WatchMode};
+use socket_client::ContextMetrics;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+use crate::process::ClaudeProcessDetector;
+
+/// Claude Code 运行时的轮询间隔
+pub const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Claude Code 未运行时的轮询间隔
+pub const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 默认上下文窗口大小
+const DEFAULT_CONTEXT_WINDOW: u64 = 200_000;
+
+/// 1M 上下文窗口大小（模型名带 `[1m]` 后缀，或上下文已超过默认窗口）
+const EXTENDED_CONTEXT_WINDOW: u64 = 1_000_000;
+
 /// 监听事件
 #[derive(Debug, Clone)]
 pub enum SessionWatchEvent {
@@ -42,41 +62,265 @@ struct SessionState {
     path: PathBuf,
     project_path: String,
     last_position: u64,
+    /// 降级模式：只轮询，不为所在项目创建目录监听
+    polling_only: bool,
+}
+
+/// 整个 projects 目录的监听（notify 模式）
+#[cfg(feature = "notify-watcher")]
+struct TreeWatch {
+    watcher: AsyncFileWatcher,
+    /// 已知的会话 ID
+    known_sessions: HashSet<String>,
 }
 
 /// 会话监听器
+///
+/// 默认按间隔轮询被监听的会话；启用 `notify-watcher` 并调用 `watch_all_projects` 后，
+/// 由文件系统事件驱动（在 `select!` 中等待 `next_tree_events`），
+/// 没有被监听的会话时也会报告新建/删除的会话。
 pub struct SessionWatcher {
     /// 被监听的会话状态
     sessions: Arc<RwLock<HashMap<String, SessionState>>>,
+    /// Claude Code 进程检测（决定轮询间隔）
+    detector: ClaudeProcessDetector,
+    /// 上次轮询时间
+    last_poll: Mutex<Option<Instant>>,
+    /// projects 目录监听（None 时使用轮询），`next_tree_events` 等待期间持有锁
+    #[cfg(feature = "notify-watcher")]
+    tree: tokio::sync::Mutex<Option<TreeWatch>>,
+    /// 是否由 projects 目录监听驱动
+    tree_active: AtomicBool,
 }
 
 impl SessionWatcher {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            detector: ClaudeProcessDetector::new(),
+            last_poll: Mutex::new(None),
+            #[cfg(feature = "notify-watcher")]
+            tree: tokio::sync::Mutex::new(None),
+            tree_active: AtomicBool::new(false),
         }
     }
 
+    /// 递归监听整个 projects 目录（已有的会话不会触发事件）
+    #[cfg(feature = "notify-watcher")]
+    pub async fn watch_all_projects(&self, projects_root: &Path) -> Result<()> {
+        let (watcher, existing) =
+            FileWatcher::new_with_initial_scan(projects_root, WatchMode::AllProjects)?;
+        let known_sessions = existing
+            .iter()
+            .filter_map(|event| event.path())
+            .filter_map(|path| path.file_stem()?.to_str().map(str::to_string))
+            .collect();
+
+        info!("Start watching all projects at {:?}", projects_root);
+        *self.tree.lock().await = Some(TreeWatch {
+            watcher: watcher.into_async(),
+            known_sessions,
+        });
+        self.tree_active.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// 是否由 projects 目录监听驱动（否则需要轮询 `check_updates`）
+    pub fn is_watching_all_projects(&self) -> bool {
+        self.tree_active.load(Ordering::SeqCst)
+    }
+
+    /// 等待 projects 目录的下一批文件事件
+    ///
+    /// 取消安全，可以与 Socket 事件一起放在 `select!` 中；返回的事件交给 `handle_tree_events`。
+    /// 未启用目录监听时永远不会返回；监听意外停止时退回轮询。
+    #[cfg(feature = "notify-watcher")]
+    pub async fn next_tree_events(&self) -> Vec<WatchEvent> {
+        let mut tree = self.tree.lock().await;
+        let next = match tree.as_mut() {
+            Some(tree) => tree.watcher.next_event().await,
+            None => None,
+        };
+        match (next, tree.as_mut()) {
+            (Some(mut changes), Some(tree)) => {
+                while let Some(batch) = tree.watcher.try_next_event() {
+                    changes.extend(batch);
+                }
+                changes
+            }
+            _ => {
+                if tree.take().is_some() {
+                    warn!("Projects watcher stopped, falling back to polling");
+                    self.tree_active.store(false, Ordering::SeqCst);
+                }
+                drop(tree);
+                std::future::pending().await
+            }
+        }
+    }
+
+    /// 处理 projects 目录的文件事件
+    ///
+    /// 新会话报告 `SessionCreated`，删除报告 `SessionDeleted`，
+    /// 被监听会话的文件变化读取增量并报告 `NewMessage`。
+    /// 监听出错（事件可能丢失）时重新检查所有被监听的会话。
+    #[cfg(feature = "notify-watcher")]
+    pub async fn handle_tree_events(
+        &self,
+        changes: Vec<WatchEvent>,
+    ) -> Result<Vec<SessionWatchEvent>> {
+        let mut events = Vec::new();
+        let mut modified = HashSet::new();
+        let mut deleted = Vec::new();
+        let mut rescan = false;
+        {
+            let mut tree = self.tree.lock().await;
+            let Some(tree) = tree.as_mut() else {
+                return Ok(events);
+            };
+
+            for change in changes {
+                if let WatchEvent::Error(e) = &change {
+                    warn!("Projects watcher error, rescanning watched sessions: {}", e);
+                    rescan = true;
+                    continue;
+                }
+                let Some((project_path, session_id)) =
+                    change.path().and_then(|path| tree.watcher.session_of(path))
+                else {
+                    continue;
+                };
+                match change {
+                    WatchEvent::Removed(_) => {
+                        if tree.known_sessions.remove(&session_id) {
+                            deleted.push(session_id.clone());
+                            events.push(SessionWatchEvent::SessionDeleted {
+                                session_id,
+                                project_path,
+                            });
+                        }
+                    }
+                    _ => {
+                        if tree.known_sessions.insert(session_id.clone()) {
+                            events.push(SessionWatchEvent::SessionCreated {
+                                session_id: session_id.clone(),
+                                project_path,
+                            });
+                        }
+                        modified.insert(session_id);
+                    }
+                }
+            }
+        }
+
+        if !deleted.is_empty() {
+            let mut sessions = self.sessions.write().await;
+            for session_id in &deleted {
+                sessions.remove(session_id);
+            }
+        }
+        if rescan {
+            modified.extend(self.sessions.read().await.keys().cloned());
+        }
+
+        for session_id in modified {
+            events.extend(self.read_session_updates(&session_id).await);
+        }
+        Ok(events)
+    }
+
+    /// 当前轮询间隔：Claude Code 运行时 100ms，否则 5s
+    ///
+    /// 由 projects 目录监听驱动时固定为 5s，作为丢失文件事件时的兜底。
+    /// 进程检测在后台刷新，不阻塞调用方。
+    pub fn poll_interval(&self) -> Duration {
+        if self.is_watching_all_projects() {
+            return IDLE_POLL_INTERVAL;
+        }
+        if self.detector.is_running_cached() {
+            ACTIVE_POLL_INTERVAL
+        } else {
+            IDLE_POLL_INTERVAL
+        }
+    }
+
+    /// 是否到达轮询时间（到达时记录本次轮询）
+    ///
+    /// 调用方在返回 true 时执行 `check_updates`。
+    pub fn poll_due(&self) -> bool {
+        let interval = self.poll_interval();
+        let Ok(mut last_poll) = self.last_poll.lock() else {
+            return true;
+        };
+
+        let due = match *last_poll {
+            Some(t) => t.elapsed() >= interval,
+            None => true,
+        };
+        if due {
+            *last_poll = Some(Instant::now());
+        }
+        due
+    }
+
     /// 添加会话监听
     pub async fn watch_session(
         &self,
         session_id: &str,
         session_path: &Path,
         project_path: &str,
+    ) -> Result<()> {
+        self.insert_session(session_id, session_path, project_path, false)
+            .await
+    }
+
+    /// 以降级模式添加会话监听（超过监听上限时使用）
+    ///
+    /// 会话仍由 check_updates 轮询，但不计入 `watched_projects`，不占用目录监听的文件句柄。
+    pub async fn watch_session_polling_only(
+        &self,
+        session_id: &str,
+        session_path: &Path,
+        project_path: &str,
+    ) -> Result<()> {
+        self.insert_session(session_id, session_path, project_path, true)
+            .await
+    }
+
+    async fn insert_session(
+        &self,
+        session_id: &str,
+        session_path: &Path,
+        project_path: &str,
+        polling_only: bool,
     ) -> Result<()> {
         info!("Start watching session: {} at {:?}", session_id, session_path);
 
-        let position = if session_path.exists() {
+        let size = if session_path.exists() {
             let metadata = std::fs::metadata(session_path)?;
             metadata.len()
         } else {
             0
         };
 
+        // 重复监听同一文件时，保留上次读到的位置，期间写入的消息由 check_updates 补发
+        let position = match self.sessions.read().await.get(session_id) {
+            Some(state) if state.path == session_path && state.last_position < size => {
+                info!(
+                    "Session {} has {} unread bytes since last poll",
+                    session_id,
+                    size - state.last_position
+                );
+                state.last_position
+            }
+            _ => size,
+        };
+
         let state = SessionState {
             path: session_path.to_path_buf(),
             project_path: project_path.to_string(),
             last_position: position,
+            polling_only,
         };
 
         self.sessions
@@ -125,31 +369,10 @@ impl SessionWatcher {
                 continue;
             }
 
-            match Self::read_incremental_static(&path, last_position) {
-                Ok((new_messages, new_position)) => {
-                    // 更新位置
-                    if new_position > last_position {
-                        let mut sessions = self.sessions.write().await;
-                        if let Some(state) = sessions.get_mut(&session_id) {
-                            state.last_position = new_position;
-                        }
-                    }
-
-                    for msg in new_messages {
-                        events.push(SessionWatchEvent::NewMessage {
-                            session_id: session_id.clone(),
-                            project_path: project_path.clone(),
-                            message: msg,
-                        });
-                    }
-                }
-                Err(e) => {
-                    events.push(SessionWatchEvent::Error {
-                        session_id: session_id.clone(),
-                        error: e.to_string(),
-                    });
-                }
-            }
+            events.extend(
+                self.read_new_messages(&session_id, &path, &project_path, last_position)
+                    .await,
+            );
         }
 
         // 移除已删除的会话
@@ -163,25 +386,132 @@ impl SessionWatcher {
         Ok(events)
     }
 
+    /// 未启用 `notify-watcher` 时永远不会返回
+    #[cfg(not(feature = "notify-watcher"))]
+    pub async fn next_tree_events(&self) -> Vec<WatchEvent> {
+        std::future::pending().await
+    }
+
+    /// 未启用 `notify-watcher` 时没有目录监听事件
+    #[cfg(not(feature = "notify-watcher"))]
+    pub async fn handle_tree_events(
+        &self,
+        _changes: Vec<WatchEvent>,
+    ) -> Result<Vec<SessionWatchEvent>> {
+        Ok(Vec::new())
+    }
+
+    /// 读取单个被监听会话的增量（未被监听时为空）
+    #[cfg(feature = "notify-watcher")]
+    async fn read_session_updates(&self, session_id: &str) -> Vec<SessionWatchEvent> {
+        let snapshot = self
+            .sessions
+            .read()
+            .await
+            .get(session_id)
+            .map(|state| (state.path.clone(), state.project_path.clone(), state.last_position));
+        match snapshot {
+            Some((path, project_path, last_position)) => {
+                self.read_new_messages(session_id, &path, &project_path, last_position)
+                    .await
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// 从 `last_position` 读取新消息并推进会话位置
+    async fn read_new_messages(
+        &self,
+        session_id: &str,
+        path: &Path,
+        project_path: &str,
+        last_position: u64,
+    ) -> Vec<SessionWatchEvent> {
+        match Self::read_incremental_static(path, last_position) {
+            Ok((new_messages, new_position)) => {
+                // 更新位置
+                if new_position > last_position {
+                    let mut sessions = self.sessions.write().await;
+                    if let Some(state) = sessions.get_mut(session_id) {
+                        state.last_position = new_position;
+                    }
+                }
+
+                new_messages
+                    .into_iter()
+                    .map(|msg| SessionWatchEvent::NewMessage {
+                        session_id: session_id.to_string(),
+                        project_path: project_path.to_string(),
+                        message: msg,
+                    })
+                    .collect()
+            }
+            Err(e) => vec![SessionWatchEvent::Error {
+                session_id: session_id.to_string(),
+                error: e.to_string(),
+            }],
+        }
+    }
+
+    /// 回放会话历史消息
+    ///
+    /// 从 `from_position` 读取到当前监听位置，返回期间的全部消息。
+    /// 之后的内容由 check_updates 增量推送，避免重复。
+    pub async fn replay_session(
+        &self,
+        session_id: &str,
+        from_position: u64,
+    ) -> Result<Vec<serde_json::Value>> {
+        let (path, end_position) = {
+            let sessions = self.sessions.read().await;
+            let state = sessions
+                .get(session_id)
+                .ok_or_else(|| anyhow::anyhow!("Session not watched: {}", session_id))?;
+            (state.path.clone(), state.last_position)
+        };
+
+        if from_position >= end_position {
+            return Ok(Vec::new());
+        }
+
+        info!(
+            "Replaying session {} from position {} to {}",
+            session_id, from_position, end_position
+        );
+
+        let (messages, _) = Self::read_range_static(&path, from_position, Some(end_position))?;
+        Ok(messages)
+    }
+
     /// 读取增量内容（静态方法，不需要锁）
     fn read_incremental_static(
         path: &Path,
         last_position: u64,
+    ) -> Result<(Vec<serde_json::Value>, u64)> {
+        Self::read_range_static(path, last_position, None)
+    }
+
+    /// 读取 [start, end) 区间内的完整行，end 为 None 时读到文件末尾
+    fn read_range_static(
+        path: &Path,
+        start: u64,
+        end: Option<u64>,
     ) -> Result<(Vec<serde_json::Value>, u64)> {
         let file = File::open(path)?;
         let metadata = file.metadata()?;
         let current_size = metadata.len();
+        let end = end.map_or(current_size, |e| e.min(current_size));
 
-        if current_size <= last_position {
-            return Ok((Vec::new(), last_position));
+        if end <= start {
+            return Ok((Vec::new(), start));
         }
 
         let mut file = file;
-        file.seek(SeekFrom::Start(last_position))?;
+        file.seek(SeekFrom::Start(start))?;
 
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(file.take(end - start));
         let mut messages = Vec::new();
-        let mut new_position = last_position;
+        let mut new_position = start;
 
         for line in reader.lines() {
             match line {
@@ -213,6 +543,69 @@ impl SessionWatcher {
     pub async fn session_count(&self) -> usize {
         self.sessions.read().await.len()
     }
+
+    /// 被监听会话所在的项目（project_path → 项目目录，不含降级模式的会话）
+    pub async fn watched_projects(&self) -> HashMap<String, PathBuf> {
+        self.sessions
+            .read()
+            .await
+            .values()
+            .filter(|state| !state.polling_only)
+            .filter_map(|state| {
+                let dir = state.path.parent()?;
+                Some((state.project_path.clone(), dir.to_path_buf()))
+            })
+            .collect()
+    }
+
+    /// 从助手消息的 `usage` 字段提取 Metrics
+    ///
+    /// 侧链（agent）消息和 API 错误消息不反映主会话的上下文，返回 None。
+    pub fn extract_metrics(message: &serde_json::Value) -> Option<ContextMetrics> {
+        if message.get("type").and_then(|v| v.as_str()) != Some("assistant") {
+            return None;
+        }
+        let is_set = |key: &str| message.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        if is_set("isSidechain") || is_set("isApiErrorMessage") {
+            return None;
+        }
+
+        let usage = message.get("message")?.get("usage")?;
+        let model = message
+            .get("message")
+            .and_then(|m| m.get("model"))
+            .and_then(|v| v.as_str());
+        let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let input_tokens = tokens("input_tokens");
+        let context_length = input_tokens
+            + tokens("cache_read_input_tokens")
+            + tokens("cache_creation_input_tokens");
+
+        let context_window = Self::context_window(model, context_length);
+
+        Some(ContextMetrics {
+            model: model.map(str::to_string),
+            context_length,
+            context_window,
+            context_percentage: context_length as f64 / context_window as f64 * 100.0,
+            input_tokens,
+            output_tokens: tokens("output_tokens"),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        })
+    }
+
+    /// 按模型推断上下文窗口大小
+    ///
+    /// 会话文件只记录模型 ID，1M 窗口的会话不一定带 `[1m]` 后缀，
+    /// 因此上下文长度超过默认窗口时也按 1M 计算。
+    fn context_window(model: Option<&str>, context_length: u64) -> u64 {
+        let extended = model.is_some_and(|m| m.to_ascii_lowercase().ends_with("[1m]"));
+        if extended || context_length > DEFAULT_CONTEXT_WINDOW {
+            EXTENDED_CONTEXT_WINDOW
+        } else {
+            DEFAULT_CONTEXT_WINDOW
+        }
+    }
 }
 
 impl Default for SessionWatcher {
@@ -221,6 +614,98 @@ impl Default for SessionWatcher {
     }
 }
 
+/// 项目监听器
+///
+/// 监听项目目录，发现新建的会话文件（`*.jsonl`，不含 agent 会话）。
+pub struct ProjectWatcher {
+    project_path: String,
+    project_dir: PathBuf,
+    watcher: Mutex<FileWatcher>,
+    /// 已知的会话 ID
+    known_sessions: Mutex<HashSet<String>>,
+}
+
+impl ProjectWatcher {
+    /// 开始监听项目目录（目录中已有的会话不会触发事件）
+    pub fn new(project_dir: &Path, project_path: &str) -> Result<Self> {
+        let (watcher, existing) = FileWatcher::new_with_initial_scan(project_dir, WatchMode::Sessions)?;
+
+        let known_sessions = existing
+            .iter()
+            .filter_map(|event| event.path().and_then(Self::session_id_of))
+            .collect();
+
+        info!("Start watching project: {} at {:?}", project_path, project_dir);
+
+        Ok(Self {
+            project_path: project_path.to_string(),
+            project_dir: project_dir.to_path_buf(),
+            watcher: Mutex::new(watcher),
+            known_sessions: Mutex::new(known_sessions),
+        })
+    }
+
+    /// 项目路径
+    pub fn project_path(&self) -> &str {
+        &self.project_path
+    }
+
+    /// 项目目录
+    pub fn project_dir(&self) -> &Path {
+        &self.project_dir
+    }
+
+    /// 检查目录变化（非阻塞），返回新建会话事件
+    pub fn check_updates(&self) -> Vec<SessionWatchEvent> {
+        let mut changes = Vec::new();
+        {
+            let watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+            while let Some(batch) = watcher.try_next_event() {
+                changes.extend(batch);
+            }
+        }
+
+        let mut known = self.known_sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut events = Vec::new();
+        for change in changes {
+            match change {
+                WatchEvent::Created(path) | WatchEvent::Modified { path, .. } => {
+                    let Some(session_id) = Self::session_id_of(&path) else {
+                        continue;
+                    };
+                    if known.insert(session_id.clone()) {
+                        events.push(SessionWatchEvent::SessionCreated {
+                            session_id,
+                            project_path: self.project_path.clone(),
+                        });
+                    }
+                }
+                WatchEvent::Removed(path) => {
+                    if let Some(session_id) = Self::session_id_of(&path) {
+                        known.remove(&session_id);
+                    }
+                }
+                WatchEvent::Error(e) => {
+                    warn!("Project watcher error for {}: {}", self.project_path, e);
+                }
+            }
+        }
+        events
+    }
+
+    /// 从会话文件路径提取 session_id（非会话文件返回 None）
+    fn session_id_of(path: &Path) -> Option<String> {
+        if path.extension()? != "jsonl" {
+            return None;
+        }
+        let session_id = path.file_stem()?.to_str()?;
+        if session_id.starts_with("agent-") {
+            return None;
+        }
+        Some(session_id.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +719,99 @@ mod tests {
         assert!(!watcher.has_sessions().await);
     }
 
+    #[test]
+    fn test_extract_metrics() {
+        let message = serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "usage": {
+                    "input_tokens": 1000,
+                    "cache_read_input_tokens": 40000,
+                    "cache_creation_input_tokens": 9000,
+                    "output_tokens": 250
+                }
+            }
+        });
+        let metrics = SessionWatcher::extract_metrics(&message).unwrap();
+        assert_eq!(metrics.model, None);
+        assert_eq!(metrics.context_length, 50000);
+        assert_eq!(metrics.context_window, 200_000);
+        assert_eq!(metrics.context_percentage, 25.0);
+        assert_eq!(metrics.input_tokens, 1000);
+        assert_eq!(metrics.output_tokens, 250);
+
+        let mut sidechain = message.clone();
+        sidechain["isSidechain"] = serde_json::json!(true);
+        assert!(SessionWatcher::extract_metrics(&sidechain).is_none());
+
+        let user = serde_json::json!({"type": "user", "message": {"content": "hi"}});
+        assert!(SessionWatcher::extract_metrics(&user).is_none());
+    }
+
+    #[test]
+    fn test_context_window() {
+        let mut message = serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "model": "claude-sonnet-4-5[1m]",
+                "usage": {"input_tokens": 100000}
+            }
+        });
+        let metrics = SessionWatcher::extract_metrics(&message).unwrap();
+        assert_eq!(metrics.model.as_deref(), Some("claude-sonnet-4-5[1m]"));
+        assert_eq!(metrics.context_window, 1_000_000);
+        assert_eq!(metrics.context_percentage, 10.0);
+
+        // 未标注 1M 但上下文已超过默认窗口
+        message["message"]["model"] = serde_json::json!("claude-sonnet-4-5");
+        message["message"]["usage"]["input_tokens"] = serde_json::json!(300000);
+        let metrics = SessionWatcher::extract_metrics(&message).unwrap();
+        assert_eq!(metrics.context_window, 1_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_rewatch_keeps_unread_messages() {
+        let watcher = SessionWatcher::new();
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, r#"{{"type":"user","message":"hello"}}"#).unwrap();
+        temp_file.flush().unwrap();
+
+        watcher
+            .watch_session("test-session", temp_file.path(), "/test/project")
+            .await
+            .unwrap();
+
+        // 两次监听之间写入的消息不丢失
+        writeln!(temp_file, r#"{{"type":"assistant","message":"hi"}}"#).unwrap();
+        temp_file.flush().unwrap();
+        watcher
+            .watch_session("test-session", temp_file.path(), "/test/project")
+            .await
+            .unwrap();
+
+        let events = watcher.check_updates().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            SessionWatchEvent::NewMessage { message, .. } if message["type"] == "assistant"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_polling_only_session_has_no_project_watch() {
+        let watcher = SessionWatcher::new();
+        let temp_file = NamedTempFile::new().unwrap();
+
+        watcher
+            .watch_session_polling_only("test-session", temp_file.path(), "/test/project")
+            .await
+            .unwrap();
+
+        assert_eq!(watcher.session_count().await, 1);
+        assert!(watcher.watched_projects().await.is_empty());
+    }
+
     #[tokio::test]
     async fn test_watch_and_unwatch() {
         let watcher = SessionWatcher::new();
@@ -284,4 +862,113 @@ mod tests {
             panic!("Expected NewMessage event");
         }
     }
+
+    #[tokio::test]
+    async fn test_replay_session() {
+        let watcher = SessionWatcher::new();
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, r#"{{"type":"user","message":"first"}}"#).unwrap();
+        let first_len = std::fs::metadata(temp_file.path()).unwrap().len();
+        writeln!(temp_file, r#"{{"type":"assistant","message":"second"}}"#).unwrap();
+        temp_file.flush().unwrap();
+
+        watcher
+            .watch_session("test-session", temp_file.path(), "/test/project")
+            .await
+            .unwrap();
+
+        let messages = watcher.replay_session("test-session", 0).await.unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let messages = watcher
+            .replay_session("test-session", first_len)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["type"], "assistant");
+
+        // 回放不影响后续增量读取
+        let events = watcher.check_updates().await.unwrap();
+        assert!(events.is_empty());
+
+        assert!(watcher.replay_session("unknown", 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_project_watcher_session_created() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("existing.jsonl"), "{}\n").unwrap();
+
+        let watcher = ProjectWatcher::new(dir.path(), "/test/project").unwrap();
+        assert!(watcher.check_updates().is_empty());
+
+        std::fs::write(dir.path().join("new-session.jsonl"), "{}\n").unwrap();
+        std::fs::write(dir.path().join("agent-123.jsonl"), "{}\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "x").unwrap();
+
+        let mut created = Vec::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            created.extend(watcher.check_updates());
+            if !created.is_empty() {
+                break;
+            }
+        }
+
+        assert_eq!(created.len(), 1);
+        match &created[0] {
+            SessionWatchEvent::SessionCreated { session_id, project_path } => {
+                assert_eq!(session_id, "new-session");
+                assert_eq!(project_path, "/test/project");
+            }
+            other => panic!("Expected SessionCreated, got {:?}", other),
+        }
+
+        // 再次修改同一文件不会重复通知
+        std::fs::write(dir.path().join("new-session.jsonl"), "{}\n{}\n").unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(watcher.check_updates().is_empty());
+    }
+
+    #[cfg(feature = "notify-watcher")]
+    #[tokio::test]
+    async fn test_watch_all_projects() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let project_dir = dir.path().join("-tmp-demo");
+        std::fs::create_dir(&project_dir).unwrap();
+        let line = "{\"type\":\"user\",\"cwd\":\"/tmp/demo\"}\n";
+        std::fs::write(project_dir.join("old.jsonl"), line).unwrap();
+
+        let watcher = SessionWatcher::new();
+        watcher.watch_all_projects(dir.path()).await.unwrap();
+        assert!(watcher.is_watching_all_projects());
+        // 没有被监听的会话也会报告新建
+        assert!(!watcher.has_sessions().await);
+
+        std::fs::write(project_dir.join("new.jsonl"), line).unwrap();
+
+        let changes = tokio::time::timeout(Duration::from_secs(5), watcher.next_tree_events())
+            .await
+            .expect("timed out waiting for tree events");
+        let events = watcher.handle_tree_events(changes).await.unwrap();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            SessionWatchEvent::SessionCreated { session_id, project_path } => {
+                assert_eq!(session_id, "new");
+                assert_eq!(project_path, "/tmp/demo");
+            }
+            other => panic!("Expected SessionCreated, got {:?}", other),
+        }
+
+        std::fs::remove_file(project_dir.join("new.jsonl")).unwrap();
+        let changes = tokio::time::timeout(Duration::from_secs(5), watcher.next_tree_events())
+            .await
+            .expect("timed out waiting for tree events");
+        let events = watcher.handle_tree_events(changes).await.unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [SessionWatchEvent::SessionDeleted { session_id, .. }] if session_id == "new"
+        ));
+    }
 }
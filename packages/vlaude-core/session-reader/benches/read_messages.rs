@@ -0,0 +1,63 @@
+//! 大会话文件读取基准：BufReader（read_messages_raw）与内存映射（read_messages_mmap）
+//!
+//! 运行：`cargo bench -p session-reader --bench read_messages`
+//! 测试数据在运行时生成（约 50 MB），不提交到仓库。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use session_reader::{ClaudeReader, Order};
+use tempfile::TempDir;
+
+/// 生成的会话文件大小
+const FIXTURE_BYTES: usize = 50 * 1024 * 1024;
+
+/// 生成约 50 MB 的会话文件（用户 / 助手消息交替）
+fn write_fixture(dir: &TempDir) -> String {
+    let path = dir.path().join("large-session.jsonl");
+    let mut writer = BufWriter::new(File::create(&path).unwrap());
+    let text = "lorem ipsum dolor sit amet ".repeat(40);
+
+    let mut written = 0;
+    let mut i = 0;
+    while written < FIXTURE_BYTES {
+        let line = if i % 2 == 0 {
+            format!(
+                r#"{{"type":"user","uuid":"u-{i}","sessionId":"bench","message":{{"role":"user","content":"{text}"}}}}"#
+            )
+        } else {
+            format!(
+                r#"{{"type":"assistant","uuid":"a-{i}","sessionId":"bench","message":{{"role":"assistant","content":[{{"type":"text","text":"{text}"}}]}}}}"#
+            )
+        };
+        writeln!(writer, "{}", line).unwrap();
+        written += line.len() + 1;
+        i += 1;
+    }
+    writer.flush().unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn bench_read_messages(c: &mut Criterion) {
+    let dir = TempDir::new().unwrap();
+    let path = write_fixture(&dir);
+    let reader = ClaudeReader::new(dir.path().to_path_buf());
+
+    let mut group = c.benchmark_group("read_messages_50mb");
+    group.sample_size(10);
+
+    for (name, order) in [("asc", Order::Asc), ("desc", Order::Desc)] {
+        group.bench_function(format!("bufreader_{}", name), |b| {
+            b.iter(|| reader.read_messages_raw(&path, 50, 0, order).unwrap())
+        });
+        group.bench_function(format!("mmap_{}", name), |b| {
+            b.iter(|| reader.read_messages_mmap(&path, 50, 0, order).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_read_messages);
+criterion_main!(benches);
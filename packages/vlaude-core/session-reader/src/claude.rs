@@ -2,36 +2,492 @@
 //!
 //! 薄封装 claude-session-db::SessionReader，提供 vlaude-core 专用的接口。
 //! 所有业务逻辑在 claude-session-db 中实现，这里只做委托。
+//!
+//! 例外：claude-session-db 未提供、且只有 vlaude 使用的部分暂时留在这里，
+//! 包括 `file_size` 的补全（适配器未填充时按 mtime 缓存读取）、星标、分页、
+//! 过滤与导出。上游提供对应接口后应改为委托并删除本地实现。
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use std::path::PathBuf;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use memchr::memmem;
+use memmap2::MmapOptions;
+use rayon::prelude::*;
+
+use tempfile::TempDir;
 
 use claude_session_db::{
     IndexableSession, ParseResult, SessionMeta,
     SessionReader as DbSessionReader,
 };
 
+use crate::git::GitBranchCache;
+use crate::iter::MessageIter;
+use crate::pagination::{page_after, SessionCursor};
+use crate::search::{self, MessageMatcher, SubstringMatcher};
+use crate::sidecar::StarCache;
+use crate::stats::{ReaderCounters, ReaderStats};
 use crate::types::*;
 
+/// 长操作检查取消标志的间隔（行数）
+const CANCEL_CHECK_INTERVAL: usize = 1000;
+
+/// 查找会话所属项目时最多读取的行数（cwd 一般出现在前几行）
+const CWD_SCAN_LINES: usize = 50;
+
+/// 读取最后一条消息时先尝试读取的文件尾部大小
+const TAIL_READ_BYTES: u64 = 256 * 1024;
+
+/// Claude Code 配置目录覆盖（默认 `~/.claude`）
+const CLAUDE_CONFIG_DIR_ENV: &str = "CLAUDE_CONFIG_DIR";
+
+/// 项目统计缓存有效期
+const PROJECT_STATS_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 小于该大小的会话文件不使用内存映射（映射开销大于收益）
+const MMAP_MIN_BYTES: u64 = 1024 * 1024;
+
+/// 校验会话 ID 可以安全地用作文件名
+fn validate_session_id(session_id: &str) -> anyhow::Result<()> {
+    if session_id.is_empty() || session_id.starts_with('.') || session_id.contains(['/', '\\']) {
+        anyhow::bail!("无效的会话 ID: {}", session_id);
+    }
+    Ok(())
+}
+
+/// 从会话 JSONL 前几行读取工作目录（即项目路径）
+fn read_session_cwd(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .take(CWD_SCAN_LINES)
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+        .find_map(|value| value.get("cwd").and_then(|v| v.as_str()).map(str::to_string))
+}
+
+/// 从项目目录下会话 JSONL 记录的 `cwd` 还原项目路径（只接受重新编码后与目录名一致的路径）
+pub(crate) fn decode_project_dir(project_dir: &Path) -> Option<String> {
+    let encoded_dir = project_dir.file_name()?.to_str()?;
+    std::fs::read_dir(project_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| read_session_cwd(&path))
+        .find(|cwd| encode_project_path(cwd) == encoded_dir)
+}
+
+/// 消息行的顶层 `type` 字段（其余字段跳过，不分配）
+#[derive(serde::Deserialize)]
+struct LineType<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Option<std::borrow::Cow<'a, str>>,
+}
+
+/// 是否为完整的用户或助手消息行
+///
+/// 先按子串快速排除，再解析确认顶层 `type`：嵌套在内容中的 `"type":"user"` 不算，
+/// 未写完（不是合法 JSON）的行也不算。
+pub(crate) fn is_message_line(line: &[u8]) -> bool {
+    let candidate = memmem::find(line, br#""type":"user""#).is_some()
+        || memmem::find(line, br#""type":"assistant""#).is_some();
+    candidate
+        && serde_json::from_slice::<LineType>(line)
+            .is_ok_and(|l| matches!(l.kind.as_deref(), Some("user" | "assistant")))
+}
+
+/// 从 JSONL 内容中分页读取原始消息
+fn read_raw_messages_from_bytes(
+    data: &[u8],
+    limit: usize,
+    offset: usize,
+    order: Order,
+) -> RawMessagesResult {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in memchr::memchr_iter(b'\n', data).chain(std::iter::once(data.len())) {
+        if start < end && is_message_line(&data[start..end]) {
+            lines.push(start..end);
+        }
+        start = end + 1;
+    }
+
+    if order == Order::Desc {
+        lines.reverse();
+    }
+
+    let total = lines.len();
+    let messages: Vec<serde_json::Value> = lines
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        // 不完整的行已在计数时排除
+        .filter_map(|range| serde_json::from_slice(&data[range]).ok())
+        .collect();
+    let has_more = offset.saturating_add(limit) < total;
+
+    RawMessagesResult {
+        messages,
+        total,
+        has_more,
+    }
+}
+
+/// 单次遍历会话文件得到的统计
+#[derive(Default)]
+struct SessionScan {
+    user_messages: usize,
+    assistant_messages: usize,
+    /// 用户与助手消息的文本字符数（没有 usage 时用于估算 token）
+    text_chars: usize,
+    /// 第一行的时间（毫秒）
+    created: Option<u64>,
+    /// 第一条与最后一条消息的时间（毫秒）
+    first_message_at: Option<u64>,
+    last_message_at: Option<u64>,
+    /// 助手消息 usage 之和 (输入 token（含缓存读写）, 输出 token)，没有 usage 时为 None
+    usage: Option<(usize, usize)>,
+    /// 助手消息使用的模型
+    models: std::collections::BTreeSet<String>,
+}
+
+impl SessionScan {
+    /// 遍历一次会话，同时统计消息数、时长、token 与模型
+    ///
+    /// 同一条助手消息按内容块拆成多行写入，每行带相同的 `message.id` 和 usage，只计一次。
+    fn scan(messages: MessageIter) -> Self {
+        let mut scan = Self::default();
+        let mut seen = std::collections::HashSet::new();
+        let mut first = true;
+        for line in messages {
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+            let timestamp = value
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.timestamp_millis() as u64);
+            if std::mem::take(&mut first) {
+                scan.created = timestamp;
+            }
+
+            match value.get("type").and_then(|v| v.as_str()) {
+                Some("user") => scan.user_messages += 1,
+                Some("assistant") => {
+                    scan.assistant_messages += 1;
+                    if let Some(model) = value.pointer("/message/model").and_then(|v| v.as_str()) {
+                        scan.models.insert(model.to_string());
+                    }
+                    let id = value.pointer("/message/id").and_then(|v| v.as_str());
+                    if let Some(usage) = value.pointer("/message/usage") {
+                        if id.is_none_or(|id| seen.insert(id.to_string())) {
+                            let tokens = |field: &str| {
+                                usage.get(field).and_then(|v| v.as_u64()).unwrap_or(0) as usize
+                            };
+                            let (input, output) = scan.usage.get_or_insert((0, 0));
+                            *input += tokens("input_tokens")
+                                + tokens("cache_creation_input_tokens")
+                                + tokens("cache_read_input_tokens");
+                            *output += tokens("output_tokens");
+                        }
+                    }
+                }
+                _ => continue,
+            }
+
+            scan.text_chars += search::message_text(&value).chars().count();
+            if let Some(timestamp) = timestamp {
+                scan.first_message_at.get_or_insert(timestamp);
+                scan.last_message_at = Some(timestamp);
+            }
+        }
+        scan
+    }
+
+    /// 会话 Metrics（没有消息时返回 None）
+    ///
+    /// 有 usage 时 `estimated_tokens` 为实际 token 之和，否则按字符数 / 4 估算。
+    fn metrics(&self) -> Option<SessionMetrics> {
+        let message_count = self.user_messages + self.assistant_messages;
+        if message_count == 0 {
+            return None;
+        }
+        Some(SessionMetrics {
+            message_count,
+            user_message_count: self.user_messages,
+            assistant_message_count: self.assistant_messages,
+            estimated_tokens: self
+                .usage
+                .map_or(self.text_chars / 4, |(input, output)| input + output),
+            actual_input_tokens: self.usage.map(|(input, _)| input),
+            actual_output_tokens: self.usage.map(|(_, output)| output),
+            duration_seconds: self
+                .first_message_at
+                .zip(self.last_message_at)
+                .map(|(first, last)| last.saturating_sub(first) / 1000),
+        })
+    }
+}
+
+/// `ClaudeReader::from_json` 的项目描述
+#[derive(serde::Deserialize)]
+struct MockProjects {
+    projects: Vec<MockProject>,
+}
+
+#[derive(serde::Deserialize)]
+struct MockProject {
+    path: String,
+    #[serde(default)]
+    sessions: Vec<MockSession>,
+}
+
+#[derive(serde::Deserialize)]
+struct MockSession {
+    id: String,
+    mtime: Option<u64>,
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+}
+
+/// 会话文件修改时间（毫秒），缺失时视为 0
+fn session_mtime(meta: &SessionMeta) -> u64 {
+    meta.file_mtime.unwrap_or(0)
+}
+
+/// 压缩单个会话文件到归档路径，成功后删除原文件
+///
+/// 目标已存在时报错，不覆盖已有归档；压缩失败时清理写了一半的目标文件。
+fn archive_session_file(source: &Path, target: &Path) -> anyhow::Result<()> {
+    if target.exists() {
+        anyhow::bail!("归档文件已存在: {:?}", target);
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let compress = || -> std::io::Result<()> {
+        let mut input = File::open(source)?;
+        let mut encoder = GzEncoder::new(File::create(target)?, Compression::default());
+        std::io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    };
+    if let Err(e) = compress() {
+        let _ = std::fs::remove_file(target);
+        return Err(e.into());
+    }
+
+    std::fs::remove_file(source)?;
+    Ok(())
+}
+
+/// Claude Code 配置目录（`$CLAUDE_CONFIG_DIR` 或 `~/.claude`）
+///
+/// 设置了 `CLAUDE_CONFIG_DIR` 但目录不存在时报错，避免静默读取空目录。
+pub fn claude_config_dir() -> anyhow::Result<PathBuf> {
+    config_dir_from_vars(|name| std::env::var_os(name))
+}
+
+/// 默认的 projects 目录（`<配置目录>/projects`）
+fn default_projects_path() -> anyhow::Result<PathBuf> {
+    Ok(claude_config_dir()?.join("projects"))
+}
+
+fn config_dir_from_vars(var: impl Fn(&str) -> Option<OsString>) -> anyhow::Result<PathBuf> {
+    if let Some(config_dir) = var(CLAUDE_CONFIG_DIR_ENV).filter(|v| !v.is_empty()) {
+        let config_dir = PathBuf::from(config_dir);
+        if !config_dir.is_dir() {
+            anyhow::bail!(
+                "{} 指向的目录不存在: {:?}",
+                CLAUDE_CONFIG_DIR_ENV,
+                config_dir
+            );
+        }
+        return Ok(config_dir);
+    }
+
+    let home = var("HOME").ok_or_else(|| anyhow::anyhow!("无法获取 HOME 环境变量"))?;
+    Ok(PathBuf::from(home).join(".claude"))
+}
+
+/// 按 Claude Code 规则编码项目路径为目录名（非字母数字字符替换为 '-'）
+fn encode_project_path(project_path: &str) -> String {
+    project_path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
 /// Claude Code 数据读取器
 ///
 /// 封装 claude-session-db::SessionReader，提供 vlaude-core 专用的接口。
 pub struct ClaudeReader {
     inner: DbSessionReader,
+    /// projects 目录路径
+    projects_path: PathBuf,
+    /// 临时目录（从归档创建时持有，drop 时自动清理）
+    temp_dir: Option<TempDir>,
+    /// 取消标志（由 `cancel` 设置，长操作定期检查）
+    cancel_flag: Arc<AtomicBool>,
+    /// 会话所属项目缓存（session_id → project_path）
+    path_cache: HashMap<String, String>,
+    /// 项目 git 分支缓存
+    branch_cache: GitBranchCache,
+    /// 统计计数器
+    stats: Arc<ReaderCounters>,
+    /// 会话星标缓存
+    star_cache: StarCache,
+    /// 会话文件大小缓存（session_id → (file_mtime, 大小)），mtime 变化时失效
+    size_cache: HashMap<String, (u64, u64)>,
+    /// 项目统计缓存（project_path → (统计, 计算时间)）
+    project_stats_cache: HashMap<String, (ProjectStats, std::time::Instant)>,
 }
 
 impl ClaudeReader {
     /// 创建读取器
     pub fn new(projects_path: PathBuf) -> Self {
         Self {
-            inner: DbSessionReader::new(projects_path),
+            inner: DbSessionReader::new(projects_path.clone()),
+            projects_path,
+            temp_dir: None,
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            path_cache: HashMap::new(),
+            branch_cache: GitBranchCache::default(),
+            stats: Arc::new(ReaderCounters::default()),
+            star_cache: StarCache::default(),
+            size_cache: HashMap::new(),
+            project_stats_cache: HashMap::new(),
+        }
+    }
+
+    /// 读取器统计快照
+    pub fn stats(&self) -> ReaderStats {
+        self.stats.snapshot(self.path_cache.len())
+    }
+
+    /// 清零统计计数
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// 取消正在进行的长操作
+    ///
+    /// 可以在其他线程调用（例如 UI 销毁时），标志保持到 `reset_cancel` 为止。
+    pub fn cancel(&self) {
+        self.cancel_flag.store(true, Ordering::SeqCst);
+    }
+
+    /// 清除取消标志
+    pub fn reset_cancel(&self) {
+        self.cancel_flag.store(false, Ordering::SeqCst);
+    }
+
+    /// 获取取消标志（供 FFI 层在不持有读取器锁的情况下取消）
+    pub fn cancellation_token(&self) -> Arc<AtomicBool> {
+        self.cancel_flag.clone()
+    }
+
+    /// 是否已取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_flag.load(Ordering::SeqCst)
+    }
+
+    fn check_cancelled(&self) -> Result<(), ParseSessionError> {
+        if self.is_cancelled() {
+            Err(ParseSessionError::new(ParseErrorCode::Cancelled, "操作已取消"))
+        } else {
+            Ok(())
         }
     }
 
+    /// 从 ZIP 归档创建读取器
+    ///
+    /// 归档解压到临时目录，读取器 drop 时自动清理。
+    /// 支持以下目录结构：`projects/...`、`.claude/projects/...` 或直接是项目目录。
+    pub fn from_archive(zip_path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(zip_path)
+            .map_err(|e| anyhow::anyhow!("无法打开归档 {:?}: {}", zip_path, e))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| anyhow::anyhow!("无效的 ZIP 归档 {:?}: {}", zip_path, e))?;
+
+        let temp_dir = TempDir::new()?;
+        archive
+            .extract(temp_dir.path())
+            .map_err(|e| anyhow::anyhow!("解压归档失败: {}", e))?;
+
+        let root = temp_dir.path();
+        let projects_path = [root.join("projects"), root.join(".claude/projects")]
+            .into_iter()
+            .find(|p| p.is_dir())
+            .unwrap_or_else(|| root.to_path_buf());
+
+        let mut reader = Self::new(projects_path);
+        reader.temp_dir = Some(temp_dir);
+        Ok(reader)
+    }
+
+    /// 从 JSON 描述创建读取器（用于测试）
+    ///
+    /// 按描述在临时目录中生成会话文件，读取器 drop 时自动清理。格式：
+    ///
+    /// ```json
+    /// {"projects": [{"path": "/tmp/demo", "sessions": [
+    ///     {"id": "session-1", "mtime": 1700000000000, "messages": [{"type": "user", "uuid": "u1"}]}
+    /// ]}]}
+    /// ```
+    ///
+    /// `mtime`（毫秒）可省略；消息原样写成 JSONL 行，缺少 `sessionId` / `cwd` 时自动补全。
+    pub fn from_json(projects_json: &str) -> anyhow::Result<Self> {
+        let mock: MockProjects = serde_json::from_str(projects_json)
+            .map_err(|e| anyhow::anyhow!("无效的项目描述 JSON: {}", e))?;
+
+        let temp_dir = TempDir::new()?;
+        let projects_path = temp_dir.path().join("projects");
+        for project in mock.projects {
+            let project_dir = projects_path.join(encode_project_path(&project.path));
+            std::fs::create_dir_all(&project_dir)?;
+            for session in project.sessions {
+                validate_session_id(&session.id)?;
+                let path = project_dir.join(format!("{}.jsonl", session.id));
+                let mut file = File::create(&path)?;
+                for mut message in session.messages {
+                    if let Some(object) = message.as_object_mut() {
+                        object.entry("sessionId").or_insert_with(|| session.id.clone().into());
+                        object.entry("cwd").or_insert_with(|| project.path.clone().into());
+                    }
+                    writeln!(file, "{}", message)?;
+                }
+                if let Some(mtime) = session.mtime {
+                    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_millis(mtime);
+                    file.set_modified(mtime)?;
+                }
+            }
+        }
+
+        let mut reader = Self::new(projects_path);
+        reader.temp_dir = Some(temp_dir);
+        Ok(reader)
+    }
+
+    /// projects 目录路径
+    pub fn projects_path(&self) -> &Path {
+        &self.projects_path
+    }
+
     /// 使用默认路径创建读取器
+    ///
+    /// 与 Claude Code 一致，优先使用 `CLAUDE_CONFIG_DIR`，未设置时为 `~/.claude`。
     pub fn default() -> anyhow::Result<Self> {
-        let home = std::env::var("HOME").map_err(|_| anyhow::anyhow!("无法获取 HOME 环境变量"))?;
-        let projects_path = PathBuf::from(home).join(".claude/projects");
-        Ok(Self::new(projects_path))
+        Ok(Self::new(default_projects_path()?))
     }
 
     /// 从路径提取项目名
@@ -41,12 +497,27 @@ impl ClaudeReader {
 
     /// 列出所有项目
     ///
-    /// 会话数量不包含 agent session。
-    pub fn list_projects(&mut self, limit: Option<usize>) -> anyhow::Result<Vec<ProjectInfo>> {
-        let projects = self.inner.list_projects(limit);
+    /// 会话数量不包含 agent session，git 分支缓存 30 秒。
+    /// 会话数少于 `min_sessions` 的项目（例如已删除项目留下的空目录）不返回，传 0 返回全部。
+    pub fn list_projects(
+        &mut self,
+        limit: Option<usize>,
+        min_sessions: usize,
+    ) -> anyhow::Result<Vec<ProjectInfo>> {
+        // 需要过滤时先取全部再截断，避免空项目占用 limit 名额
+        let started = std::time::Instant::now();
+        let projects = if min_sessions == 0 {
+            self.inner.list_projects(limit)
+        } else {
+            self.inner.list_projects(None)
+        };
+        self.stats.record_scan(projects.len(), started.elapsed());
         Ok(projects
             .into_iter()
+            .filter(|p| p.session_count >= min_sessions)
+            .take(limit.unwrap_or(usize::MAX))
             .map(|p| ProjectInfo {
+                git_branch: self.branch_cache.get(&p.path),
                 encoded_name: p.encoded_name,
                 path: p.path,
                 name: p.name,
@@ -56,17 +527,174 @@ impl ClaudeReader {
             .collect())
     }
 
+    /// 按项目路径查找单个项目
+    ///
+    /// 先通过已知的编码目录名定位，找不到时按编码规则直接检查目录，避免扫描全部项目。
+    pub fn get_project_info(&mut self, project_path: &str) -> anyhow::Result<Option<ProjectInfo>> {
+        let encoded_name = self
+            .get_encoded_dir_name(project_path)
+            .unwrap_or_else(|| encode_project_path(project_path));
+        if !self.projects_path.join(&encoded_name).is_dir() {
+            return Ok(None);
+        }
+
+        let sessions = self.list_sessions(Some(project_path), false, None)?;
+        let last_active = sessions.iter().map(session_mtime).max();
+
+        Ok(Some(ProjectInfo {
+            encoded_name,
+            path: project_path.to_string(),
+            name: Self::extract_project_name(project_path),
+            session_count: sessions.len(),
+            last_active,
+            git_branch: self.branch_cache.get(project_path),
+        }))
+    }
+
     /// 列出项目下的所有会话
     ///
     /// # Arguments
     /// * `project_path` - 可选的项目路径过滤
     /// * `include_agents` - 是否包含 agent session (agent-xxx)
+    /// * `since_mtime` - 只返回文件修改时间（毫秒）晚于该时间的会话，用于增量更新
     pub fn list_sessions(
         &mut self,
         project_path: Option<&str>,
         include_agents: bool,
+        since_mtime: Option<u64>,
+    ) -> anyhow::Result<Vec<SessionMeta>> {
+        let mut sessions = self.inner.list_sessions(project_path, include_agents);
+        self.fill_file_sizes(&mut sessions);
+        Ok(match since_mtime {
+            Some(since) => sessions
+                .into_iter()
+                .filter(|s| session_mtime(s) > since)
+                .collect(),
+            None => sessions,
+        })
+    }
+
+    /// 列出会话并附带星标状态
+    ///
+    /// 参数同 `list_sessions`，星标读取自各项目目录下的 `.vlaude-meta.json`（按文件修改时间缓存）。
+    pub fn list_sessions_with_stars(
+        &mut self,
+        project_path: Option<&str>,
+        include_agents: bool,
+        since_mtime: Option<u64>,
+    ) -> anyhow::Result<Vec<SessionEntry>> {
+        let sessions = self.list_sessions(project_path, include_agents, since_mtime)?;
+        Ok(sessions
+            .into_iter()
+            .map(|meta| {
+                let project_dir = self.project_dir(&meta.project_path);
+                let is_starred = self.star_cache.starred(&project_dir).contains(&meta.id);
+                SessionEntry { meta, is_starred }
+            })
+            .collect())
+    }
+
+    /// 设置或取消会话星标
+    pub fn star_session(
+        &mut self,
+        project_path: &str,
+        session_id: &str,
+        starred: bool,
+    ) -> anyhow::Result<()> {
+        validate_session_id(session_id)?;
+        let project_dir = self.project_dir(project_path);
+        self.star_cache.set_starred(&project_dir, session_id, starred)
+    }
+
+    /// 项目目录（已知编码时使用实际目录名）
+    fn project_dir(&mut self, project_path: &str) -> PathBuf {
+        let encoded = self
+            .get_encoded_dir_name(project_path)
+            .unwrap_or_else(|| encode_project_path(project_path));
+        self.projects_path.join(encoded)
+    }
+
+    /// 按文件大小过滤会话（闭区间，None 表示不限）
+    ///
+    /// 用于过滤只有几行的空壳会话（如 < 1 KB）。
+    pub fn list_sessions_filtered(
+        &mut self,
+        project_path: Option<&str>,
+        min_size_bytes: Option<u64>,
+        max_size_bytes: Option<u64>,
     ) -> anyhow::Result<Vec<SessionMeta>> {
-        Ok(self.inner.list_sessions(project_path, include_agents))
+        let sessions = self.list_sessions(project_path, false, None)?;
+        Ok(sessions
+            .into_iter()
+            .filter(|s| {
+                let size = s.file_size.unwrap_or(0);
+                min_size_bytes.is_none_or(|min| size >= min)
+                    && max_size_bytes.is_none_or(|max| size <= max)
+            })
+            .collect())
+    }
+
+    /// 补全缺失的 `file_size`
+    ///
+    /// 按 `file_mtime` 缓存，只有新会话或 mtime 变化的会话才读取文件元数据；
+    /// 项目目录每次调用只解析一次。
+    fn fill_file_sizes(&mut self, sessions: &mut [SessionMeta]) {
+        let mut project_dirs: HashMap<String, PathBuf> = HashMap::new();
+        for meta in sessions.iter_mut().filter(|s| s.file_size.is_none()) {
+            if let Some(mtime) = meta.file_mtime {
+                if let Some(&(cached_mtime, size)) = self.size_cache.get(&meta.id) {
+                    if cached_mtime == mtime {
+                        meta.file_size = Some(size);
+                        continue;
+                    }
+                }
+            }
+
+            let project_dir = match project_dirs.get(&meta.project_path) {
+                Some(dir) => dir.clone(),
+                None => {
+                    let dir = self.project_dir(&meta.project_path);
+                    project_dirs.insert(meta.project_path.clone(), dir.clone());
+                    dir
+                }
+            };
+            let path = project_dir.join(format!("{}.jsonl", meta.id));
+            meta.file_size = std::fs::metadata(path).ok().map(|m| m.len());
+
+            match (meta.file_mtime, meta.file_size) {
+                (Some(mtime), Some(size)) => {
+                    self.size_cache.insert(meta.id.clone(), (mtime, size));
+                }
+                _ => {
+                    self.size_cache.remove(&meta.id);
+                }
+            }
+        }
+    }
+
+    /// 分页列出会话（游标分页）
+    ///
+    /// 按修改时间降序排列，`cursor` 为上一页返回的 `next_cursor`。
+    pub fn list_sessions_paginated(
+        &mut self,
+        project_path: Option<&str>,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> anyhow::Result<SessionPage> {
+        let cursor = cursor.map(SessionCursor::decode).transpose()?;
+        let sessions = self.inner.list_sessions(project_path, false);
+
+        let (sessions, next_cursor) = page_after(
+            sessions,
+            |s| (session_mtime(s), s.id.clone()),
+            limit,
+            cursor.as_ref(),
+        );
+
+        Ok(SessionPage {
+            sessions,
+            next_cursor: next_cursor.map(|c| c.encode()),
+        })
     }
 
     /// 查找最新会话
@@ -78,6 +706,63 @@ impl ClaudeReader {
         Ok(self.inner.find_latest_session(project_path, within_seconds))
     }
 
+    /// 按 ID 查找单个会话
+    ///
+    /// 扫描 `*/{session_id}.jsonl` 找到所在项目目录（命中即停），再从 JSONL 的 `cwd` 得到项目路径，
+    /// 只列出该项目的会话，避免遍历全部会话。项目路径缓存在 `path_cache` 中。
+    pub fn get_session_by_id(&mut self, session_id: &str) -> anyhow::Result<Option<SessionMeta>> {
+        validate_session_id(session_id)?;
+
+        if let Some(project_path) = self.path_cache.get(session_id).cloned() {
+            if let Some(meta) = self.find_in_project(&project_path, session_id) {
+                self.stats.record_cache(true);
+                return Ok(Some(meta));
+            }
+            self.path_cache.remove(session_id);
+        }
+        self.stats.record_cache(false);
+
+        let Ok(entries) = std::fs::read_dir(&self.projects_path) else {
+            return Ok(None);
+        };
+        let file_name = format!("{}.jsonl", session_id);
+        let Some(session_file) = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(&file_name))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+
+        let meta = match read_session_cwd(&session_file) {
+            Some(project_path) => self.find_in_project(&project_path, session_id),
+            None => None,
+        };
+        // cwd 缺失或与目录不匹配时回退全量查找
+        let meta = match meta {
+            Some(meta) => Some(meta),
+            None => self
+                .inner
+                .list_sessions(None, true)
+                .into_iter()
+                .find(|s| s.id == session_id),
+        };
+
+        if let Some(meta) = &meta {
+            self.path_cache
+                .insert(session_id.to_string(), meta.project_path.clone());
+        }
+        Ok(meta)
+    }
+
+    /// 在指定项目中查找会话
+    fn find_in_project(&mut self, project_path: &str, session_id: &str) -> Option<SessionMeta> {
+        self.inner
+            .list_sessions(Some(project_path), true)
+            .into_iter()
+            .find(|s| s.id == session_id)
+    }
+
     /// 获取会话文件路径
     ///
     /// 通过 session_id 查询完整的文件路径。
@@ -90,6 +775,150 @@ impl ClaudeReader {
         self.inner.get_encoded_dir_name(project_path)
     }
 
+    /// 按 Claude Code 规则把项目路径编码为目录名
+    ///
+    /// 编码有损（`/a-b` 与 `/a/b` 都编码为 `-a-b`，连续的 `-` 也可能来自 `/.`、`--` 等），
+    /// 不能从目录名反推路径，需要反查时使用 `decode_path`。
+    pub fn encode_path(project_path: &str) -> String {
+        encode_project_path(project_path)
+    }
+
+    /// 从编码目录名还原项目路径
+    ///
+    /// 读取目录下会话 JSONL 中记录的 `cwd`，只接受重新编码后与目录名一致的路径；
+    /// 目录不存在或没有可用的 `cwd` 时返回 None。
+    pub fn decode_path(&self, encoded_dir: &str) -> Option<String> {
+        decode_project_dir(&self.projects_path.join(encoded_dir))
+    }
+
+    /// 获取会话 JSONL 文件路径（用于写操作）
+    ///
+    /// 返回 `{projects_path}/{encoded_dir}/{session_id}.jsonl`，项目目录不存在时自动创建。
+    /// 已有项目优先使用实际的目录名。
+    pub fn get_or_create_session_path(
+        &mut self,
+        project_path: &str,
+        session_id: &str,
+    ) -> anyhow::Result<PathBuf> {
+        validate_session_id(session_id)?;
+
+        let encoded = self
+            .get_encoded_dir_name(project_path)
+            .unwrap_or_else(|| encode_project_path(project_path));
+        let project_dir = self.projects_path.join(encoded);
+
+        std::fs::create_dir_all(&project_dir)
+            .map_err(|e| anyhow::anyhow!("无法创建项目目录 {:?}: {}", project_dir, e))?;
+
+        Ok(project_dir.join(format!("{}.jsonl", session_id)))
+    }
+
+    /// 从其他目录批量导入会话文件
+    ///
+    /// 把 `source_dir` 下的 `*.jsonl` 复制到 `target_project_path` 对应的项目目录（不存在时创建），
+    /// 目标中已存在的会话跳过，不覆盖；单个文件失败记入 `failed`，不影响其他文件。
+    pub fn bulk_import_jsonl(
+        &mut self,
+        source_dir: &Path,
+        target_project_path: &str,
+    ) -> anyhow::Result<ImportResult> {
+        let entries = std::fs::read_dir(source_dir)
+            .map_err(|e| anyhow::anyhow!("无法读取导入目录 {:?}: {}", source_dir, e))?;
+        let encoded = self
+            .get_encoded_dir_name(target_project_path)
+            .unwrap_or_else(|| encode_project_path(target_project_path));
+        let project_dir = self.projects_path.join(&encoded);
+        std::fs::create_dir_all(&project_dir)
+            .map_err(|e| anyhow::anyhow!("无法创建项目目录 {:?}: {}", project_dir, e))?;
+
+        let mut result = ImportResult::default();
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if !path.is_file() || path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                result.failed.push((file_name, "文件名不是有效的 UTF-8".to_string()));
+                continue;
+            };
+            if let Err(e) = validate_session_id(session_id) {
+                result.failed.push((file_name, e.to_string()));
+                continue;
+            }
+
+            let target = project_dir.join(&file_name);
+            if target.exists() {
+                result.skipped.push(session_id.to_string());
+                continue;
+            }
+            match std::fs::copy(&path, &target) {
+                Ok(_) => result.imported.push(session_id.to_string()),
+                Err(e) => {
+                    let _ = std::fs::remove_file(&target);
+                    result.failed.push((file_name, e.to_string()));
+                }
+            }
+        }
+
+        result.imported.sort();
+        result.skipped.sort();
+        result.failed.sort();
+        Ok(result)
+    }
+
+    /// 归档旧会话
+    ///
+    /// 把修改时间早于 `older_than_days` 天的会话文件 gzip 压缩到
+    /// `{archive_dir}/{encoded_dir}/{session_id}.jsonl.gz`，成功后删除原文件。
+    /// 单个会话失败记入 `errors`，不影响其他会话。`dry_run` 时只统计不移动。
+    pub fn archive_old_sessions(
+        &mut self,
+        project_path: &str,
+        older_than_days: u64,
+        archive_dir: &Path,
+        dry_run: bool,
+    ) -> anyhow::Result<ArchiveResult> {
+        let encoded = self
+            .get_encoded_dir_name(project_path)
+            .unwrap_or_else(|| encode_project_path(project_path));
+        let project_dir = self.projects_path.join(&encoded);
+        let entries = std::fs::read_dir(&project_dir)
+            .map_err(|e| anyhow::anyhow!("无法读取项目目录 {:?}: {}", project_dir, e))?;
+
+        let cutoff = std::time::SystemTime::now()
+            - std::time::Duration::from_secs(older_than_days.saturating_mul(24 * 60 * 60));
+        let target_dir = archive_dir.join(&encoded);
+        let mut result = ArchiveResult::default();
+
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Ok(meta) = path.metadata() else {
+                continue;
+            };
+            if !meta.modified().is_ok_and(|mtime| mtime < cutoff) {
+                continue;
+            }
+
+            if !dry_run {
+                let target = target_dir.join(format!("{}.jsonl.gz", session_id));
+                if let Err(e) = archive_session_file(&path, &target) {
+                    result.errors.push((session_id.to_string(), e.to_string()));
+                    continue;
+                }
+            }
+            result.moved.push(session_id.to_string());
+            result.total_bytes_freed += meta.len();
+        }
+
+        result.moved.sort();
+        Ok(result)
+    }
+
     /// 读取会话消息（支持分页）
     pub fn read_messages(
         &self,
@@ -136,6 +965,217 @@ impl ClaudeReader {
             })
     }
 
+    /// 读取原始 JSONL 消息（大文件使用内存映射）
+    ///
+    /// 分页语义与 `read_messages_raw` 相同。小于 1 MB 的文件直接走 `read_messages_raw`；
+    /// 大文件按换行扫描映射内容，按顶层 `type` 计数（不完整的行不计入），只保留本页内的消息。
+    pub fn read_messages_mmap(
+        &self,
+        session_path: &str,
+        limit: usize,
+        offset: usize,
+        order: Order,
+    ) -> anyhow::Result<RawMessagesResult> {
+        let file = File::open(session_path)
+            .map_err(|e| anyhow::anyhow!("无法打开会话文件 {}: {}", session_path, e))?;
+        let len = file.metadata()?.len();
+        self.stats.record_open();
+        self.stats.record_bytes(len);
+        if len < MMAP_MIN_BYTES {
+            return self.read_messages_raw(session_path, limit, offset, order);
+        }
+
+        // SAFETY: 只读映射；Claude Code 只追加写入会话文件，映射范围内的内容不会被截断
+        let mmap = unsafe { MmapOptions::new().map(&file) }
+            .map_err(|e| anyhow::anyhow!("无法映射会话文件 {}: {}", session_path, e))?;
+        Ok(read_raw_messages_from_bytes(&mmap, limit, offset, order))
+    }
+
+    /// 按选项读取原始 JSONL 消息（支持子串 / 正则搜索）
+    ///
+    /// 搜索只匹配消息文本，原始行预筛不通过的行不做 JSON 解析，不匹配的消息不保留在内存中。
+    pub fn read_messages_with_options(
+        &self,
+        session_path: &str,
+        options: &ReadMessagesOptions,
+    ) -> anyhow::Result<RawMessagesResult> {
+        let iter = self.iter_messages(session_path, options.order)?;
+        self.stats.record_open();
+
+        let matchers = options.matchers();
+        let mut messages = Vec::new();
+        let mut total = 0;
+        for (index, line) in iter.enumerate() {
+            if index % CANCEL_CHECK_INTERVAL == 0 {
+                self.check_cancelled()?;
+            }
+            self.stats.record_bytes(line.len() as u64 + 1);
+            let matched = if matchers.is_empty() {
+                None
+            } else {
+                let Some(value) = search::match_line(&line, &matchers) else {
+                    continue;
+                };
+                Some(value)
+            };
+            if total >= options.offset && messages.len() < options.limit {
+                let value = match matched {
+                    Some(value) => value,
+                    None => serde_json::from_str(&line)?,
+                };
+                messages.push(value);
+            }
+            total += 1;
+        }
+
+        Ok(RawMessagesResult {
+            messages,
+            total,
+            has_more: options.offset.saturating_add(options.limit) < total,
+        })
+    }
+
+    /// 逐条迭代会话消息（原始 JSON 行）
+    ///
+    /// 适合逐条处理的调用方（如搜索），不分配完整的消息数组。
+    pub fn iter_messages(&self, session_path: &str, order: Order) -> anyhow::Result<MessageIter> {
+        MessageIter::open(Path::new(session_path), order)
+    }
+
+    /// 读取指定 UUID 之后的消息（不做格式转换）
+    ///
+    /// `from_uuid` 为 None 时从头读取；找不到 `from_uuid` 时报错，调用方应回退全量同步。
+    /// 与 offset 分页不同，流式写入时前面插入消息不会导致结果错位。
+    pub fn read_messages_from_uuid(
+        &self,
+        session_path: &str,
+        from_uuid: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<UuidMessagesResult> {
+        let file = File::open(session_path)
+            .map_err(|e| anyhow::anyhow!("无法打开会话文件 {}: {}", session_path, e))?;
+        self.stats.record_open();
+
+        let mut found = from_uuid.is_none();
+        let mut prev_uuid = from_uuid.map(str::to_string);
+        let mut messages = Vec::new();
+        let mut has_more = false;
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            if index % CANCEL_CHECK_INTERVAL == 0 {
+                self.check_cancelled()?;
+            }
+            let line = line?;
+            self.stats.record_bytes(line.len() as u64 + 1);
+            // 跳过空行和损坏行（流式写入时最后一行可能不完整）
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+            let Some(uuid) = value.get("uuid").and_then(|v| v.as_str()) else {
+                continue;
+            };
+
+            if !found {
+                if Some(uuid) == from_uuid {
+                    found = true;
+                }
+                continue;
+            }
+
+            if messages.len() == limit {
+                has_more = true;
+                break;
+            }
+            messages.push(value);
+        }
+
+        if !found {
+            anyhow::bail!("消息不存在: {}", from_uuid.unwrap_or_default());
+        }
+
+        let next_uuid = if has_more {
+            messages
+                .last()
+                .and_then(|m| m.get("uuid"))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        } else {
+            None
+        };
+        if from_uuid.is_none() {
+            prev_uuid = None;
+        }
+
+        Ok(UuidMessagesResult {
+            messages,
+            prev_uuid,
+            next_uuid,
+            has_more,
+        })
+    }
+
+    /// 读取会话 JSONL 的最后一条消息（原始格式）
+    ///
+    /// 先读文件尾部；尾部没有完整消息（单行过长）时回退整文件扫描。
+    pub fn read_last_message(&self, session_path: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        fn last_message<'a>(lines: impl DoubleEndedIterator<Item = &'a str>) -> Option<serde_json::Value> {
+            lines
+                .rev()
+                .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                .find(|value| value.get("uuid").is_some())
+        }
+
+        let mut file = File::open(session_path)
+            .map_err(|e| anyhow::anyhow!("无法打开会话文件 {}: {}", session_path, e))?;
+        self.stats.record_open();
+        let len = file.metadata()?.len();
+        let start = len.saturating_sub(TAIL_READ_BYTES);
+
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+        self.stats.record_bytes(tail.len() as u64);
+        let tail = String::from_utf8_lossy(&tail);
+        if let Some(message) = last_message(tail.lines()) {
+            return Ok(Some(message));
+        }
+        if start == 0 {
+            return Ok(None);
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        self.stats.record_bytes(start);
+        let mut last = None;
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            if index % CANCEL_CHECK_INTERVAL == 0 {
+                self.check_cancelled()?;
+            }
+            let line = line?;
+            if let Some(message) = last_message(std::iter::once(line.as_str())) {
+                last = Some(message);
+            }
+        }
+        Ok(last)
+    }
+
+    /// 会话是否正在生成（最后一条消息没有 stop_reason）
+    ///
+    /// Claude Code 流式写入时 assistant 消息的 `stop_reason` 为 null，完成后为 `end_turn` 等。
+    pub fn is_session_loading(&self, session_path: &str) -> anyhow::Result<bool> {
+        let Some(message) = self.read_last_message(session_path)? else {
+            return Ok(false);
+        };
+
+        let stop_reason = message
+            .get("message")
+            .and_then(|m| m.get("stop_reason"))
+            .or_else(|| message.get("stop_reason"));
+        Ok(match stop_reason {
+            Some(value) => value.is_null(),
+            None => true,
+        })
+    }
+
     /// 解析完整会话
     pub fn parse_session(&self, meta: &SessionMeta) -> anyhow::Result<Option<ParseResult>> {
         Ok(self.inner.parse_session(meta))
@@ -146,17 +1186,1014 @@ impl ClaudeReader {
         Ok(self.inner.parse_jsonl_for_index(jsonl_path))
     }
 
+    /// 并行解析多个 JSONL 文件（用于批量索引）
+    ///
+    /// 结果顺序与 `paths` 一致；取消后尚未开始的文件返回取消错误。
+    pub fn parse_sessions_batch(
+        &self,
+        paths: Vec<String>,
+    ) -> Vec<anyhow::Result<Option<IndexableSession>>> {
+        paths
+            .par_iter()
+            .map(|path| {
+                self.check_cancelled()?;
+                self.parse_session_from_path(path)
+            })
+            .collect()
+    }
+
+    /// 解析 JSONL 文件（用于索引），失败时返回结构化错误
+    ///
+    /// 与 `parse_session_from_path` 不同，空会话、I/O 错误、解析错误可以被区分。
+    pub fn parse_session_checked(
+        &self,
+        jsonl_path: &str,
+    ) -> Result<IndexableSession, ParseSessionError> {
+        self.check_cancelled()?;
+
+        // 先确认文件可读，区分 I/O 错误和权限错误
+        let file = File::open(jsonl_path)?;
+
+        let parsed = self.inner.parse_jsonl_for_index(jsonl_path);
+        // 解析期间被取消：丢弃结果，避免回调到已销毁的调用方
+        self.check_cancelled()?;
+        if let Some(session) = parsed {
+            return Ok(session);
+        }
+
+        // 没有可索引的消息：区分空文件和内容损坏
+        let mut has_content = false;
+        let mut has_valid_json = false;
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            if index % CANCEL_CHECK_INTERVAL == 0 {
+                self.check_cancelled()?;
+            }
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            has_content = true;
+            if serde_json::from_str::<serde::de::IgnoredAny>(&line).is_ok() {
+                has_valid_json = true;
+                break;
+            }
+        }
+
+        if has_content && !has_valid_json {
+            Err(ParseSessionError::new(
+                ParseErrorCode::ParseError,
+                format!("会话文件不包含有效的 JSON 行: {}", jsonl_path),
+            ))
+        } else {
+            Err(ParseSessionError::new(
+                ParseErrorCode::Empty,
+                format!("会话文件没有有效消息: {}", jsonl_path),
+            ))
+        }
+    }
+
+    /// 校验会话文件（预检，不做完整解析）
+    ///
+    /// 逐行流式读取，只检查 JSON 是否合法并提取 `type` 字段，不构建完整的 JSON 树。
+    pub fn validate_session_file(&self, session_path: &str) -> anyhow::Result<ValidationResult> {
+        /// 只提取 type 字段，其余字段由 serde 跳过
+        #[derive(serde::Deserialize)]
+        struct LineHeader {
+            #[serde(rename = "type", default)]
+            kind: Option<String>,
+        }
+
+        let file = File::open(session_path)
+            .map_err(|e| anyhow::anyhow!("无法打开会话文件 {}: {}", session_path, e))?;
+        let estimated_bytes = file.metadata()?.len();
+        self.stats.record_open();
+        self.stats.record_bytes(estimated_bytes);
+
+        let mut result = ValidationResult {
+            line_count: 0,
+            valid_json_lines: 0,
+            malformed_lines: Vec::new(),
+            has_session_summary: false,
+            estimated_bytes,
+        };
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            if index % CANCEL_CHECK_INTERVAL == 0 {
+                self.check_cancelled()?;
+            }
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            result.line_count += 1;
+
+            let parsed = serde_json::from_str::<LineHeader>(&line)
+                .map(|header| header.kind)
+                // 非对象的合法 JSON 也算有效行
+                .or_else(|_| serde_json::from_str::<serde::de::IgnoredAny>(&line).map(|_| None));
+
+            match parsed {
+                Ok(kind) => {
+                    result.valid_json_lines += 1;
+                    if kind.as_deref() == Some("summary") {
+                        result.has_session_summary = true;
+                    }
+                }
+                Err(e) => {
+                    if result.malformed_lines.len() < ValidationResult::MAX_MALFORMED_LINES {
+                        result.malformed_lines.push((index + 1, e.to_string()));
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 在消息文本中搜索子串（区分大小写）
+    ///
+    /// `project_path` 为 None 时搜索所有项目，按会话列表顺序最多返回 `limit` 条结果。
+    pub fn search_messages(
+        &mut self,
+        query: &str,
+        project_path: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<SearchHit>> {
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.search_messages_with(&SubstringMatcher::new(query), project_path, limit)
+    }
+
+    /// 使用自定义匹配器搜索消息（如 `regex::Regex`）
+    pub fn search_messages_with(
+        &mut self,
+        matcher: &dyn MessageMatcher,
+        project_path: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<SearchHit>> {
+        let sessions = self.list_sessions(project_path, false, None)?;
+
+        let mut hits = Vec::new();
+        for meta in sessions {
+            if hits.len() >= limit {
+                break;
+            }
+            self.check_cancelled()?;
+
+            let path = self.project_dir(&meta.project_path).join(format!("{}.jsonl", meta.id));
+            // 会话文件可能在列出后被删除，跳过即可
+            let Ok(file_hits) = search::search_file(&path, matcher, limit - hits.len()) else {
+                continue;
+            };
+            self.stats.record_open();
+            hits.extend(file_hits.into_iter().map(|hit| SearchHit {
+                session_id: meta.id.clone(),
+                project_path: meta.project_path.clone(),
+                message_uuid: hit.message_uuid,
+                snippet: hit.snippet,
+                byte_offset: hit.byte_offset,
+            }));
+        }
+        Ok(hits)
+    }
+
+    /// 项目聚合统计（缓存 60 秒）
+    ///
+    /// 逐个会话累加 `calculate_metrics`，并扫描助手消息中的模型名和第一条消息时间。
+    pub fn get_project_stats(&mut self, project_path: &str) -> anyhow::Result<ProjectStats> {
+        if let Some((stats, at)) = self.project_stats_cache.get(project_path) {
+            if at.elapsed() < PROJECT_STATS_TTL {
+                return Ok(stats.clone());
+            }
+        }
+
+        let sessions = self.list_sessions(Some(project_path), false, None)?;
+        let mut stats = ProjectStats {
+            total_sessions: sessions.len(),
+            ..Default::default()
+        };
+        let mut models = std::collections::BTreeSet::new();
+
+        for meta in &sessions {
+            self.check_cancelled()?;
+            if let Some(mtime) = meta.file_mtime {
+                stats.last_activity = stats.last_activity.max(Some(mtime));
+            }
+
+            let Some(scan) = self.scan_session(meta) else {
+                continue;
+            };
+            if let Some(metrics) = scan.metrics() {
+                stats.total_messages += metrics.message_count;
+                stats.total_estimated_tokens += metrics.estimated_tokens;
+                stats.total_duration_seconds += metrics.duration_seconds.unwrap_or(0);
+            }
+            if let Some(created) = scan.created {
+                stats.first_session_created = Some(
+                    stats.first_session_created.map_or(created, |first| first.min(created)),
+                );
+            }
+            models.extend(scan.models);
+        }
+
+        stats.unique_models_used = models.into_iter().collect();
+        if stats.total_sessions > 0 {
+            stats.average_messages_per_session =
+                stats.total_messages as f64 / stats.total_sessions as f64;
+        }
+
+        self.project_stats_cache.insert(
+            project_path.to_string(),
+            (stats.clone(), std::time::Instant::now()),
+        );
+        Ok(stats)
+    }
+
     /// 计算会话 Metrics
-    pub fn calculate_metrics(&self, meta: &SessionMeta) -> anyhow::Result<Option<SessionMetrics>> {
-        Ok(self
-            .inner
-            .calculate_metrics(meta)
-            .map(|m| SessionMetrics {
-                message_count: m.message_count,
-                user_message_count: m.user_message_count,
-                assistant_message_count: m.assistant_message_count,
-                estimated_tokens: m.estimated_tokens,
-                duration_seconds: m.duration_seconds,
-            }))
+    ///
+    /// 助手消息带 `usage` 时使用实际 token 数（输入包含缓存读写），否则按字符数估算。
+    pub fn calculate_metrics(&mut self, meta: &SessionMeta) -> anyhow::Result<Option<SessionMetrics>> {
+        Ok(self.scan_session(meta).and_then(|scan| scan.metrics()))
+    }
+
+    /// 遍历一次会话文件（文件不存在或无法读取时返回 None）
+    fn scan_session(&mut self, meta: &SessionMeta) -> Option<SessionScan> {
+        let path = self.project_dir(&meta.project_path).join(format!("{}.jsonl", meta.id));
+        let iter = self.iter_messages(path.to_str()?, Order::Asc).ok()?;
+        self.stats.record_open();
+        Some(SessionScan::scan(iter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn test_reader(dir: &TempDir) -> ClaudeReader {
+        ClaudeReader::new(dir.path().join("projects"))
+    }
+
+    /// 创建会话文件并设置修改时间（毫秒）
+    fn write_session(dir: &Path, session_id: &str, mtime_ms: u64) {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(format!("{}.jsonl", session_id));
+        let mut file = File::create(&path).unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"user","uuid":"u1","sessionId":"{}","cwd":"/tmp/demo"}}"#,
+            session_id
+        )
+        .unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_millis(mtime_ms))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_cancel_long_operation() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        let path = dir.path().join("session.jsonl");
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, r#"{{"type":"user"}}"#).unwrap();
+        drop(file);
+        let path = path.to_str().unwrap();
+
+        // 其他线程通过 token 取消
+        let token = reader.cancellation_token();
+        token.store(true, Ordering::SeqCst);
+        assert!(reader.is_cancelled());
+
+        let err = reader.parse_session_checked(path).unwrap_err();
+        assert_eq!(err.code, ParseErrorCode::Cancelled);
+
+        let err = reader.validate_session_file(path).unwrap_err();
+        let err = err.downcast_ref::<ParseSessionError>().unwrap();
+        assert_eq!(err.code, ParseErrorCode::Cancelled);
+
+        reader.reset_cancel();
+        assert!(reader.validate_session_file(path).is_ok());
+    }
+
+    #[test]
+    fn test_validate_session_file() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        let path = dir.path().join("session.jsonl");
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, r#"{{"type":"summary","summary":"demo"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"user","message":{{"content":"hi"}}}}"#).unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "{{broken").unwrap();
+        writeln!(file, r#"{{"type":"assistant"}}"#).unwrap();
+        drop(file);
+
+        let result = reader
+            .validate_session_file(path.to_str().unwrap())
+            .unwrap();
+        assert_eq!(result.line_count, 4);
+        assert_eq!(result.valid_json_lines, 3);
+        assert_eq!(result.malformed_lines.len(), 1);
+        assert_eq!(result.malformed_lines[0].0, 4);
+        assert!(result.has_session_summary);
+        assert_eq!(result.estimated_bytes, std::fs::metadata(&path).unwrap().len());
+        assert!(!result.is_valid());
+
+        assert!(reader
+            .validate_session_file(dir.path().join("missing.jsonl").to_str().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_encode_project_path() {
+        assert_eq!(encode_project_path("/Users/me/my_app"), "-Users-me-my-app");
+        assert_eq!(encode_project_path("/tmp/a.b c"), "-tmp-a-b-c");
+    }
+
+    #[test]
+    fn test_encode_decode_path_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        // 连续的 '-' 以及会编码成 '-' 的字符组合
+        let paths = [
+            "/tmp/demo",
+            "/Users/me/my-project",
+            "/Users/me/my--project",
+            "/Users/me/my---project-",
+            "/Users/me/.config/app",
+            "/Users/me/-leading",
+            "/Users/me/a_b.c d",
+            "/Users/me/项目-1",
+        ];
+        for (i, path) in paths.iter().enumerate() {
+            let encoded = ClaudeReader::encode_path(path);
+            assert_eq!(encoded.len(), path.chars().count());
+            assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+
+            let project_dir = dir.path().join("projects").join(&encoded);
+            std::fs::create_dir_all(&project_dir).unwrap();
+            let mut file = File::create(project_dir.join(format!("s{}.jsonl", i))).unwrap();
+            writeln!(file, "{}", serde_json::json!({"type": "user", "cwd": path})).unwrap();
+
+            assert_eq!(reader.decode_path(&encoded).as_deref(), Some(*path));
+        }
+
+        // 同一编码目录下 cwd 不匹配时不采用
+        let project_dir = dir.path().join("projects/-tmp-other");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let mut file = File::create(project_dir.join("s.jsonl")).unwrap();
+        writeln!(file, r#"{{"type":"user","cwd":"/tmp/demo"}}"#).unwrap();
+        assert_eq!(reader.decode_path("-tmp-other"), None);
+        assert_eq!(reader.decode_path("-tmp-missing"), None);
+    }
+
+    #[test]
+    fn test_archive_old_sessions() {
+        let dir = TempDir::new().unwrap();
+        let mut reader = test_reader(&dir);
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let day_ms = 24 * 60 * 60 * 1000;
+        write_session(&project_dir, "old", now - 100 * day_ms);
+        write_session(&project_dir, "recent", now - day_ms);
+        let archive_dir = dir.path().join("archive");
+
+        let dry = reader
+            .archive_old_sessions("/tmp/demo", 90, &archive_dir, true)
+            .unwrap();
+        assert_eq!(dry.moved, ["old"]);
+        assert!(project_dir.join("old.jsonl").exists());
+        assert!(!archive_dir.exists());
+
+        let original = std::fs::read(project_dir.join("old.jsonl")).unwrap();
+        let result = reader
+            .archive_old_sessions("/tmp/demo", 90, &archive_dir, false)
+            .unwrap();
+        assert_eq!(result.moved, ["old"]);
+        assert_eq!(result.total_bytes_freed, original.len() as u64);
+        assert!(result.errors.is_empty());
+        assert!(!project_dir.join("old.jsonl").exists());
+        assert!(project_dir.join("recent.jsonl").exists());
+
+        let archived = File::open(archive_dir.join("-tmp-demo/old.jsonl.gz")).unwrap();
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(archived)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, original);
+    }
+
+    #[test]
+    fn test_bulk_import_jsonl() {
+        let dir = TempDir::new().unwrap();
+        let mut reader = test_reader(&dir);
+        let source_dir = dir.path().join("export");
+        write_session(&source_dir, "new-session", 1_000_000);
+        write_session(&source_dir, "existing", 1_000_000);
+        std::fs::write(source_dir.join("notes.txt"), "ignored").unwrap();
+
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(project_dir.join("existing.jsonl"), "keep").unwrap();
+
+        let result = reader.bulk_import_jsonl(&source_dir, "/tmp/demo").unwrap();
+        assert_eq!(result.imported, ["new-session"]);
+        assert_eq!(result.skipped, ["existing"]);
+        assert!(result.failed.is_empty());
+        assert!(project_dir.join("new-session.jsonl").is_file());
+        assert_eq!(std::fs::read_to_string(project_dir.join("existing.jsonl")).unwrap(), "keep");
+
+        assert!(reader.bulk_import_jsonl(&dir.path().join("missing"), "/tmp/demo").is_err());
+    }
+
+    #[test]
+    fn test_default_uses_claude_config_dir() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        let vars = |config_dir: &Path| {
+            let config_dir = config_dir.as_os_str().to_os_string();
+            move |name: &str| match name {
+                CLAUDE_CONFIG_DIR_ENV => Some(config_dir.clone()),
+                "HOME" => Some(OsString::from("/home/test")),
+                _ => None,
+            }
+        };
+
+        let path = config_dir_from_vars(vars(dir.path())).unwrap();
+        assert_eq!(path, dir.path());
+
+        let error = config_dir_from_vars(vars(&missing)).unwrap_err().to_string();
+        assert!(error.contains(CLAUDE_CONFIG_DIR_ENV));
+
+        let fallback = config_dir_from_vars(|name| match name {
+            "HOME" => Some(OsString::from("/home/test")),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(fallback, PathBuf::from("/home/test/.claude"));
+    }
+
+    #[test]
+    fn test_reader_stats() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"type\":\"user\",\"uuid\":\"u1\"}\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        reader.read_last_message(path).unwrap();
+        reader.validate_session_file(path).unwrap();
+        let stats = reader.stats();
+        assert_eq!(stats.total_files_opened, 2);
+        assert_eq!(stats.total_bytes_read, 2 * 28);
+
+        reader.reset_stats();
+        assert_eq!(reader.stats(), ReaderStats::default());
+    }
+
+    #[test]
+    fn test_get_or_create_session_path() {
+        let dir = TempDir::new().unwrap();
+        let mut reader = test_reader(&dir);
+
+        let path = reader
+            .get_or_create_session_path("/tmp/new_project", "abc-123")
+            .unwrap();
+        assert_eq!(
+            path,
+            dir.path().join("projects/-tmp-new-project/abc-123.jsonl")
+        );
+        assert!(path.parent().unwrap().is_dir());
+        // 只创建目录，不创建文件
+        assert!(!path.exists());
+
+        assert!(reader.get_or_create_session_path("/tmp/x", "").is_err());
+        assert!(reader.get_or_create_session_path("/tmp/x", "../evil").is_err());
+    }
+
+    #[test]
+    fn test_get_project_info() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        write_session(&project_dir, "session-a", 1_000_000);
+        write_session(&project_dir, "session-b", 2_000_000);
+
+        let mut reader = test_reader(&dir);
+
+        let info = reader.get_project_info("/tmp/demo").unwrap().unwrap();
+        assert_eq!(info.encoded_name, "-tmp-demo");
+        assert_eq!(info.path, "/tmp/demo");
+        assert_eq!(info.session_count, 2);
+        assert_eq!(info.last_active, Some(2_000_000));
+
+        assert!(reader.get_project_info("/tmp/missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_session_by_id() {
+        let dir = TempDir::new().unwrap();
+        write_session(&dir.path().join("projects/-tmp-demo"), "session-a", 1_000_000);
+        write_session(&dir.path().join("projects/-tmp-other"), "session-b", 1_000_000);
+
+        let mut reader = test_reader(&dir);
+
+        let meta = reader.get_session_by_id("session-a").unwrap().unwrap();
+        assert_eq!(meta.id, "session-a");
+        assert_eq!(meta.project_path, "/tmp/demo");
+        assert_eq!(reader.path_cache.get("session-a").map(String::as_str), Some("/tmp/demo"));
+
+        // 命中缓存
+        assert!(reader.get_session_by_id("session-a").unwrap().is_some());
+
+        assert!(reader.get_session_by_id("missing").unwrap().is_none());
+        assert!(reader.get_session_by_id("../escape").is_err());
+    }
+
+    #[test]
+    fn test_list_sessions_since_mtime() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        write_session(&project_dir, "old-session", 1_000_000);
+        write_session(&project_dir, "new-session", 3_000_000);
+
+        let mut reader = test_reader(&dir);
+
+        let all = reader.list_sessions(None, false, None).unwrap();
+        assert_eq!(all.len(), 2);
+
+        let recent = reader.list_sessions(None, false, Some(2_000_000)).unwrap();
+        let ids: Vec<_> = recent.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["new-session"]);
+
+        // 严格晚于：等于 since_mtime 的会话不返回
+        let none = reader.list_sessions(None, false, Some(3_000_000)).unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_read_messages_with_search() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        let path = dir.path().join("session.jsonl");
+        let mut file = File::create(&path).unwrap();
+        let texts = ["deploy the app", "run tests", "deploy again", "Deploy docs"];
+        for (i, text) in texts.iter().enumerate() {
+            writeln!(
+                file,
+                r#"{{"type":"user","uuid":"u{}","message":{{"content":"{}"}}}}"#,
+                i, text
+            )
+            .unwrap();
+        }
+        let path = path.to_str().unwrap();
+
+        let mut options = ReadMessagesOptions::new(1, 0, Order::Asc);
+        options.search_query = Some("deploy".to_string());
+        let result = reader.read_messages_with_options(path, &options).unwrap();
+        assert_eq!(result.total, 2);
+        assert!(result.has_more);
+        assert_eq!(result.messages[0]["uuid"], "u0");
+
+        let mut options = ReadMessagesOptions::new(10, 0, Order::Desc);
+        options.search_regex = Some(regex::Regex::new("(?i)deploy (again|docs)").unwrap());
+        let result = reader.read_messages_with_options(path, &options).unwrap();
+        let uuids: Vec<_> = result.messages.iter().map(|m| m["uuid"].as_str().unwrap()).collect();
+        assert_eq!(uuids, vec!["u3", "u2"]);
+        assert!(!result.has_more);
+
+        // 只匹配消息文本，不匹配 JSON 字段名
+        let mut options = ReadMessagesOptions::new(10, 0, Order::Asc);
+        options.search_query = Some("uuid".to_string());
+        let result = reader.read_messages_with_options(path, &options).unwrap();
+        assert_eq!(result.total, 0);
+    }
+
+    #[test]
+    fn test_search_messages() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let lines = [
+            r#"{"type":"summary","summary":"数据库迁移"}"#,
+            r#"{"type":"user","uuid":"u1","cwd":"/tmp/demo","message":{"content":"请帮我修复数据库迁移脚本 🚀"}}"#,
+            r#"{"type":"assistant","uuid":"a1","cwd":"/tmp/demo","message":{"content":[{"type":"text","text":"好的，数据库迁移已修复"}]}}"#,
+        ];
+        let content = lines.join("\n") + "\n";
+        std::fs::write(project_dir.join("session-a.jsonl"), &content).unwrap();
+        write_session(&project_dir, "session-b", 3_000_000);
+
+        let mut reader = test_reader(&dir);
+        let hits = reader.search_messages("数据库迁移", Some("/tmp/demo"), 10).unwrap();
+        // summary 行不参与搜索
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].session_id, "session-a");
+        assert_eq!(hits[0].project_path, "/tmp/demo");
+        assert_eq!(hits[0].message_uuid.as_deref(), Some("u1"));
+        assert_eq!(hits[0].snippet, "请帮我修复数据库迁移脚本 🚀");
+        assert_eq!(hits[0].byte_offset, content.find(lines[1]).unwrap() as u64);
+        assert_eq!(hits[1].message_uuid.as_deref(), Some("a1"));
+
+        assert_eq!(reader.search_messages("数据库迁移", None, 1).unwrap().len(), 1);
+        assert!(reader.search_messages("不存在", None, 10).unwrap().is_empty());
+
+        let re = regex::Regex::new("修复.+脚本").unwrap();
+        let hits = reader.search_messages_with(&re, None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_uuid.as_deref(), Some("u1"));
+    }
+
+    #[test]
+    fn test_get_project_stats() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let lines = [
+            r#"{"type":"user","uuid":"u1","cwd":"/tmp/demo","timestamp":"2025-01-01T00:00:00Z"}"#,
+            r#"{"type":"assistant","uuid":"a1","cwd":"/tmp/demo","message":{"model":"claude-opus"}}"#,
+            r#"{"type":"assistant","uuid":"a2","cwd":"/tmp/demo","message":{"model":"claude-haiku"}}"#,
+        ];
+        std::fs::write(project_dir.join("session-a.jsonl"), lines.join("\n") + "\n").unwrap();
+        write_session(&project_dir, "session-b", 3_000_000);
+
+        let mut reader = test_reader(&dir);
+        let stats = reader.get_project_stats("/tmp/demo").unwrap();
+        assert_eq!(stats.total_sessions, 2);
+        assert_eq!(stats.unique_models_used, ["claude-haiku", "claude-opus"]);
+        assert_eq!(stats.first_session_created, Some(1_735_689_600_000));
+        assert!(stats.last_activity.is_some());
+
+        // 缓存期内不重新计算
+        std::fs::remove_file(project_dir.join("session-b.jsonl")).unwrap();
+        assert_eq!(reader.get_project_stats("/tmp/demo").unwrap(), stats);
+    }
+
+    #[test]
+    fn test_session_scan_usage() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let scan = |lines: &[&str]| {
+            std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+            SessionScan::scan(MessageIter::open(&path, Order::Asc).unwrap())
+        };
+
+        // 同一条消息拆成多行时 usage 只计一次
+        let result = scan(&[
+            r#"{"type":"user","uuid":"u1","message":{"role":"user","content":"hi"}}"#,
+            r#"{"type":"assistant","uuid":"a1","message":{"id":"msg_1","content":[{"type":"thinking"}],"usage":{"input_tokens":10,"cache_read_input_tokens":500,"output_tokens":3}}}"#,
+            r#"{"type":"assistant","uuid":"a2","message":{"id":"msg_1","content":[{"type":"text","text":"hello"}],"usage":{"input_tokens":10,"cache_read_input_tokens":500,"output_tokens":3}}}"#,
+            r#"{"type":"assistant","uuid":"a3","message":{"id":"msg_2","content":"bye","usage":{"input_tokens":7,"output_tokens":20}}}"#,
+        ]);
+        // 缓存读写 token 计入输入
+        assert_eq!(result.usage, Some((517, 23)));
+        assert_eq!(result.metrics().unwrap().estimated_tokens, 540);
+
+        // 没有 usage 时按字符数估算
+        let result = scan(&[
+            r#"{"type":"user","uuid":"u1","timestamp":"2025-01-01T00:00:00Z","message":{"role":"user","content":"usage"}}"#,
+            r#"{"type":"assistant","uuid":"a1","timestamp":"2025-01-01T00:01:30Z","message":{"content":"hello"}}"#,
+        ]);
+        assert_eq!(result.usage, None);
+        let metrics = result.metrics().unwrap();
+        assert_eq!(metrics.message_count, 2);
+        assert_eq!(metrics.estimated_tokens, 2);
+        assert_eq!(metrics.duration_seconds, Some(90));
+    }
+
+    #[test]
+    fn test_calculate_metrics_actual_usage() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let lines = [
+            r#"{"type":"user","uuid":"u1","sessionId":"usage","cwd":"/tmp/demo","message":{"role":"user","content":"hi"}}"#,
+            r#"{"type":"assistant","uuid":"a1","sessionId":"usage","cwd":"/tmp/demo","message":{"id":"msg_1","role":"assistant","content":"hello","usage":{"input_tokens":12,"cache_creation_input_tokens":100,"output_tokens":5}}}"#,
+            r#"{"type":"assistant","uuid":"a2","sessionId":"usage","cwd":"/tmp/demo","message":{"id":"msg_2","role":"assistant","content":"bye","usage":{"input_tokens":30,"output_tokens":8}}}"#,
+        ];
+        std::fs::write(project_dir.join("usage.jsonl"), lines.join("\n") + "\n").unwrap();
+
+        let mut reader = test_reader(&dir);
+        let meta = reader.get_session_by_id("usage").unwrap().unwrap();
+        let metrics = reader.calculate_metrics(&meta).unwrap().unwrap();
+        assert_eq!(metrics.actual_input_tokens, Some(142));
+        assert_eq!(metrics.actual_output_tokens, Some(13));
+        assert_eq!(metrics.estimated_tokens, 155);
+        assert_eq!(metrics.message_count, 3);
+    }
+
+    #[test]
+    fn test_star_session() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        write_session(&project_dir, "session-a", 1_000_000);
+        write_session(&project_dir, "session-b", 2_000_000);
+
+        let mut reader = test_reader(&dir);
+        reader.star_session("/tmp/demo", "session-a", true).unwrap();
+
+        let sessions = reader.list_sessions_with_stars(None, false, None).unwrap();
+        let starred: Vec<_> = sessions
+            .iter()
+            .filter(|s| s.is_starred)
+            .map(|s| s.meta.id.as_str())
+            .collect();
+        assert_eq!(starred, vec!["session-a"]);
+
+        let json = serde_json::to_value(&sessions[0]).unwrap();
+        assert!(json.get("isStarred").is_some());
+
+        reader.star_session("/tmp/demo", "session-a", false).unwrap();
+        let sessions = reader.list_sessions_with_stars(None, false, None).unwrap();
+        assert!(sessions.iter().all(|s| !s.is_starred));
+        assert!(reader.star_session("/tmp/demo", "../escape", true).is_err());
+    }
+
+    #[test]
+    fn test_list_sessions_file_size() {
+        let dir = TempDir::new().unwrap();
+        let project_dir = dir.path().join("projects/-tmp-demo");
+        write_session(&project_dir, "stub", 1_000_000);
+        write_session(&project_dir, "long", 2_000_000);
+        let long_path = project_dir.join("long.jsonl");
+        let mut file = std::fs::OpenOptions::new().append(true).open(&long_path).unwrap();
+        file.write_all(&[b' '; 2048]).unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_millis(2_000_000))
+            .unwrap();
+
+        let mut reader = test_reader(&dir);
+
+        let all = reader.list_sessions(None, false, None).unwrap();
+        assert!(all.iter().all(|s| s.file_size.is_some()));
+        let cached = reader.list_sessions(None, false, None).unwrap();
+        assert_eq!(
+            cached.iter().map(|s| s.file_size).collect::<Vec<_>>(),
+            all.iter().map(|s| s.file_size).collect::<Vec<_>>()
+        );
+
+        let stub_path = project_dir.join("stub.jsonl");
+        let stub_size = |sessions: &[SessionMeta]| {
+            sessions.iter().find(|s| s.id == "stub").and_then(|s| s.file_size)
+        };
+        let before = stub_size(&all).unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&stub_path).unwrap();
+        file.write_all(b"\n").unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_500_000))
+            .unwrap();
+        let updated = reader.list_sessions(None, false, None).unwrap();
+        assert_eq!(stub_size(&updated), Some(before + 1));
+
+        let sessions = reader.list_sessions_filtered(None, Some(1024), None).unwrap();
+        let ids: Vec<_> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["long"]);
+
+        let sessions = reader.list_sessions_filtered(None, None, Some(1024)).unwrap();
+        let ids: Vec<_> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["stub"]);
+    }
+
+    #[test]
+    fn test_from_archive() {
+        let dir = TempDir::new().unwrap();
+        let zip_path = dir.path().join("sessions.zip");
+
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        writer
+            .start_file(
+                ".claude/projects/-tmp-demo/session-1.jsonl",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        writeln!(writer, r#"{{"type":"user","uuid":"u1","sessionId":"session-1"}}"#).unwrap();
+        writer.finish().unwrap();
+
+        let reader = ClaudeReader::from_archive(&zip_path).unwrap();
+        let projects_path = reader.projects_path().to_path_buf();
+        assert!(projects_path.ends_with(".claude/projects"));
+        assert!(projects_path.join("-tmp-demo/session-1.jsonl").is_file());
+
+        // drop 后临时目录被清理
+        drop(reader);
+        assert!(!projects_path.exists());
+
+        assert!(ClaudeReader::from_archive(&dir.path().join("missing.zip")).is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = r#"{"projects": [{"path": "/tmp/demo", "sessions": [
+            {"id": "session-1", "mtime": 1700000000000, "messages": [
+                {"type": "user", "uuid": "u1"},
+                {"type": "assistant", "uuid": "a1"}
+            ]}
+        ]}]}"#;
+        let mut reader = ClaudeReader::from_json(json).unwrap();
+
+        let sessions = reader.list_sessions(None, false, None).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].project_path, "/tmp/demo");
+
+        let path = reader.projects_path().join("-tmp-demo/session-1.jsonl");
+        let options = ReadMessagesOptions::new(10, 0, Order::Asc);
+        let messages = reader
+            .read_messages_with_options(path.to_str().unwrap(), &options)
+            .unwrap();
+        assert_eq!(messages.total, 2);
+        assert_eq!(messages.messages[0]["sessionId"], "session-1");
+
+        let invalid_id = r#"{"projects": [{"path": "/tmp", "sessions": [{"id": "../x"}]}]}"#;
+        assert!(ClaudeReader::from_json(invalid_id).is_err());
+        assert!(ClaudeReader::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_sessions_batch() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        let paths: Vec<String> = (0..8)
+            .map(|i| {
+                let path = dir.path().join(format!("session-{}.jsonl", i));
+                let mut file = File::create(&path).unwrap();
+                writeln!(file, r#"{{"type":"user","uuid":"u{}","sessionId":"session-{}"}}"#, i, i)
+                    .unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+
+        let results = reader.parse_sessions_batch(paths.clone());
+        assert_eq!(results.len(), paths.len());
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        // 取消后全部返回错误
+        reader.cancel();
+        let results = reader.parse_sessions_batch(paths);
+        assert!(results.iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn test_read_messages_from_uuid() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        let path = dir.path().join("session.jsonl");
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, r#"{{"type":"summary","summary":"demo"}}"#).unwrap();
+        for i in 1..=5 {
+            writeln!(file, r#"{{"type":"user","uuid":"u{}"}}"#, i).unwrap();
+        }
+        writeln!(file, "{{broken").unwrap();
+        let path = path.to_str().unwrap();
+
+        let uuids = |r: &UuidMessagesResult| -> Vec<String> {
+            r.messages.iter().map(|m| m["uuid"].as_str().unwrap().to_string()).collect()
+        };
+
+        // 从头读取
+        let page = reader.read_messages_from_uuid(path, None, 2).unwrap();
+        assert_eq!(uuids(&page), ["u1", "u2"]);
+        assert_eq!(page.prev_uuid, None);
+        assert_eq!(page.next_uuid.as_deref(), Some("u2"));
+        assert!(page.has_more);
+
+        // 从 next_uuid 继续
+        let page = reader.read_messages_from_uuid(path, Some("u2"), 2).unwrap();
+        assert_eq!(uuids(&page), ["u3", "u4"]);
+        assert_eq!(page.prev_uuid.as_deref(), Some("u2"));
+
+        // 最后一页
+        let page = reader.read_messages_from_uuid(path, Some("u4"), 10).unwrap();
+        assert_eq!(uuids(&page), ["u5"]);
+        assert_eq!(page.next_uuid, None);
+        assert!(!page.has_more);
+
+        // UUID 不存在
+        assert!(reader.read_messages_from_uuid(path, Some("missing"), 10).is_err());
+    }
+
+    #[test]
+    fn test_is_session_loading() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+        let path = dir.path().join("session.jsonl");
+
+        let write = |last: &str| {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, r#"{{"type":"user","uuid":"u1","message":{{"content":"hi"}}}}"#).unwrap();
+            writeln!(file, "{}", last).unwrap();
+        };
+        let path_str = path.to_str().unwrap();
+
+        write(r#"{"type":"assistant","uuid":"a1","message":{"stop_reason":null}}"#);
+        assert!(reader.is_session_loading(path_str).unwrap());
+
+        write(r#"{"type":"assistant","uuid":"a1","message":{"stop_reason":"end_turn"}}"#);
+        assert!(!reader.is_session_loading(path_str).unwrap());
+        let last = reader.read_last_message(path_str).unwrap().unwrap();
+        assert_eq!(last["uuid"], "a1");
+
+        // 空会话不在生成中
+        File::create(&path).unwrap();
+        assert!(!reader.is_session_loading(path_str).unwrap());
+    }
+
+    #[test]
+    fn test_parse_session_checked_error_codes() {
+        let dir = TempDir::new().unwrap();
+        let reader = test_reader(&dir);
+
+        // 文件不存在
+        let missing = dir.path().join("missing.jsonl");
+        let err = reader
+            .parse_session_checked(missing.to_str().unwrap())
+            .unwrap_err();
+        assert_eq!(err.code, ParseErrorCode::IoError);
+
+        // 空文件
+        let empty = dir.path().join("empty.jsonl");
+        File::create(&empty).unwrap();
+        let err = reader
+            .parse_session_checked(empty.to_str().unwrap())
+            .unwrap_err();
+        assert_eq!(err.code, ParseErrorCode::Empty);
+
+        // 内容损坏
+        let corrupt = dir.path().join("corrupt.jsonl");
+        let mut file = File::create(&corrupt).unwrap();
+        writeln!(file, "not json at all").unwrap();
+        writeln!(file, "{{broken").unwrap();
+        let err = reader
+            .parse_session_checked(corrupt.to_str().unwrap())
+            .unwrap_err();
+        assert_eq!(err.code, ParseErrorCode::ParseError);
+    }
+
+    #[test]
+    fn test_is_message_line() {
+        assert!(is_message_line(br#"{"type":"user","uuid":"u1"}"#));
+        assert!(is_message_line(br#"{"uuid":"a1","type":"assistant"}"#));
+        // 内容中嵌套的 type 不算
+        assert!(!is_message_line(br#"{"type":"summary","message":{"type":"user"}}"#));
+        // 未写完的行不算
+        assert!(!is_message_line(br#"{"type":"user","uuid":"u"#));
+    }
+
+    #[test]
+    fn test_read_raw_messages_from_bytes() {
+        let data = concat!(
+            r#"{"type":"summary","summary":"s"}"#, "\n",
+            r#"{"type":"progress","data":{"type":"user"}}"#, "\n",
+            r#"{"type":"user","uuid":"u1"}"#, "\n",
+            "\n",
+            r#"{"type":"assistant","uuid":"a1"}"#, "\n",
+            r#"{"type":"user","uuid":"u2"}"#, "\n",
+            r#"{"type":"assistant","uuid":"a2"#,
+        );
+
+        let uuids = |r: &RawMessagesResult| -> Vec<String> {
+            r.messages.iter().map(|m| m["uuid"].as_str().unwrap().to_string()).collect()
+        };
+
+        // 不完整的最后一行不计入总数
+        let page = read_raw_messages_from_bytes(data.as_bytes(), 2, 0, Order::Asc);
+        assert_eq!(page.total, 3);
+        assert!(page.has_more);
+        assert_eq!(uuids(&page), ["u1", "a1"]);
+
+        // 倒序时也返回整页
+        let page = read_raw_messages_from_bytes(data.as_bytes(), 2, 0, Order::Desc);
+        assert_eq!(uuids(&page), ["u2", "a1"]);
+
+        let page = read_raw_messages_from_bytes(data.as_bytes(), 10, 2, Order::Asc);
+        assert!(!page.has_more);
+        assert_eq!(uuids(&page), ["u2"]);
+    }
+
+    #[test]
+    fn test_read_messages_mmap_large_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("large.jsonl");
+        let mut file = File::create(&path).unwrap();
+        let padding = "x".repeat(1024);
+        for i in 0..1200 {
+            writeln!(file, r#"{{"type":"user","uuid":"u{}","text":"{}"}}"#, i, padding).unwrap();
+        }
+        drop(file);
+
+        let reader = test_reader(&dir);
+        let result = reader
+            .read_messages_mmap(path.to_str().unwrap(), 10, 5, Order::Desc)
+            .unwrap();
+        assert_eq!(result.total, 1200);
+        assert!(result.has_more);
+        assert_eq!(result.messages.len(), 10);
+        assert_eq!(result.messages[0]["uuid"], "u1194");
     }
 }
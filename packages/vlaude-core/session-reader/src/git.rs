@@ -0,0 +1,113 @@
+//! Git 分支查询
+//!
+//! 直接读取 `.git/HEAD` 获取项目当前分支（不启动 git 进程），结果按项目路径缓存。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 分支缓存有效期
+const BRANCH_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// 项目分支缓存（project_path → (分支, 查询时间)）
+#[derive(Debug, Default)]
+pub(crate) struct GitBranchCache {
+    entries: HashMap<String, (Option<String>, Instant)>,
+}
+
+impl GitBranchCache {
+    /// 获取项目当前分支（缓存 30 秒）
+    pub(crate) fn get(&mut self, project_path: &str) -> Option<String> {
+        if let Some((branch, at)) = self.entries.get(project_path) {
+            if at.elapsed() < BRANCH_CACHE_TTL {
+                return branch.clone();
+            }
+        }
+
+        let branch = read_git_branch(Path::new(project_path));
+        self.entries
+            .insert(project_path.to_string(), (branch.clone(), Instant::now()));
+        branch
+    }
+}
+
+/// 读取目录当前所在的 git 分支
+///
+/// 不是仓库或处于 detached HEAD 时返回 None。
+fn read_git_branch(dir: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(find_git_dir(dir)?.join("HEAD")).ok()?;
+    let branch = head.trim().strip_prefix("ref: refs/heads/")?;
+    (!branch.is_empty()).then(|| branch.to_string())
+}
+
+/// 从目录向上查找 git 目录
+///
+/// `.git` 为文件时（worktree、submodule）按其中的 `gitdir:` 定位。
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some(ancestor.join(git_dir));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_repo_has_no_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_git_branch(dir.path()), None);
+        assert_eq!(read_git_branch(&dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn test_read_branch_from_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let git_dir = dir.path().join(".git");
+        std::fs::create_dir_all(&git_dir).unwrap();
+        let nested = dir.path().join("src/app");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/login\n").unwrap();
+        assert_eq!(read_git_branch(dir.path()).as_deref(), Some("feature/login"));
+        assert_eq!(read_git_branch(&nested).as_deref(), Some("feature/login"));
+
+        // detached HEAD
+        std::fs::write(git_dir.join("HEAD"), "9fceb02d0ae598e95dc970b74767f19372d61af8\n").unwrap();
+        assert_eq!(read_git_branch(dir.path()), None);
+
+        // worktree：.git 为指向实际 git 目录的文件
+        let worktree = dir.path().join("worktree");
+        let worktree_git = git_dir.join("worktrees/wt");
+        std::fs::create_dir_all(&worktree).unwrap();
+        std::fs::create_dir_all(&worktree_git).unwrap();
+        std::fs::write(worktree_git.join("HEAD"), "ref: refs/heads/wt\n").unwrap();
+        std::fs::write(worktree.join(".git"), format!("gitdir: {}\n", worktree_git.display())).unwrap();
+        assert_eq!(read_git_branch(&worktree).as_deref(), Some("wt"));
+    }
+
+    #[test]
+    fn test_cache_reuses_result() {
+        let mut cache = GitBranchCache::default();
+        cache
+            .entries
+            .insert("/tmp/project".to_string(), (Some("main".to_string()), Instant::now()));
+        assert_eq!(cache.get("/tmp/project"), Some("main".to_string()));
+
+        // 过期后重新查询（目录不存在，返回 None）
+        let expired = Instant::now() - BRANCH_CACHE_TTL - Duration::from_secs(1);
+        cache
+            .entries
+            .insert("/tmp/missing-project".to_string(), (Some("main".to_string()), expired));
+        assert_eq!(cache.get("/tmp/missing-project"), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! 会话消息迭代器
+//!
+//! 逐行读取 JSONL，每次只返回一条消息，不一次性分配整个消息数组。
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::de::IgnoredAny;
+
+use crate::claude::is_message_line;
+use crate::types::Order;
+
+/// 会话消息迭代器
+///
+/// 返回用户/助手消息的原始 JSON 行，不完整或无法解析的行直接跳过。
+/// 正序时边读边返回；倒序时先扫描一遍记录消息行的偏移，再从后往前逐条读取。
+pub struct MessageIter {
+    reader: BufReader<File>,
+    /// 倒序时尚未返回的消息行偏移（从末尾弹出）
+    offsets: Option<Vec<u64>>,
+    line: Vec<u8>,
+}
+
+impl MessageIter {
+    /// 打开会话文件
+    pub fn open(session_path: &Path, order: Order) -> anyhow::Result<Self> {
+        let file = File::open(session_path)
+            .map_err(|e| anyhow::anyhow!("无法打开会话文件 {:?}: {}", session_path, e))?;
+        let mut iter = Self {
+            reader: BufReader::new(file),
+            offsets: None,
+            line: Vec::new(),
+        };
+        if order == Order::Desc {
+            iter.offsets = Some(iter.scan_offsets()?);
+        }
+        Ok(iter)
+    }
+
+    /// 记录所有消息行的起始偏移
+    fn scan_offsets(&mut self) -> anyhow::Result<Vec<u64>> {
+        let mut offsets = Vec::new();
+        let mut position = 0u64;
+        loop {
+            self.line.clear();
+            let read = self.reader.read_until(b'\n', &mut self.line)?;
+            if read == 0 {
+                break;
+            }
+            if is_message_line(&self.line) {
+                offsets.push(position);
+            }
+            position += read as u64;
+        }
+        Ok(offsets)
+    }
+
+    /// 读取下一行（不含换行符），文件结束时返回 false
+    fn read_line(&mut self) -> bool {
+        self.line.clear();
+        match self.reader.read_until(b'\n', &mut self.line) {
+            Ok(0) | Err(_) => false,
+            Ok(_) => {
+                if self.line.last() == Some(&b'\n') {
+                    self.line.pop();
+                }
+                true
+            }
+        }
+    }
+
+    /// 当前行是合法 JSON 时取出
+    fn take_valid_line(&mut self) -> Option<String> {
+        serde_json::from_slice::<IgnoredAny>(&self.line).ok()?;
+        String::from_utf8(std::mem::take(&mut self.line)).ok()
+    }
+}
+
+impl Iterator for MessageIter {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            match self.offsets.as_mut() {
+                Some(offsets) => {
+                    let offset = offsets.pop()?;
+                    if self.reader.seek(SeekFrom::Start(offset)).is_err() || !self.read_line() {
+                        continue;
+                    }
+                }
+                None => {
+                    if !self.read_line() {
+                        return None;
+                    }
+                    if !is_message_line(&self.line) {
+                        continue;
+                    }
+                }
+            }
+            if let Some(line) = self.take_valid_line() {
+                return Some(line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn session_file() -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"type":"summary","summary":"demo"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"user","uuid":"u1"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"assistant","uuid":"a1"}}"#).unwrap();
+        writeln!(file, r#"{{"type":"user","uuid":"u2"}}"#).unwrap();
+        // 写到一半的最后一行
+        write!(file, r#"{{"type":"assistant","uuid":"a2","mess"#).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    fn uuids(iter: MessageIter) -> Vec<String> {
+        iter.map(|line| {
+            let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+            value["uuid"].as_str().unwrap().to_string()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_iter_asc() {
+        let file = session_file();
+        let iter = MessageIter::open(file.path(), Order::Asc).unwrap();
+        assert_eq!(uuids(iter), ["u1", "a1", "u2"]);
+    }
+
+    #[test]
+    fn test_iter_desc() {
+        let file = session_file();
+        let iter = MessageIter::open(file.path(), Order::Desc).unwrap();
+        assert_eq!(uuids(iter), ["u2", "a1", "u1"]);
+    }
+}
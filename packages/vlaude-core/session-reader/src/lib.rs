@@ -6,10 +6,22 @@
 //! - 消息读取
 //! - 文件监听
 
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
 pub mod types;
 pub mod claude;
 pub mod watcher;
+pub mod iter;
+pub mod stats;
+pub mod search;
+mod git;
+mod sidecar;
+mod pagination;
 
 pub use types::*;
-pub use claude::ClaudeReader;
-pub use watcher::{FileWatcher, WatchEvent, WatchMode};
+pub use claude::{claude_config_dir, ClaudeReader};
+pub use watcher::{AsyncFileWatcher, FileWatcher, WatchEvent, WatchMode};
+pub use iter::MessageIter;
+pub use stats::ReaderStats;
+pub use search::{MessageMatcher, SubstringMatcher};
@@ -0,0 +1,111 @@
+//! 会话游标分页
+//!
+//! 游标编码最后一条会话的 (file_mtime, session_id)，按 mtime 降序、id 升序稳定排序。
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// 分页游标
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SessionCursor {
+    pub file_mtime: u64,
+    pub session_id: String,
+}
+
+impl SessionCursor {
+    /// 编码为不透明字符串
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// 从不透明字符串解码
+    pub fn decode(cursor: &str) -> anyhow::Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|e| anyhow::anyhow!("无效的分页游标: {}", e))?;
+        serde_json::from_slice(&bytes).map_err(|e| anyhow::anyhow!("无效的分页游标: {}", e))
+    }
+
+    fn sort_key(&self) -> (Reverse<u64>, &str) {
+        (Reverse(self.file_mtime), self.session_id.as_str())
+    }
+}
+
+/// 按 (mtime 降序, id 升序) 排序并取游标之后的一页
+///
+/// 返回当前页和下一页游标（没有更多结果时为 None）。
+pub(crate) fn page_after<T>(
+    mut items: Vec<T>,
+    key: impl Fn(&T) -> (u64, String),
+    limit: usize,
+    cursor: Option<&SessionCursor>,
+) -> (Vec<T>, Option<SessionCursor>) {
+    items.sort_by_cached_key(|item| {
+        let (mtime, id) = key(item);
+        (Reverse(mtime), id)
+    });
+
+    let start = match cursor {
+        Some(cursor) => items.partition_point(|item| {
+            let (mtime, id) = key(item);
+            (Reverse(mtime), id.as_str()) <= cursor.sort_key()
+        }),
+        None => 0,
+    };
+
+    let mut page: Vec<T> = items.into_iter().skip(start).take(limit + 1).collect();
+    let has_more = page.len() > limit;
+    page.truncate(limit);
+
+    let next_cursor = if has_more {
+        page.last().map(|item| {
+            let (file_mtime, session_id) = key(item);
+            SessionCursor {
+                file_mtime,
+                session_id,
+            }
+        })
+    } else {
+        None
+    };
+
+    (page, next_cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(item: &(u64, &str)) -> (u64, String) {
+        (item.0, item.1.to_string())
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = SessionCursor {
+            file_mtime: 1_700_000_000_000,
+            session_id: "abc-123".to_string(),
+        };
+        assert_eq!(SessionCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(SessionCursor::decode("not a cursor!").is_err());
+    }
+
+    #[test]
+    fn test_page_after() {
+        let items = vec![(100, "b"), (300, "a"), (200, "c"), (200, "a"), (100, "a")];
+
+        let (page, cursor) = page_after(items.clone(), key, 2, None);
+        assert_eq!(page, vec![(300, "a"), (200, "a")]);
+        let cursor = cursor.unwrap();
+
+        let (page, cursor) = page_after(items.clone(), key, 2, Some(&cursor));
+        assert_eq!(page, vec![(200, "c"), (100, "a")]);
+        let cursor = cursor.unwrap();
+
+        let (page, cursor) = page_after(items, key, 2, Some(&cursor));
+        assert_eq!(page, vec![(100, "b")]);
+        assert!(cursor.is_none());
+    }
+}
@@ -0,0 +1,195 @@
+//! 消息全文搜索
+//!
+//! 逐行扫描 JSONL，只在用户/助手消息的文本内容中匹配。
+//! 匹配逻辑由 `MessageMatcher` 提供：默认子串匹配，也可以换成正则或全文索引。
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::claude::is_message_line;
+
+/// 片段中匹配位置前后各保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// 消息匹配器
+pub trait MessageMatcher: Send + Sync {
+    /// 在消息文本中查找第一个匹配，返回字节范围
+    fn find(&self, text: &str) -> Option<Range<usize>>;
+
+    /// 原始 JSON 行是否可能匹配（用于跳过解析，不确定时返回 true）
+    fn may_match(&self, _raw_line: &str) -> bool {
+        true
+    }
+}
+
+/// 子串匹配（区分大小写）
+#[derive(Debug, Clone)]
+pub struct SubstringMatcher {
+    query: String,
+    /// 查询在 JSON 中会被转义，无法直接在原始行中预筛
+    escaped_in_json: bool,
+}
+
+impl SubstringMatcher {
+    pub fn new(query: impl Into<String>) -> Self {
+        let query = query.into();
+        let escaped_in_json = query.chars().any(|c| c == '"' || c == '\\' || c.is_control());
+        Self {
+            query,
+            escaped_in_json,
+        }
+    }
+}
+
+impl MessageMatcher for SubstringMatcher {
+    fn find(&self, text: &str) -> Option<Range<usize>> {
+        text.find(&self.query).map(|start| start..start + self.query.len())
+    }
+
+    fn may_match(&self, raw_line: &str) -> bool {
+        self.escaped_in_json || raw_line.contains(&self.query)
+    }
+}
+
+impl MessageMatcher for regex::Regex {
+    fn find(&self, text: &str) -> Option<Range<usize>> {
+        regex::Regex::find(self, text).map(|m| m.range())
+    }
+}
+
+/// 文件中的一条匹配消息
+pub(crate) struct FileHit {
+    pub message_uuid: Option<String>,
+    pub snippet: String,
+    /// 消息行在文件中的字节偏移
+    pub byte_offset: u64,
+}
+
+/// 扫描会话文件，最多返回 `limit` 条匹配消息（每条消息一个结果）
+pub(crate) fn search_file(
+    path: &Path,
+    matcher: &dyn MessageMatcher,
+    limit: usize,
+) -> anyhow::Result<Vec<FileHit>> {
+    let file = File::open(path)
+        .map_err(|e| anyhow::anyhow!("无法打开会话文件 {:?}: {}", path, e))?;
+    let mut reader = BufReader::new(file);
+
+    let mut hits = Vec::new();
+    let mut line = Vec::new();
+    let mut position = 0u64;
+    while hits.len() < limit {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        let line_offset = position;
+        position += read as u64;
+
+        if !is_message_line(&line) {
+            continue;
+        }
+        let Ok(raw) = std::str::from_utf8(&line) else {
+            continue;
+        };
+        if !matcher.may_match(raw) {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) else {
+            continue;
+        };
+        let text = message_text(&value);
+        if let Some(range) = matcher.find(&text) {
+            hits.push(FileHit {
+                message_uuid: value.get("uuid").and_then(|v| v.as_str()).map(str::to_string),
+                snippet: snippet(&text, range),
+                byte_offset: line_offset,
+            });
+        }
+    }
+    Ok(hits)
+}
+
+/// 原始消息行的文本内容是否满足全部匹配器，满足时返回解析后的消息
+pub(crate) fn match_line(
+    raw_line: &str,
+    matchers: &[Box<dyn MessageMatcher>],
+) -> Option<serde_json::Value> {
+    if !matchers.iter().all(|m| m.may_match(raw_line)) {
+        return None;
+    }
+    let value = serde_json::from_str::<serde_json::Value>(raw_line).ok()?;
+    let text = message_text(&value);
+    matchers.iter().all(|m| m.find(&text).is_some()).then_some(value)
+}
+
+/// 消息的文本内容（字符串 content，或 content 数组中的 text 块，以换行连接）
+pub(crate) fn message_text(value: &serde_json::Value) -> String {
+    match value.pointer("/message/content") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 截取匹配位置前后的文本（按字符截断，不拆分多字节字符）
+fn snippet(text: &str, range: Range<usize>) -> String {
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[range.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map_or(text.len(), |(i, _)| range.end + i);
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(&text[start..end]);
+    if end < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet_unicode() {
+        let text = format!("{}目标文本{}", "前".repeat(50), "后".repeat(50));
+        let range = SubstringMatcher::new("目标").find(&text).unwrap();
+        assert_eq!(
+            snippet(&text, range),
+            format!("…{}目标文本{}…", "前".repeat(40), "后".repeat(38))
+        );
+
+        let range = SubstringMatcher::new("短").find("很短").unwrap();
+        assert_eq!(snippet("很短", range), "很短");
+    }
+
+    #[test]
+    fn test_matcher_prefilter() {
+        let line = r#"{"type":"user","message":{"content":"say \"hi\""}}"#;
+        assert!(SubstringMatcher::new("say").may_match(line));
+        assert!(!SubstringMatcher::new("bye").may_match(line));
+        // 含引号的查询无法在原始行中预筛
+        assert!(SubstringMatcher::new("\"hi\"").may_match(line));
+
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(SubstringMatcher::new("\"hi\"").find(&message_text(&value)), Some(4..8));
+        let re = regex::Regex::new(r"h.").unwrap();
+        assert_eq!(MessageMatcher::find(&re, "say hi"), Some(4..6));
+    }
+}
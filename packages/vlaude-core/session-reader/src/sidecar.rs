@@ -0,0 +1,124 @@
+//! 项目目录下的 vlaude 元数据文件
+//!
+//! `{projects}/{encoded}/.vlaude-meta.json` 记录 vlaude 自己的会话标记（如星标），
+//! Claude Code 不读取该文件。格式：`{ "starred_sessions": ["uuid1", "uuid2"] }`。
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 元数据文件名
+pub(crate) const SIDECAR_FILE_NAME: &str = ".vlaude-meta.json";
+
+const STARRED_SESSIONS_KEY: &str = "starred_sessions";
+
+type Document = serde_json::Map<String, serde_json::Value>;
+
+/// 星标缓存（项目目录 → (文件修改时间, 星标会话)），文件修改后重新读取
+#[derive(Debug, Default)]
+pub(crate) struct StarCache {
+    entries: HashMap<PathBuf, (Option<SystemTime>, HashSet<String>)>,
+}
+
+impl StarCache {
+    /// 获取项目的星标会话
+    pub(crate) fn starred(&mut self, project_dir: &Path) -> &HashSet<String> {
+        let path = project_dir.join(SIDECAR_FILE_NAME);
+        let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+        let stale = self
+            .entries
+            .get(project_dir)
+            .is_none_or(|(cached_mtime, _)| *cached_mtime != mtime);
+        if stale {
+            let starred = read_starred(&path);
+            self.entries.insert(project_dir.to_path_buf(), (mtime, starred));
+        }
+        &self.entries[project_dir].1
+    }
+
+    /// 设置会话星标并写回文件（写临时文件后原子替换，保留文件中的其他字段）
+    pub(crate) fn set_starred(
+        &mut self,
+        project_dir: &Path,
+        session_id: &str,
+        starred: bool,
+    ) -> anyhow::Result<()> {
+        let path = project_dir.join(SIDECAR_FILE_NAME);
+        let mut document = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Document>(&bytes)
+                .map_err(|e| anyhow::anyhow!("无效的元数据文件 {:?}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Document::new(),
+            Err(e) => return Err(anyhow::anyhow!("无法读取元数据文件 {:?}: {}", path, e)),
+        };
+
+        let mut sessions = starred_from_document(&document);
+        let changed = if starred {
+            sessions.insert(session_id.to_string())
+        } else {
+            sessions.remove(session_id)
+        };
+        if !changed {
+            return Ok(());
+        }
+
+        let mut sorted: Vec<_> = sessions.into_iter().collect();
+        sorted.sort();
+        document.insert(STARRED_SESSIONS_KEY.to_string(), sorted.into());
+
+        std::fs::create_dir_all(project_dir)?;
+        let mut temp = tempfile::NamedTempFile::new_in(project_dir)?;
+        serde_json::to_writer_pretty(&mut temp, &document)?;
+        temp.write_all(b"\n")?;
+        temp.as_file().sync_all()?;
+        temp.persist(&path)
+            .map_err(|e| anyhow::anyhow!("无法写入元数据文件 {:?}: {}", path, e))?;
+
+        self.entries.remove(project_dir);
+        Ok(())
+    }
+}
+
+/// 读取星标会话（文件不存在或无效时为空）
+fn read_starred(path: &Path) -> HashSet<String> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Document>(&bytes).ok())
+        .map(|document| starred_from_document(&document))
+        .unwrap_or_default()
+}
+
+fn starred_from_document(document: &Document) -> HashSet<String> {
+    document
+        .get(STARRED_SESSIONS_KEY)
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| id.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_starred_keeps_other_fields() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(SIDECAR_FILE_NAME);
+        std::fs::write(&path, r#"{"note": "keep", "starred_sessions": ["a"]}"#).unwrap();
+
+        let mut cache = StarCache::default();
+        assert!(cache.starred(dir.path()).contains("a"));
+
+        cache.set_starred(dir.path(), "b", true).unwrap();
+        cache.set_starred(dir.path(), "a", false).unwrap();
+        let starred = cache.starred(dir.path());
+        assert_eq!(starred.len(), 1);
+        assert!(starred.contains("b"));
+
+        let document: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(document["note"], "keep");
+        assert_eq!(document["starred_sessions"], serde_json::json!(["b"]));
+    }
+}
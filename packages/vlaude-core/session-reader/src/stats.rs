@@ -0,0 +1,87 @@
+//! 读取器统计
+//!
+//! 计数器在关键操作处累加，供调用方（如 Swift 监控面板）查看读取器的负载。
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 读取器统计快照
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReaderStats {
+    /// 最近一次 list_projects 返回的项目数
+    pub projects_cached: u64,
+    /// 已缓存所属项目的会话数
+    pub sessions_cached: u64,
+    /// 打开的会话文件数
+    pub total_files_opened: u64,
+    /// 读取的字节数
+    pub total_bytes_read: u64,
+    /// 会话项目缓存命中次数
+    pub cache_hits: u64,
+    /// 会话项目缓存未命中次数
+    pub cache_misses: u64,
+    /// 最近一次项目扫描耗时（毫秒）
+    pub last_scan_duration_ms: u64,
+}
+
+/// 读取器计数器
+#[derive(Debug, Default)]
+pub(crate) struct ReaderCounters {
+    projects_cached: AtomicU64,
+    files_opened: AtomicU64,
+    bytes_read: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    last_scan_duration_ms: AtomicU64,
+}
+
+impl ReaderCounters {
+    /// 记录打开一个文件
+    pub fn record_open(&self) {
+        self.files_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录读取的字节数
+    pub fn record_bytes(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// 记录缓存查找结果
+    pub fn record_cache(&self, hit: bool) {
+        let counter = if hit { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次项目扫描
+    pub fn record_scan(&self, projects: usize, duration: Duration) {
+        self.projects_cached.store(projects as u64, Ordering::Relaxed);
+        self.last_scan_duration_ms
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// 生成快照
+    pub fn snapshot(&self, sessions_cached: usize) -> ReaderStats {
+        ReaderStats {
+            projects_cached: self.projects_cached.load(Ordering::Relaxed),
+            sessions_cached: sessions_cached as u64,
+            total_files_opened: self.files_opened.load(Ordering::Relaxed),
+            total_bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            last_scan_duration_ms: self.last_scan_duration_ms.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 清零累计计数（缓存大小类的值保留）
+    pub fn reset(&self) {
+        for counter in [
+            &self.files_opened,
+            &self.bytes_read,
+            &self.cache_hits,
+            &self.cache_misses,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
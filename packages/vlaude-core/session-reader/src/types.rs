@@ -5,6 +5,8 @@
 
 use serde::Serialize;
 
+use crate::search::{MessageMatcher, SubstringMatcher};
+
 // Re-export core types from claude-session-db
 pub use claude_session_db::{
     IndexableMessage, IndexableSession, MessageType, ParsedMessage, ParseResult, SessionMeta,
@@ -18,6 +20,45 @@ pub enum Order {
     Desc,
 }
 
+/// 消息读取选项
+///
+/// 设置 `search_query` / `search_regex` 时只返回消息文本匹配的消息（与 `search_messages`
+/// 的匹配规则相同），`limit` / `offset` / `total` 均针对匹配结果。
+#[derive(Debug, Clone)]
+pub struct ReadMessagesOptions {
+    pub limit: usize,
+    pub offset: usize,
+    pub order: Order,
+    /// 子串匹配
+    pub search_query: Option<String>,
+    /// 正则匹配
+    pub search_regex: Option<regex::Regex>,
+}
+
+impl ReadMessagesOptions {
+    pub fn new(limit: usize, offset: usize, order: Order) -> Self {
+        Self {
+            limit,
+            offset,
+            order,
+            search_query: None,
+            search_regex: None,
+        }
+    }
+
+    /// 搜索条件对应的匹配器（未设置搜索条件时为空）
+    pub(crate) fn matchers(&self) -> Vec<Box<dyn MessageMatcher>> {
+        let mut matchers: Vec<Box<dyn MessageMatcher>> = Vec::new();
+        if let Some(query) = &self.search_query {
+            matchers.push(Box::new(SubstringMatcher::new(query.clone())));
+        }
+        if let Some(regex) = &self.search_regex {
+            matchers.push(Box::new(regex.clone()));
+        }
+        matchers
+    }
+}
+
 /// 项目信息
 #[derive(Debug, Clone, Serialize)]
 pub struct ProjectInfo {
@@ -31,6 +72,41 @@ pub struct ProjectInfo {
     pub session_count: usize,
     /// 最后活跃时间（毫秒时间戳）
     pub last_active: Option<u64>,
+    /// 当前 git 分支（不是仓库或 git 不可用时为 None）
+    pub git_branch: Option<String>,
+}
+
+// 项目以路径为唯一标识，便于放入 HashSet 去重
+impl PartialEq for ProjectInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for ProjectInfo {}
+
+impl std::hash::Hash for ProjectInfo {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+/// 会话分页结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionPage {
+    pub sessions: Vec<SessionMeta>,
+    /// 下一页游标（不透明 base64 字符串），没有更多结果时为 None
+    pub next_cursor: Option<String>,
+}
+
+/// 带 vlaude 标记的会话（字段与 `SessionMeta` 平铺序列化）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEntry {
+    #[serde(flatten)]
+    pub meta: SessionMeta,
+    /// 是否加了星标（记录在项目目录的 `.vlaude-meta.json` 中）
+    pub is_starred: bool,
 }
 
 /// 消息读取结果
@@ -49,6 +125,20 @@ pub struct RawMessagesResult {
     pub has_more: bool,
 }
 
+/// 按 UUID 定位的消息读取结果（用于增量同步）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UuidMessagesResult {
+    /// 原始 JSONL 消息
+    pub messages: Vec<serde_json::Value>,
+    /// 本页之前最后一条消息的 UUID（本页从头开始时为 None）
+    pub prev_uuid: Option<String>,
+    /// 本页最后一条消息的 UUID（后面没有更多消息时为 None），作为下一页的 `from_uuid`
+    pub next_uuid: Option<String>,
+    /// 是否还有更多消息
+    pub has_more: bool,
+}
+
 /// 会话 Metrics
 #[derive(Debug, Clone, Serialize)]
 pub struct SessionMetrics {
@@ -58,8 +148,143 @@ pub struct SessionMetrics {
     pub user_message_count: usize,
     /// 助手消息数
     pub assistant_message_count: usize,
-    /// 总 token 数（估算）
+    /// 总 token 数（有 usage 时为实际值之和，否则按字符数估算）
     pub estimated_tokens: usize,
+    /// 实际输入 token 数（助手消息 `usage` 中 `input_tokens` 与缓存读写 token 之和，没有 usage 时为 None）
+    pub actual_input_tokens: Option<usize>,
+    /// 实际输出 token 数（助手消息 `usage.output_tokens` 之和，没有 usage 时为 None）
+    pub actual_output_tokens: Option<usize>,
     /// 会话时长（秒）
     pub duration_seconds: Option<u64>,
 }
+
+/// 项目聚合统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProjectStats {
+    /// 会话数（不含 agent session）
+    pub total_sessions: usize,
+    /// 消息总数
+    pub total_messages: usize,
+    /// 总 token 数（估算）
+    pub total_estimated_tokens: usize,
+    /// 各会话时长之和（秒）
+    pub total_duration_seconds: u64,
+    /// 使用过的模型（按名称排序）
+    pub unique_models_used: Vec<String>,
+    /// 最早会话的第一条消息时间（毫秒）
+    pub first_session_created: Option<u64>,
+    /// 最近一次会话文件修改时间（毫秒）
+    pub last_activity: Option<u64>,
+    /// 平均每个会话的消息数
+    pub average_messages_per_session: f64,
+}
+
+/// 会话文件校验结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationResult {
+    /// 非空行数
+    pub line_count: usize,
+    /// 有效 JSON 行数
+    pub valid_json_lines: usize,
+    /// 损坏的行（行号从 1 开始，错误信息），最多记录 `MAX_MALFORMED_LINES` 条
+    pub malformed_lines: Vec<(usize, String)>,
+    /// 是否包含 summary 行
+    pub has_session_summary: bool,
+    /// 文件大小（字节）
+    pub estimated_bytes: u64,
+}
+
+impl ValidationResult {
+    /// 记录损坏行的上限，避免损坏严重的大文件产生巨大结果
+    pub const MAX_MALFORMED_LINES: usize = 100;
+
+    /// 是否所有行都是有效 JSON
+    pub fn is_valid(&self) -> bool {
+        self.valid_json_lines == self.line_count
+    }
+}
+
+/// 会话归档结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveResult {
+    /// 已归档（dry run 时为将要归档）的会话 ID
+    pub moved: Vec<String>,
+    /// 释放的字节数（原始 JSONL 大小）
+    pub total_bytes_freed: u64,
+    /// 归档失败的会话（会话 ID，错误信息）
+    pub errors: Vec<(String, String)>,
+}
+
+/// 消息搜索结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub session_id: String,
+    pub project_path: String,
+    /// 匹配消息的 UUID（消息没有 uuid 字段时为 None）
+    pub message_uuid: Option<String>,
+    /// 匹配位置前后的文本片段
+    pub snippet: String,
+    /// 消息行在会话文件中的字节偏移
+    pub byte_offset: u64,
+}
+
+/// 会话导入结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportResult {
+    /// 已导入的会话 ID
+    pub imported: Vec<String>,
+    /// 目标项目中已存在而跳过的会话 ID
+    pub skipped: Vec<String>,
+    /// 导入失败的文件（文件名，错误信息）
+    pub failed: Vec<(String, String)>,
+}
+
+/// 会话解析错误码
+///
+/// 区分「空会话」（预期情况）与 I/O、解析等异常，供 FFI 层直接返回。
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorCode {
+    Success = 0,
+    Empty = 1,
+    IoError = 2,
+    ParseError = 3,
+    PermissionDenied = 4,
+    /// 操作被 `ClaudeReader::cancel` 取消
+    Cancelled = 5,
+}
+
+/// 会话解析错误
+#[derive(Debug, Clone)]
+pub struct ParseSessionError {
+    pub code: ParseErrorCode,
+    pub message: String,
+}
+
+impl ParseSessionError {
+    pub fn new(code: ParseErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseSessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ParseSessionError {}
+
+impl From<std::io::Error> for ParseSessionError {
+    fn from(e: std::io::Error) -> Self {
+        let code = match e.kind() {
+            std::io::ErrorKind::PermissionDenied => ParseErrorCode::PermissionDenied,
+            _ => ParseErrorCode::IoError,
+        };
+        Self::new(code, e.to_string())
+    }
+}
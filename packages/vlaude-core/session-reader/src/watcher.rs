@@ -3,12 +3,20 @@
 //! 监听 Claude projects 目录的变化
 
 use anyhow::Result;
+use futures::task::AtomicWaker;
+use futures::{Stream, StreamExt};
 use notify::{RecommendedWatcher, RecursiveMode};
-use notify_debouncer_mini::{new_debouncer, DebouncedEvent, Debouncer};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Receiver};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::Duration;
 
+use crate::claude::decode_project_dir;
+
 /// 监听模式
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WatchMode {
@@ -18,6 +26,10 @@ pub enum WatchMode {
     Sessions,
     /// 监听指定会话文件的内容变化
     SessionContent,
+    /// 递归监听整个 projects 目录（所有项目的会话文件）
+    ///
+    /// 首次出现的文件报告为 `Created`，可用 `FileWatcher::session_of` 把路径还原为会话。
+    AllProjects,
 }
 
 /// 监听事件
@@ -26,59 +38,178 @@ pub enum WatchEvent {
     /// 文件创建
     Created(PathBuf),
     /// 文件修改
-    Modified(PathBuf),
+    Modified {
+        path: PathBuf,
+        /// 修改前的文件大小（首次观察到该文件时为 None）
+        size_before: Option<u64>,
+        /// 修改后的文件大小
+        size_after: u64,
+    },
     /// 文件删除
     Removed(PathBuf),
     /// 错误
     Error(String),
 }
 
+impl WatchEvent {
+    /// 旧版 `Modified(PathBuf)` 的兼容构造（大小从文件系统读取）
+    #[deprecated(note = "use WatchEvent::Modified { path, size_before, size_after }")]
+    pub fn modified(path: PathBuf) -> Self {
+        let size_after = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        WatchEvent::Modified {
+            path,
+            size_before: None,
+            size_after,
+        }
+    }
+
+    /// 事件对应的文件路径
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            WatchEvent::Created(path) | WatchEvent::Removed(path) => Some(path),
+            WatchEvent::Modified { path, .. } => Some(path),
+            WatchEvent::Error(_) => None,
+        }
+    }
+
+    /// 修改事件的大小变化（字节，截断时为负；修改前大小未知时为 None）
+    pub fn size_delta(&self) -> Option<i64> {
+        match self {
+            WatchEvent::Modified {
+                size_before: Some(before),
+                size_after,
+                ..
+            } => Some(*size_after as i64 - *before as i64),
+            _ => None,
+        }
+    }
+}
+
 /// 文件监听器
+///
+/// 既可以同步调用 `next_event` / `try_next_event`，也可以作为异步 `Stream` 使用；
+/// 需要在 `select!` 中等待时用 `into_async` 转换为 `AsyncFileWatcher`。
 pub struct FileWatcher {
     debouncer: Debouncer<RecommendedWatcher>,
-    rx: Receiver<Result<Vec<DebouncedEvent>, notify::Error>>,
+    rx: Receiver<DebounceEventResult>,
+    /// 异步消费者的 waker，由 notify 线程在有新事件时唤醒
+    waker: Arc<AtomicWaker>,
+    state: WatchState,
+}
+
+/// 事件转换所需的状态
+struct WatchState {
+    mode: WatchMode,
+    /// 监听的根路径（规范化后，与 notify 上报的路径一致）
+    root: PathBuf,
+    /// 已观察到的文件大小（用于计算修改前后的大小）
+    sizes: Mutex<HashMap<PathBuf, u64>>,
+    /// 已还原的项目路径（项目目录 → project_path），`AllProjects` 模式使用
+    project_paths: Mutex<HashMap<PathBuf, String>>,
 }
 
 impl FileWatcher {
     /// 创建监听器
     pub fn new(path: &Path, mode: WatchMode) -> Result<Self> {
         let (tx, rx) = channel();
+        let waker = Arc::new(AtomicWaker::new());
 
         let debounce_time = match mode {
             WatchMode::Projects => Duration::from_millis(500),
             WatchMode::Sessions => Duration::from_millis(300),
             WatchMode::SessionContent => Duration::from_millis(100),
+            WatchMode::AllProjects => Duration::from_millis(300),
         };
 
-        let mut debouncer = new_debouncer(debounce_time, tx)?;
+        let mut debouncer = new_debouncer(debounce_time, {
+            let waker = waker.clone();
+            move |result: DebounceEventResult| {
+                if tx.send(result).is_ok() {
+                    waker.wake();
+                }
+            }
+        })?;
 
         let recursive_mode = match mode {
             WatchMode::Projects => RecursiveMode::NonRecursive,
             WatchMode::Sessions => RecursiveMode::NonRecursive,
             WatchMode::SessionContent => RecursiveMode::NonRecursive,
+            WatchMode::AllProjects => RecursiveMode::Recursive,
         };
 
         debouncer.watcher().watch(path, recursive_mode)?;
 
-        Ok(Self { debouncer, rx })
+        let state = WatchState {
+            mode,
+            root: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            sizes: Mutex::new(HashMap::new()),
+            project_paths: Mutex::new(HashMap::new()),
+        };
+        state.record_size(path);
+        Ok(Self {
+            debouncer,
+            rx,
+            waker,
+            state,
+        })
+    }
+
+    /// 创建监听器并扫描当前状态
+    ///
+    /// 监听开始前已存在的文件不会触发事件，这里为每个现有文件生成一个
+    /// `Modified { size_before: None, .. }` 快照（按路径排序），
+    /// 调用方可以据此发现监听中断期间的变化。
+    pub fn new_with_initial_scan(path: &Path, mode: WatchMode) -> Result<(Self, Vec<WatchEvent>)> {
+        let watcher = Self::new(path, mode)?;
+
+        let mut files: Vec<(PathBuf, u64)> = if mode == WatchMode::AllProjects {
+            // 只扫描项目目录下一层的会话文件
+            std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .flat_map(|entry| list_files(&entry.path()))
+                .collect()
+        } else if path.is_dir() {
+            list_files(path)
+        } else {
+            std::fs::metadata(path)
+                .map(|metadata| vec![(path.to_path_buf(), metadata.len())])
+                .unwrap_or_default()
+        };
+        files.sort();
+
+        let events = {
+            let mut sizes = watcher.state.sizes.lock().unwrap_or_else(|e| e.into_inner());
+            files
+                .into_iter()
+                .map(|(path, size)| {
+                    sizes.insert(path.clone(), size);
+                    WatchEvent::Modified {
+                        path,
+                        size_before: None,
+                        size_after: size,
+                    }
+                })
+                .collect()
+        };
+        Ok((watcher, events))
     }
 
     /// 获取下一个事件（阻塞）
     pub fn next_event(&self) -> Option<Vec<WatchEvent>> {
-        match self.rx.recv() {
-            Ok(Ok(events)) => Some(self.convert_events(events)),
-            Ok(Err(e)) => Some(vec![WatchEvent::Error(e.to_string())]),
-            Err(_) => None,
-        }
+        self.rx.recv().ok().map(|result| self.state.convert_result(result))
     }
 
     /// 尝试获取事件（非阻塞）
     pub fn try_next_event(&self) -> Option<Vec<WatchEvent>> {
-        match self.rx.try_recv() {
-            Ok(Ok(events)) => Some(self.convert_events(events)),
-            Ok(Err(e)) => Some(vec![WatchEvent::Error(e.to_string())]),
-            Err(_) => None,
-        }
+        self.rx.try_recv().ok().map(|result| self.state.convert_result(result))
+    }
+
+    /// 转换为异步监听器
+    ///
+    /// 基于 `Stream` 实现等待事件，不占用额外线程。
+    pub fn into_async(self) -> AsyncFileWatcher {
+        AsyncFileWatcher { inner: self }
     }
 
     /// 添加监听路径
@@ -89,6 +220,7 @@ impl FileWatcher {
             RecursiveMode::NonRecursive
         };
         self.debouncer.watcher().watch(path, mode)?;
+        self.state.record_size(path);
         Ok(())
     }
 
@@ -98,14 +230,155 @@ impl FileWatcher {
         Ok(())
     }
 
+    /// `AllProjects` 模式下把会话文件路径还原为 (project_path, session_id)
+    ///
+    /// 路径须为 `{root}/{encoded_dir}/{session_id}.jsonl`（不含 agent 会话）。
+    /// 项目路径从目录中会话记录的 `cwd` 还原并缓存，文件删除后仍可还原。
+    pub fn session_of(&self, path: &Path) -> Option<(String, String)> {
+        self.state.session_of(path)
+    }
+}
+
+/// 异步文件监听器（由 `FileWatcher::into_async` 创建）
+///
+/// `next_event` 是取消安全的，可以放在 `tokio::select!` 中与其他事件一起等待。
+pub struct AsyncFileWatcher {
+    inner: FileWatcher,
+}
+
+impl AsyncFileWatcher {
+    /// 等待下一批事件，监听器已停止时返回 None
+    pub async fn next_event(&mut self) -> Option<Vec<WatchEvent>> {
+        self.inner.next().await
+    }
+
+    /// 尝试获取事件（非阻塞）
+    pub fn try_next_event(&mut self) -> Option<Vec<WatchEvent>> {
+        self.inner.try_next_event()
+    }
+
+    /// 添加监听路径
+    pub fn watch(&mut self, path: &Path, recursive: bool) -> Result<()> {
+        self.inner.watch(path, recursive)
+    }
+
+    /// 移除监听路径
+    pub fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.inner.unwatch(path)
+    }
+
+    /// 见 `FileWatcher::session_of`
+    pub fn session_of(&self, path: &Path) -> Option<(String, String)> {
+        self.inner.session_of(path)
+    }
+}
+
+impl WatchState {
+    fn session_of(&self, path: &Path) -> Option<(String, String)> {
+        if self.mode != WatchMode::AllProjects || path.extension()? != "jsonl" {
+            return None;
+        }
+        let project_dir = path.parent()?;
+        let root = project_dir.parent()?;
+        if root != self.root && root.canonicalize().ok()? != self.root {
+            return None;
+        }
+        let session_id = path.file_stem()?.to_str()?;
+        if session_id.starts_with("agent-") {
+            return None;
+        }
+
+        let mut project_paths = self.project_paths.lock().unwrap_or_else(|e| e.into_inner());
+        let project_path = match project_paths.get(project_dir) {
+            Some(project_path) => project_path.clone(),
+            None => {
+                let project_path = decode_project_dir(project_dir)?;
+                project_paths.insert(project_dir.to_path_buf(), project_path.clone());
+                project_path
+            }
+        };
+        Some((project_path, session_id.to_string()))
+    }
+
+    /// 记录被监听文件的当前大小（目录不记录）
+    fn record_size(&self, path: &Path) {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.is_file() {
+                let mut sizes = self.sizes.lock().unwrap_or_else(|e| e.into_inner());
+                sizes.insert(path.to_path_buf(), metadata.len());
+            }
+        }
+    }
+
+    fn convert_result(&self, result: DebounceEventResult) -> Vec<WatchEvent> {
+        match result {
+            Ok(events) => self.convert_events(events),
+            Err(e) => vec![WatchEvent::Error(e.to_string())],
+        }
+    }
+
     fn convert_events(&self, events: Vec<DebouncedEvent>) -> Vec<WatchEvent> {
+        let mut sizes = self.sizes.lock().unwrap_or_else(|e| e.into_inner());
         events
             .into_iter()
-            .map(|e| WatchEvent::Modified(e.path))
+            .map(|e| match std::fs::metadata(&e.path) {
+                Ok(metadata) => {
+                    let size_after = metadata.len();
+                    let size_before = if metadata.is_file() {
+                        sizes.insert(e.path.clone(), size_after)
+                    } else {
+                        None
+                    };
+                    let first_seen = metadata.is_file() && size_before.is_none();
+                    if self.mode == WatchMode::AllProjects && first_seen {
+                        return WatchEvent::Created(e.path);
+                    }
+                    WatchEvent::Modified {
+                        path: e.path,
+                        size_before,
+                        size_after,
+                    }
+                }
+                Err(_) => {
+                    sizes.remove(&e.path);
+                    WatchEvent::Removed(e.path)
+                }
+            })
             .collect()
     }
 }
 
+impl Stream for FileWatcher {
+    type Item = Vec<WatchEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // 先注册 waker 再检查 channel，避免错过两者之间到达的事件
+        this.waker.register(cx.waker());
+
+        match this.rx.try_recv() {
+            Ok(result) => Poll::Ready(Some(this.state.convert_result(result))),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
+
+/// 目录下的文件及其大小（不递归）
+fn list_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (entry.path(), metadata.len()))
+        })
+        .collect()
+}
+
 /// 创建异步事件流的 helper
 pub fn watch_with_callback<F>(path: &Path, mode: WatchMode, _callback: F) -> Result<FileWatcher>
 where
@@ -128,4 +401,133 @@ mod tests {
         assert_eq!(WatchMode::Projects, WatchMode::Projects);
         assert_ne!(WatchMode::Projects, WatchMode::Sessions);
     }
+
+    #[test]
+    fn test_initial_scan_reports_existing_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("b.jsonl"), "{}\n").unwrap();
+        std::fs::write(dir.path().join("a.jsonl"), "{}\n{}\n").unwrap();
+        std::fs::create_dir(dir.path().join("subdir")).unwrap();
+
+        let (_watcher, events) =
+            FileWatcher::new_with_initial_scan(dir.path(), WatchMode::Sessions).unwrap();
+        let snapshot: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                WatchEvent::Modified { path, size_before, size_after } => {
+                    assert_eq!(*size_before, None);
+                    (path.file_name().unwrap().to_str().unwrap().to_string(), *size_after)
+                }
+                other => panic!("unexpected event: {:?}", other),
+            })
+            .collect();
+        assert_eq!(snapshot, [("a.jsonl".to_string(), 6), ("b.jsonl".to_string(), 3)]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_receives_events() {
+        use futures::StreamExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut watcher = FileWatcher::new(dir.path(), WatchMode::SessionContent).unwrap();
+
+        let file = dir.path().join("session.jsonl");
+        std::fs::write(&file, "{}\n").unwrap();
+
+        let events = tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("timed out waiting for watch event")
+            .expect("stream ended");
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, WatchEvent::Modified { path, .. } if path.ends_with("session.jsonl"))));
+    }
+
+    #[tokio::test]
+    async fn test_into_async() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut watcher = FileWatcher::new(dir.path(), WatchMode::SessionContent)
+            .unwrap()
+            .into_async();
+        assert!(watcher.try_next_event().is_none());
+
+        std::fs::write(dir.path().join("session.jsonl"), "{}\n").unwrap();
+
+        let events = tokio::time::timeout(Duration::from_secs(5), watcher.next_event())
+            .await
+            .expect("timed out waiting for watch event")
+            .expect("watcher stopped");
+        assert!(events.iter().any(|e| matches!(
+            e,
+            WatchEvent::Modified { path, .. } if path.ends_with("session.jsonl")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_all_projects_reports_created_sessions() {
+        use futures::StreamExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let project_dir = dir.path().join("-tmp-demo");
+        std::fs::create_dir(&project_dir).unwrap();
+        std::fs::write(project_dir.join("old.jsonl"), "{\"cwd\":\"/tmp/demo\"}\n").unwrap();
+
+        let (mut watcher, existing) =
+            FileWatcher::new_with_initial_scan(dir.path(), WatchMode::AllProjects).unwrap();
+        assert_eq!(existing.len(), 1);
+
+        let file = project_dir.join("new.jsonl");
+        std::fs::write(&file, "{\"cwd\":\"/tmp/demo\"}\n").unwrap();
+
+        let events = tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("timed out waiting for watch event")
+            .expect("stream ended");
+        let path = events
+            .iter()
+            .find_map(|e| match e {
+                WatchEvent::Created(path) if path.ends_with("new.jsonl") => Some(path),
+                _ => None,
+            })
+            .expect("no created event for new session");
+        assert_eq!(
+            watcher.session_of(path),
+            Some(("/tmp/demo".to_string(), "new".to_string()))
+        );
+        assert_eq!(watcher.session_of(&dir.path().join("stray.jsonl")), None);
+    }
+
+    #[tokio::test]
+    async fn test_modified_reports_size_delta() {
+        use futures::StreamExt;
+        use std::io::Write;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("session.jsonl");
+        std::fs::write(&file, "{}\n").unwrap();
+
+        let mut watcher = FileWatcher::new(&file, WatchMode::SessionContent).unwrap();
+
+        let mut handle = std::fs::OpenOptions::new().append(true).open(&file).unwrap();
+        writeln!(handle, r#"{{"type":"user"}}"#).unwrap();
+        drop(handle);
+
+        let events = tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("timed out waiting for watch event")
+            .expect("stream ended");
+        let event = events
+            .iter()
+            .find(|e| e.path().is_some_and(|p| p.ends_with("session.jsonl")))
+            .expect("no event for session file");
+
+        match event {
+            WatchEvent::Modified { size_before, size_after, .. } => {
+                assert_eq!(*size_before, Some(3));
+                assert_eq!(*size_after, 3 + r#"{"type":"user"}"#.len() as u64 + 1);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert_eq!(event.size_delta(), Some(16));
+    }
 }
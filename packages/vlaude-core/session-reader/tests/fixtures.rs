@@ -0,0 +1,138 @@
+//! 使用 `tests/fixtures/projects` 下的真实格式 JSONL 验证索引解析结果
+//!
+//! 样本为脱敏后的 Claude Code 会话（不含 API key、真实路径）。
+
+use std::path::PathBuf;
+
+use session_reader::{ClaudeReader, IndexableSession};
+
+const SIMPLE_SESSION: &str = "-tmp-fixture-simple/5c1e7a52-2f0e-4c1b-9a3e-000000000001.jsonl";
+const TOOLS_SESSION: &str = "-tmp-fixture-tools/5c1e7a52-2f0e-4c1b-9a3e-000000000002.jsonl";
+const AGENTS_SESSION: &str = "-tmp-fixture-agents/5c1e7a52-2f0e-4c1b-9a3e-000000000003.jsonl";
+const UNICODE_SESSION: &str = "-tmp-fixture-unicode/5c1e7a52-2f0e-4c1b-9a3e-000000000004.jsonl";
+const TRUNCATED_SESSION: &str = "-tmp-fixture-truncated/5c1e7a52-2f0e-4c1b-9a3e-000000000005.jsonl";
+
+fn projects_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/projects")
+}
+
+fn reader() -> ClaudeReader {
+    ClaudeReader::new(projects_path())
+}
+
+fn parse(relative: &str) -> IndexableSession {
+    let path = projects_path().join(relative);
+    reader()
+        .parse_session_from_path(path.to_str().unwrap())
+        .unwrap()
+        .unwrap_or_else(|| panic!("fixture {} produced no session", relative))
+}
+
+fn uuids(session: &IndexableSession) -> Vec<&str> {
+    session.messages.iter().map(|m| m.uuid.as_str()).collect()
+}
+
+fn contents(session: &IndexableSession) -> String {
+    session
+        .messages
+        .iter()
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_simple_session() {
+    let session = parse(SIMPLE_SESSION);
+
+    assert_eq!(session.session_id, "5c1e7a52-2f0e-4c1b-9a3e-000000000001");
+    assert_eq!(session.project_path, "/tmp/fixture-simple");
+    // summary 行不是消息
+    assert_eq!(
+        uuids(&session),
+        [
+            "u0000001-0000-4000-8000-000000000001",
+            "a0000001-0000-4000-8000-000000000002",
+            "u0000001-0000-4000-8000-000000000003",
+            "a0000001-0000-4000-8000-000000000004",
+        ]
+    );
+    assert_eq!(session.messages[0].content, "What is 2 + 2?");
+    assert_eq!(session.messages[1].content, "2 + 2 = 4.");
+}
+
+#[test]
+fn test_tool_use_session() {
+    let session = parse(TOOLS_SESSION);
+
+    assert_eq!(session.session_id, "5c1e7a52-2f0e-4c1b-9a3e-000000000002");
+    assert_eq!(session.project_path, "/tmp/fixture-tools");
+
+    let ids = uuids(&session);
+    assert!(ids.contains(&"u0000002-0000-4000-8000-000000000001"));
+    assert!(ids.contains(&"a0000002-0000-4000-8000-000000000002"));
+    assert!(ids.contains(&"a0000002-0000-4000-8000-000000000004"));
+
+    let text = contents(&session);
+    assert!(text.contains("Let me check the project files."));
+    assert!(text.contains("The project contains Cargo.toml and a src directory."));
+}
+
+#[test]
+fn test_session_with_agents() {
+    let session = parse(AGENTS_SESSION);
+
+    assert_eq!(session.session_id, "5c1e7a52-2f0e-4c1b-9a3e-000000000003");
+    assert_eq!(session.project_path, "/tmp/fixture-agents");
+
+    // 主会话不包含 agent 子会话的消息
+    let ids = uuids(&session);
+    assert!(ids.iter().all(|id| !id.contains("agent-")));
+    assert!(contents(&session).contains("Both reviews are done"));
+
+    // agent 子会话单独成文件，默认不出现在会话列表中
+    let mut reader = reader();
+    let sessions = reader
+        .list_sessions(Some("/tmp/fixture-agents"), false, None)
+        .unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, "5c1e7a52-2f0e-4c1b-9a3e-000000000003");
+
+    let with_agents = reader
+        .list_sessions(Some("/tmp/fixture-agents"), true, None)
+        .unwrap();
+    assert_eq!(with_agents.len(), 3);
+}
+
+#[test]
+fn test_unicode_session() {
+    let session = parse(UNICODE_SESSION);
+
+    assert_eq!(session.messages.len(), 2);
+    assert_eq!(session.messages[0].content, "帮我把这段日志翻译成英文：连接已断开 🔌");
+    assert_eq!(
+        session.messages[1].content,
+        "\"Connection lost\" 🔌 — 日本語: 接続が切断されました、한국어: 연결이 끊어졌습니다"
+    );
+}
+
+#[test]
+fn test_truncated_session() {
+    let session = parse(TRUNCATED_SESSION);
+
+    // 写到一半的最后一行被跳过，之前的完整消息保留
+    assert_eq!(
+        uuids(&session),
+        [
+            "u0000005-0000-4000-8000-000000000001",
+            "a0000005-0000-4000-8000-000000000002",
+            "u0000005-0000-4000-8000-000000000003",
+        ]
+    );
+    assert!(!contents(&session).contains("cut off"));
+
+    // 读取最后一条消息时同样跳过不完整的行
+    let path = projects_path().join(TRUNCATED_SESSION);
+    let last = reader().read_last_message(path.to_str().unwrap()).unwrap().unwrap();
+    assert_eq!(last["uuid"], "u0000005-0000-4000-8000-000000000003");
+}
@@ -1,15 +1,56 @@
 use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 提交到仓库的头文件
+const HEADER: &str = "socket_client_ffi.h";
 
 fn main() {
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
 
+    // 只在 FFI 源码或配置变化时重新生成头文件
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=CBINDGEN_CHECK");
+
     let config = cbindgen::Config::from_file("cbindgen.toml")
         .unwrap_or_default();
 
-    cbindgen::Builder::new()
+    let bindings = cbindgen::Builder::new()
         .with_crate(&crate_dir)
         .with_config(config)
         .generate()
-        .expect("Unable to generate bindings")
-        .write_to_file("socket_client_ffi.h");
+        .expect("Unable to generate bindings");
+
+    // CI 中设置 CBINDGEN_CHECK=1：只检查已提交的头文件是否最新，不覆盖
+    if env::var("CBINDGEN_CHECK").is_ok_and(|v| v == "1") {
+        let generated = PathBuf::from(env::var("OUT_DIR").unwrap()).join(HEADER);
+        bindings.write_to_file(&generated);
+        check_header(&generated, &Path::new(&crate_dir).join(HEADER));
+        return;
+    }
+
+    bindings.write_to_file(HEADER);
+}
+
+/// 对比生成的头文件和已提交的头文件，不一致时让构建失败
+fn check_header(generated: &Path, committed: &Path) {
+    let generated_content = std::fs::read(generated).expect("Unable to read generated header");
+    let committed_content = std::fs::read(committed).unwrap_or_default();
+    if generated_content == committed_content {
+        return;
+    }
+
+    let diff = Command::new("diff")
+        .arg("-u")
+        .arg(committed)
+        .arg(generated)
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_else(|_| "(diff unavailable)".to_string());
+
+    panic!(
+        "{} is out of date, rebuild without CBINDGEN_CHECK and commit the result:\n{}",
+        HEADER, diff
+    );
 }
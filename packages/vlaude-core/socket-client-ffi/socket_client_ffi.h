@@ -20,14 +20,37 @@ typedef enum SocketClientError {
   EMIT_FAILED = 5,
   RUNTIME_ERROR = 6,
   REGISTRY_ERROR = 7,
+  INVALID_ARGUMENT = 8,
+  DNS_RESOLUTION_FAILED = 9,
+  CONNECTION_REFUSED = 10,
+  TLS_HANDSHAKE_FAILED = 11,
+  CONNECTION_TIMEOUT = 12,
+  ACK_TIMEOUT = 13,
+  RECONNECTING = 14,
   UNKNOWN = 99,
 } SocketClientError;
 
+/**
+ * 连接状态（`socket_client_is_connected` 返回值）
+ *
+ * 变体带 `Status` 前缀，避免与 `SocketClientError` 在 C 头文件中重名。
+ */
+typedef enum SocketConnectionStatus {
+  STATUS_DISCONNECTED = 0,
+  STATUS_CONNECTED = 1,
+  STATUS_RECONNECTING = 2,
+} SocketConnectionStatus;
+
 /**
  * 不透明句柄
  */
 typedef struct SocketClientHandle SocketClientHandle;
 
+/**
+ * 连接池不透明句柄
+ */
+typedef struct SocketPoolHandle SocketPoolHandle;
+
 /**
  * 事件回调类型
  */
@@ -36,6 +59,8 @@ typedef void (*EventCallbackFn)(const char *event, const char *data, void *user_
 /**
  * 连接到服务器
  *
+ * 正在重连时不再发起新连接，返回 `Reconnecting`。
+ *
  * # Safety
  * - `handle` 必须是有效句柄
  */
@@ -62,12 +87,32 @@ enum SocketClientError socket_client_connect_with_discovery(struct SocketClientH
  * # Safety
  * - `url` 必须是有效的 UTF-8 C 字符串（如 "https://localhost:10005"）
  * - `namespace` 必须是有效的 UTF-8 C 字符串（如 "/daemon"），可为 null 使用默认值
+ * - `resolver_name` 可为 null；非 null 时按名称选择内置命名空间解析器（如 "platform"），
+ *   此时忽略 `namespace`
+ * - `ack_timeout_json` 可为 null 使用默认策略；非 null 时为 JSON 字符串，
+ *   如 `{"defaultSecs": 10, "perEvent": {"daemon:register": 5}}`
  * - 返回的句柄需要通过 `socket_client_destroy` 释放
  */
 enum SocketClientError socket_client_create(const char *url,
                                             const char *namespace_,
+                                            const char *resolver_name,
+                                            const char *ack_timeout_json,
                                             struct SocketClientHandle **out_handle);
 
+/**
+ * 创建带握手认证的 Socket 客户端
+ *
+ * # Safety
+ * - `url` 必须是有效的 UTF-8 C 字符串
+ * - `namespace` 可为 null 使用默认值 "/daemon"
+ * - `auth_json` 必须是有效的 JSON 字符串（如 `{"token": "xxx"}`），作为 Socket.IO 握手 `auth` 发送
+ * - 返回的句柄需要通过 `socket_client_destroy` 释放
+ */
+enum SocketClientError socket_client_create_authenticated(const char *url,
+                                                          const char *namespace_,
+                                                          const char *auth_json,
+                                                          struct SocketClientHandle **out_handle);
+
 /**
  * 创建带 Redis 配置的 Socket 客户端
  *
@@ -75,7 +120,9 @@ enum SocketClientError socket_client_create(const char *url,
  * - `url` 必须是有效的 UTF-8 C 字符串
  * - `namespace` 可为 null
  * - `redis_host` 可为 null（不启用 Redis）
- * - `device_id`, `device_name`, `platform`, `version` 启用 Redis 时必填
+ * - `redis_port` 为 0 时使用默认端口 6379
+ * - `device_id`, `device_name`, `platform`, `version` 启用 Redis 时必填，
+ *   `device_id` 只能包含字母数字和 '-'（最长 64），否则返回 `InvalidArgument`
  * - 返回的句柄需要通过 `socket_client_destroy` 释放
  */
 enum SocketClientError socket_client_create_with_redis(const char *url,
@@ -129,6 +176,19 @@ enum SocketClientError socket_client_emit(struct SocketClientHandle *handle,
                                           const char *event,
                                           const char *json_data);
 
+/**
+ * 合并发送一批事件
+ *
+ * `events_json` 为 `[{"event": "...", "data": {...}}, ...]`，按顺序打包为 `daemon:batch` 信封发送
+ * （过大时自动拆分），批次之间不会交错。遇到第一个发送失败的信封即停止并返回其错误码。
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ * - `events_json` 必须是有效的 UTF-8 C 字符串
+ */
+enum SocketClientError socket_client_emit_batch(struct SocketClientHandle *handle,
+                                                const char *events_json);
+
 /**
  * 释放 C 字符串
  *
@@ -138,12 +198,40 @@ enum SocketClientError socket_client_emit(struct SocketClientHandle *handle,
 void socket_client_free_string(char *s);
 
 /**
- * 检查是否已连接
+ * 获取连接状态
+ *
+ * 返回 `StatusConnected` / `StatusReconnecting` / `StatusDisconnected`（断开为 0，已连接为 1）
  *
  * # Safety
  * - `handle` 必须是有效句柄
  */
-bool socket_client_is_connected(const struct SocketClientHandle *handle);
+enum SocketConnectionStatus socket_client_is_connected(const struct SocketClientHandle *handle);
+
+/**
+ * 探测 Server 是否在响应
+ *
+ * 发送 `daemon:ping` 并在 `timeout_ms` 内等待 Server 的 pong（ack），`timeout_ms` 为 0 时使用 5 秒。
+ * 成功时往返时间（毫秒）写入 `out_latency_ms`。
+ * 未连接返回 `NotConnected`，没有响应返回 `AckTimeout`。
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ * - `out_latency_ms` 可为 null（不需要延迟时）
+ */
+enum SocketClientError socket_client_ping(struct SocketClientHandle *handle,
+                                          uint64_t timeout_ms,
+                                          uint64_t *out_latency_ms);
+
+/**
+ * 获取当前连接状态名
+ *
+ * 返回 `disconnected` / `connecting` / `authenticating` / `ready` / `reconnecting` / `disconnecting`，
+ * 调用者需要使用 `socket_client_free_string` 释放返回的字符串
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ */
+char *socket_client_get_state(const struct SocketClientHandle *handle);
 
 /**
  * 上报新消息
@@ -183,7 +271,8 @@ enum SocketClientError socket_client_notify_session_list_update(struct SocketCli
  *
  * # Safety
  * - `handle` 必须是有效句柄
- * - `hostname`, `platform`, `version` 必须是有效的 UTF-8 C 字符串
+ * - `hostname`, `version` 必须是有效的 UTF-8 C 字符串
+ * - `platform` 可为 null，此时自动检测当前平台（macOS 上报为 "darwin"）
  */
 enum SocketClientError socket_client_register(struct SocketClientHandle *handle,
                                               const char *hostname,
@@ -234,6 +323,27 @@ enum SocketClientError socket_client_report_session_messages(struct SocketClient
                                                              bool has_more,
                                                              const char *request_id);
 
+/**
+ * 上报会话消息（MessagePack 编码）
+ *
+ * 大批量消息避免在 FFI 边界构造和解析巨大的 C 字符串。
+ * `msgpack_data` 为消息数组的 MessagePack 编码，发送前转换为 JSON。
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ * - `session_id`, `project_path` 必须是有效字符串
+ * - `msgpack_data` 必须指向至少 `data_len` 字节的有效内存
+ * - `request_id` 可为 null
+ */
+enum SocketClientError socket_client_report_session_messages_msgpack(struct SocketClientHandle *handle,
+                                                                     const char *session_id,
+                                                                     const char *project_path,
+                                                                     const uint8_t *msgpack_data,
+                                                                     uintptr_t data_len,
+                                                                     uintptr_t total,
+                                                                     bool has_more,
+                                                                     const char *request_id);
+
 /**
  * 上报会话元数据
  *
@@ -275,6 +385,8 @@ enum SocketClientError socket_client_send_message_result(struct SocketClientHand
 /**
  * 发送会话创建结果
  *
+ * ETerm 只创建新会话，结果中 `resumed` 固定为 false。
+ *
  * # Safety
  * - `handle` 必须是有效句柄
  * - `request_id` 必须是有效字符串
@@ -302,6 +414,33 @@ void socket_client_set_event_callback(struct SocketClientHandle *handle,
                                       EventCallbackFn callback,
                                       void *user_data);
 
+/**
+ * 为指定事件添加处理器（连接前后均可调用）
+ *
+ * 返回处理器 ID（用于移除），参数无效时返回 0。
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ * - `event` 必须是有效的 UTF-8 C 字符串
+ * - `callback` 在处理器移除前必须有效
+ * - `user_data` 可为 null
+ */
+uint64_t socket_client_add_event_handler(struct SocketClientHandle *handle,
+                                         const char *event,
+                                         EventCallbackFn callback,
+                                         void *user_data);
+
+/**
+ * 移除事件处理器
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ * - `event` 必须是有效的 UTF-8 C 字符串
+ */
+enum SocketClientError socket_client_remove_event_handler(struct SocketClientHandle *handle,
+                                                          const char *event,
+                                                          uint64_t handler_id);
+
 /**
  * 更新 Redis 中的 Session 列表
  *
@@ -313,6 +452,17 @@ void socket_client_set_event_callback(struct SocketClientHandle *handle,
 enum SocketClientError socket_client_update_sessions(struct SocketClientHandle *handle,
                                                      const char *sessions_json);
 
+/**
+ * 获取所有 Daemon 当前打开的 Session
+ *
+ * 返回 JSON 对象 `{"<sessionId>": "<deviceId>"}`，未启用 Redis 时为 `{}`，出错时返回 null。
+ * 调用者需要使用 `socket_client_free_string` 释放返回的字符串
+ *
+ * # Safety
+ * - `handle` 必须是有效句柄
+ */
+char *socket_client_list_daemon_sessions(struct SocketClientHandle *handle);
+
 /**
  * 获取版本号
  *
@@ -321,4 +471,40 @@ enum SocketClientError socket_client_update_sessions(struct SocketClientHandle *
  */
 const char *socket_client_version(void);
 
+/**
+ * 创建 Socket 连接池
+ *
+ * # Safety
+ * - `url` 必须是有效的 UTF-8 C 字符串（如 "https://localhost:10005"）
+ * - `policies_json` 可为 null 使用默认重连策略；非 null 时为 JSON 对象，
+ *   `default` 为默认策略，其余键为命名空间，如
+ *   `{"default": {"maxAttempts": 5}, "/daemon": {"maxAttempts": null, "baseDelayMs": 200}}`
+ * - 返回的句柄需要通过 `socket_pool_destroy` 释放
+ */
+enum SocketClientError socket_pool_create(const char *url,
+                                          const char *policies_json,
+                                          struct SocketPoolHandle **out_pool);
+
+/**
+ * 销毁连接池（断开并释放池中所有客户端句柄）
+ *
+ * # Safety
+ * - `pool` 必须是 `socket_pool_create` 返回的有效句柄
+ * - 调用后连接池及 `socket_pool_get_socket` 返回的句柄都不再有效
+ */
+void socket_pool_destroy(struct SocketPoolHandle *pool);
+
+/**
+ * 获取连接池中命名空间对应的客户端句柄（不存在时创建，不会自动连接）
+ *
+ * # Safety
+ * - `pool` 必须是 `socket_pool_create` 返回的有效句柄
+ * - `namespace` 必须是有效的 UTF-8 C 字符串（如 "/daemon"）
+ * - 返回的句柄归连接池所有，在 `socket_pool_destroy` 之前有效，
+ *   不能传给 `socket_client_destroy`
+ */
+enum SocketClientError socket_pool_get_socket(struct SocketPoolHandle *pool,
+                                              const char *namespace_,
+                                              struct SocketClientHandle **out_handle);
+
 #endif  /* SOCKET_CLIENT_FFI_H */
@@ -6,12 +6,19 @@
 //! - VlaudeKit 使用此 FFI 处理 Socket 连接和数据同步
 //! - ETerm 特有逻辑（createSession, sendMessage 等）保留在 Swift 层
 
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
 use socket_client::{
-    DaemonRegistration, ServiceRegistryConfig, SessionInfo, SocketClient, SocketConfig, TlsConfig,
+    AckTimeoutPolicy, ConnectionError, ConnectionState, DaemonRegistration, HandlerId,
+    NamespaceConfig, ReconnectPolicy, ServiceRegistryConfig, SessionInfo, SocketClient,
+    SocketClientPool, SocketConfig, SocketError, TlsConfig, DEFAULT_MAX_BATCH_BYTES, PING_TIMEOUT,
 };
+use std::collections::HashMap;
 use std::ffi::{c_char, c_void, CStr, CString};
 use std::panic::{self, AssertUnwindSafe};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use tokio::runtime::Runtime;
 use tokio::sync::RwLock;
 
@@ -29,9 +36,45 @@ pub enum SocketClientError {
     EmitFailed = 5,
     RuntimeError = 6,
     RegistryError = 7,
+    InvalidArgument = 8,
+    DnsResolutionFailed = 9,
+    ConnectionRefused = 10,
+    TlsHandshakeFailed = 11,
+    ConnectionTimeout = 12,
+    AckTimeout = 13,
+    Reconnecting = 14,
     Unknown = 99,
 }
 
+/// 连接状态（`socket_client_is_connected` 返回值）
+///
+/// 变体带 `Status` 前缀，避免与 `SocketClientError` 在 C 头文件中重名。
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketConnectionStatus {
+    StatusDisconnected = 0,
+    StatusConnected = 1,
+    StatusReconnecting = 2,
+}
+
+impl SocketClientError {
+    /// 连接错误映射为错误码（区分 DNS / TCP / TLS / 超时）
+    fn from_connect_error(err: &SocketError) -> Self {
+        match err {
+            SocketError::ConnectionFailed(e) => match e {
+                ConnectionError::DnsResolution(_) => SocketClientError::DnsResolutionFailed,
+                ConnectionError::TcpRefused => SocketClientError::ConnectionRefused,
+                ConnectionError::TlsHandshake(_) => SocketClientError::TlsHandshakeFailed,
+                ConnectionError::Timeout => SocketClientError::ConnectionTimeout,
+                ConnectionError::Other(_) => SocketClientError::ConnectionFailed,
+            },
+            SocketError::RegistryError(_) => SocketClientError::RegistryError,
+            SocketError::Reconnecting => SocketClientError::Reconnecting,
+            _ => SocketClientError::ConnectionFailed,
+        }
+    }
+}
+
 // ==================== 句柄 ====================
 
 /// 不透明句柄
@@ -42,6 +85,18 @@ pub struct SocketClientHandle {
     event_callback: Arc<RwLock<Option<EventCallback>>>,
     /// 事件循环任务句柄（用于避免多次启动）
     event_loop_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    /// 重连循环任务句柄（运行时共享，销毁句柄时需主动停止）
+    reconnect_loop_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    /// 重连循环正在重连
+    reconnecting: Arc<AtomicBool>,
+}
+
+impl SocketClientHandle {
+    /// 是否正在重连（重连循环进行中，或客户端处于重连状态）
+    fn is_reconnecting(&self) -> bool {
+        self.reconnecting.load(Ordering::SeqCst)
+            || self.client.state() == ConnectionState::Reconnecting
+    }
 }
 
 /// 事件回调类型
@@ -56,6 +111,28 @@ struct EventCallback {
 unsafe impl Send for EventCallback {}
 unsafe impl Sync for EventCallback {}
 
+// ==================== 共享运行时 ====================
+
+/// 所有句柄共享的 tokio 运行时
+///
+/// 只保存弱引用：每个句柄持有一个 `Arc`，最后一个句柄销毁时运行时随之释放。
+static SHARED_RUNTIME: Mutex<Weak<Runtime>> = Mutex::new(Weak::new());
+
+/// 获取共享运行时（不存在时创建）
+fn acquire_runtime() -> Result<Arc<Runtime>, SocketClientError> {
+    let mut shared = SHARED_RUNTIME
+        .lock()
+        .map_err(|_| SocketClientError::RuntimeError)?;
+
+    if let Some(runtime) = shared.upgrade() {
+        return Ok(runtime);
+    }
+
+    let runtime = Arc::new(Runtime::new().map_err(|_| SocketClientError::RuntimeError)?);
+    *shared = Arc::downgrade(&runtime);
+    Ok(runtime)
+}
+
 // ==================== 创建/销毁 ====================
 
 /// 创建 Socket 客户端
@@ -63,31 +140,132 @@ unsafe impl Sync for EventCallback {}
 /// # Safety
 /// - `url` 必须是有效的 UTF-8 C 字符串（如 "https://localhost:10005"）
 /// - `namespace` 必须是有效的 UTF-8 C 字符串（如 "/daemon"），可为 null 使用默认值
+/// - `resolver_name` 可为 null；非 null 时按名称选择内置命名空间解析器（如 "platform"），
+///   此时忽略 `namespace`
+/// - `ack_timeout_json` 可为 null 使用默认策略；非 null 时为 JSON 字符串，
+///   如 `{"defaultSecs": 10, "perEvent": {"daemon:register": 5}}`
 /// - 返回的句柄需要通过 `socket_client_destroy` 释放
 #[no_mangle]
 pub unsafe extern "C" fn socket_client_create(
     url: *const c_char,
     namespace: *const c_char,
+    resolver_name: *const c_char,
+    ack_timeout_json: *const c_char,
     out_handle: *mut *mut SocketClientHandle,
 ) -> SocketClientError {
     if url.is_null() || out_handle.is_null() {
         return SocketClientError::NullPointer;
     }
 
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let url_str = CStr::from_ptr(url)
+            .to_str()
+            .map_err(|_| SocketClientError::InvalidUtf8)?;
+
+        let namespace_config = if !resolver_name.is_null() {
+            let name = CStr::from_ptr(resolver_name)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?;
+            NamespaceConfig::from_resolver_name(name)
+                .ok_or(SocketClientError::InvalidArgument)?
+        } else if namespace.is_null() {
+            NamespaceConfig::Static("/daemon".to_string())
+        } else {
+            NamespaceConfig::Static(
+                CStr::from_ptr(namespace)
+                    .to_str()
+                    .map_err(|_| SocketClientError::InvalidUtf8)?
+                    .to_string(),
+            )
+        };
+
+        let ack_timeout = if ack_timeout_json.is_null() {
+            AckTimeoutPolicy::default()
+        } else {
+            let json_str = CStr::from_ptr(ack_timeout_json)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?;
+            serde_json::from_str(json_str).map_err(|_| SocketClientError::InvalidArgument)?
+        };
+
+        // 创建 TLS 配置（开发模式：跳过证书验证）
+        let tls = TlsConfig {
+            danger_accept_invalid_certs: true,
+            ..Default::default()
+        };
+
+        let config = SocketConfig {
+            url: url_str.to_string(),
+            namespace: namespace_config,
+            tls,
+            redis: None,
+            daemon_info: None,
+            auth: None,
+            ack_timeout,
+            reconnect: ReconnectPolicy::default(),
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+        };
+
+        let runtime = acquire_runtime()?;
+        let client = SocketClient::new(config);
+
+        Ok(SocketClientHandle {
+            client: Arc::new(client),
+            runtime,
+            event_callback: Arc::new(RwLock::new(None)),
+            event_loop_handle: Arc::new(RwLock::new(None)),
+            reconnect_loop_handle: Arc::new(RwLock::new(None)),
+            reconnecting: Arc::new(AtomicBool::new(false)),
+        })
+    }));
+
+    match result {
+        Ok(Ok(handle)) => {
+            *out_handle = Box::into_raw(Box::new(handle));
+            SocketClientError::Success
+        }
+        Ok(Err(e)) => e,
+        Err(_) => SocketClientError::Unknown,
+    }
+}
+
+/// 创建带握手认证的 Socket 客户端
+///
+/// # Safety
+/// - `url` 必须是有效的 UTF-8 C 字符串
+/// - `namespace` 可为 null 使用默认值 "/daemon"
+/// - `auth_json` 必须是有效的 JSON 字符串（如 `{"token": "xxx"}`），作为 Socket.IO 握手 `auth` 发送
+/// - 返回的句柄需要通过 `socket_client_destroy` 释放
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_create_authenticated(
+    url: *const c_char,
+    namespace: *const c_char,
+    auth_json: *const c_char,
+    out_handle: *mut *mut SocketClientHandle,
+) -> SocketClientError {
+    if url.is_null() || auth_json.is_null() || out_handle.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
     let result = panic::catch_unwind(AssertUnwindSafe(|| {
         let url_str = CStr::from_ptr(url)
             .to_str()
             .map_err(|_| SocketClientError::InvalidUtf8)?;
 
         let namespace_str = if namespace.is_null() {
-            "/daemon".to_string()
+            "/daemon"
         } else {
             CStr::from_ptr(namespace)
                 .to_str()
                 .map_err(|_| SocketClientError::InvalidUtf8)?
-                .to_string()
         };
 
+        let auth_str = CStr::from_ptr(auth_json)
+            .to_str()
+            .map_err(|_| SocketClientError::InvalidUtf8)?;
+        let auth: serde_json::Value =
+            serde_json::from_str(auth_str).map_err(|_| SocketClientError::InvalidArgument)?;
+
         // 创建 TLS 配置（开发模式：跳过证书验证）
         let tls = TlsConfig {
             danger_accept_invalid_certs: true,
@@ -96,20 +274,26 @@ pub unsafe extern "C" fn socket_client_create(
 
         let config = SocketConfig {
             url: url_str.to_string(),
-            namespace: namespace_str,
+            namespace: namespace_str.into(),
             tls,
             redis: None,
             daemon_info: None,
+            auth: Some(auth),
+            ack_timeout: AckTimeoutPolicy::default(),
+            reconnect: ReconnectPolicy::default(),
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
         };
 
-        let runtime = Runtime::new().map_err(|_| SocketClientError::RuntimeError)?;
+        let runtime = acquire_runtime()?;
         let client = SocketClient::new(config);
 
         Ok(SocketClientHandle {
             client: Arc::new(client),
-            runtime: Arc::new(runtime),
+            runtime,
             event_callback: Arc::new(RwLock::new(None)),
             event_loop_handle: Arc::new(RwLock::new(None)),
+            reconnect_loop_handle: Arc::new(RwLock::new(None)),
+            reconnecting: Arc::new(AtomicBool::new(false)),
         })
     }));
 
@@ -132,18 +316,30 @@ pub unsafe extern "C" fn socket_client_create(
 pub unsafe extern "C" fn socket_client_destroy(handle: *mut SocketClientHandle) {
     if !handle.is_null() {
         let handle = Box::from_raw(handle);
-        // 断开连接
-        handle.runtime.block_on(async {
-            handle.client.disconnect().await;
-        });
-        // handle 自动 drop
+        shutdown_handle(&handle);
+        // handle 自动 drop，最后一个句柄释放共享运行时
     }
 }
 
+/// 断开连接，并停止句柄的后台任务（运行时可能仍被其他句柄使用）
+fn shutdown_handle(handle: &SocketClientHandle) {
+    handle.runtime.block_on(async {
+        handle.client.disconnect().await;
+        if let Some(task) = handle.event_loop_handle.write().await.take() {
+            task.abort();
+        }
+        if let Some(task) = handle.reconnect_loop_handle.write().await.take() {
+            task.abort();
+        }
+    });
+}
+
 // ==================== 连接管理 ====================
 
 /// 连接到服务器
 ///
+/// 正在重连时不再发起新连接，返回 `Reconnecting`。
+///
 /// # Safety
 /// - `handle` 必须是有效句柄
 #[no_mangle]
@@ -153,9 +349,13 @@ pub unsafe extern "C" fn socket_client_connect(handle: *mut SocketClientHandle)
     }
 
     let handle = &*handle;
-    let result = handle.runtime.block_on(async {
-        handle.client.connect().await
-    });
+    let result = if handle.is_reconnecting() {
+        Err(SocketError::Reconnecting)
+    } else {
+        handle.runtime.block_on(async {
+            handle.client.connect().await
+        })
+    };
 
     match result {
         Ok(_) => {
@@ -163,7 +363,7 @@ pub unsafe extern "C" fn socket_client_connect(handle: *mut SocketClientHandle)
             start_event_loop(handle);
             SocketClientError::Success
         }
-        Err(_) => SocketClientError::ConnectionFailed,
+        Err(e) => SocketClientError::from_connect_error(&e),
     }
 }
 
@@ -183,18 +383,85 @@ pub unsafe extern "C" fn socket_client_disconnect(handle: *mut SocketClientHandl
     });
 }
 
-/// 检查是否已连接
+/// 获取连接状态
+///
+/// 返回 `StatusConnected` / `StatusReconnecting` / `StatusDisconnected`（断开为 0，已连接为 1）
 ///
 /// # Safety
 /// - `handle` 必须是有效句柄
 #[no_mangle]
-pub unsafe extern "C" fn socket_client_is_connected(handle: *const SocketClientHandle) -> bool {
+pub unsafe extern "C" fn socket_client_is_connected(
+    handle: *const SocketClientHandle,
+) -> SocketConnectionStatus {
     if handle.is_null() {
-        return false;
+        return SocketConnectionStatus::StatusDisconnected;
     }
 
     let handle = &*handle;
-    handle.client.is_connected()
+    if handle.client.is_connected() {
+        SocketConnectionStatus::StatusConnected
+    } else if handle.is_reconnecting() {
+        SocketConnectionStatus::StatusReconnecting
+    } else {
+        SocketConnectionStatus::StatusDisconnected
+    }
+}
+
+/// 探测 Server 是否在响应
+///
+/// 发送 `daemon:ping` 并在 `timeout_ms` 内等待 Server 的 pong（ack），`timeout_ms` 为 0 时使用 5 秒。
+/// 成功时往返时间（毫秒）写入 `out_latency_ms`。
+/// 未连接返回 `NotConnected`，没有响应返回 `AckTimeout`。
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+/// - `out_latency_ms` 可为 null（不需要延迟时）
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_ping(
+    handle: *mut SocketClientHandle,
+    timeout_ms: u64,
+    out_latency_ms: *mut u64,
+) -> SocketClientError {
+    if handle.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    let handle = &*handle;
+    let timeout = if timeout_ms == 0 {
+        PING_TIMEOUT
+    } else {
+        std::time::Duration::from_millis(timeout_ms)
+    };
+    match handle.runtime.block_on(handle.client.ping(timeout)) {
+        Ok(latency) => {
+            if !out_latency_ms.is_null() {
+                *out_latency_ms = latency.as_millis() as u64;
+            }
+            SocketClientError::Success
+        }
+        Err(SocketError::NotConnected) => SocketClientError::NotConnected,
+        Err(SocketError::AckTimeout) => SocketClientError::AckTimeout,
+        Err(_) => SocketClientError::EmitFailed,
+    }
+}
+
+/// 获取当前连接状态名
+///
+/// 返回 `disconnected` / `connecting` / `authenticating` / `ready` / `reconnecting` / `disconnecting`，
+/// 调用者需要使用 `socket_client_free_string` 释放返回的字符串
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_get_state(handle: *const SocketClientHandle) -> *mut c_char {
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let handle = &*handle;
+    CString::new(handle.client.state().as_str())
+        .map(|s| s.into_raw())
+        .unwrap_or(std::ptr::null_mut())
 }
 
 // ==================== 事件回调 ====================
@@ -224,6 +491,77 @@ pub unsafe extern "C" fn socket_client_set_event_callback(
     });
 }
 
+/// 为指定事件添加处理器（连接前后均可调用）
+///
+/// 返回处理器 ID（用于移除），参数无效时返回 0。
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+/// - `event` 必须是有效的 UTF-8 C 字符串
+/// - `callback` 在处理器移除前必须有效
+/// - `user_data` 可为 null
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_add_event_handler(
+    handle: *mut SocketClientHandle,
+    event: *const c_char,
+    callback: EventCallbackFn,
+    user_data: *mut c_void,
+) -> u64 {
+    if handle.is_null() || event.is_null() {
+        return 0;
+    }
+    let Ok(event_str) = CStr::from_ptr(event).to_str() else {
+        return 0;
+    };
+
+    let handle = &*handle;
+    let cb = EventCallback { callback, user_data };
+    let event_name = event_str.to_string();
+    let id = handle.client.add_event_handler(
+        event_str,
+        Arc::new(move |data: serde_json::Value| {
+            // 整体捕获 cb（单独捕获 user_data 字段不满足 Send）
+            let cb = &cb;
+            if let (Ok(event_c), Ok(data_c)) = (
+                CString::new(event_name.as_str()),
+                CString::new(data.to_string()),
+            ) {
+                (cb.callback)(event_c.as_ptr(), data_c.as_ptr(), cb.user_data);
+            }
+        }),
+    );
+    id.as_u64()
+}
+
+/// 移除事件处理器
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+/// - `event` 必须是有效的 UTF-8 C 字符串
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_remove_event_handler(
+    handle: *mut SocketClientHandle,
+    event: *const c_char,
+    handler_id: u64,
+) -> SocketClientError {
+    if handle.is_null() || event.is_null() {
+        return SocketClientError::NullPointer;
+    }
+    let Ok(event_str) = CStr::from_ptr(event).to_str() else {
+        return SocketClientError::InvalidUtf8;
+    };
+
+    let handle = &*handle;
+    if handle
+        .client
+        .remove_event_handler(event_str, HandlerId::from_u64(handler_id))
+    {
+        SocketClientError::Success
+    } else {
+        SocketClientError::InvalidArgument
+    }
+}
+
 /// 启动事件接收循环
 ///
 /// 修复：
@@ -280,15 +618,20 @@ fn start_event_loop(handle: &SocketClientHandle) {
 fn start_reconnect_loop(handle: &SocketClientHandle) {
     let client = handle.client.clone();
     let callback_holder = handle.event_callback.clone();
+    let reconnect_loop_holder = handle.reconnect_loop_handle.clone();
+    let reconnecting = handle.reconnecting.clone();
 
-    handle.runtime.spawn(async move {
+    let join_handle = handle.runtime.spawn(async move {
         loop {
             // 等待重连信号
             if client.wait_for_reconnect().await {
                 eprintln!("[SocketClient FFI] Received reconnect signal, attempting reconnect...");
 
                 // 尝试重连
-                match client.reconnect().await {
+                reconnecting.store(true, Ordering::SeqCst);
+                let result = client.reconnect_with_policy().await;
+                reconnecting.store(false, Ordering::SeqCst);
+                match result {
                     Ok(_) => {
                         eprintln!("[SocketClient FFI] Reconnect successful");
                         // 通知 Swift 层已重连
@@ -322,6 +665,14 @@ fn start_reconnect_loop(handle: &SocketClientHandle) {
             }
         }
     });
+
+    // 存储 JoinHandle，并停止旧的重连循环（如果存在）
+    handle.runtime.block_on(async {
+        let mut guard = reconnect_loop_holder.write().await;
+        if let Some(old_handle) = guard.replace(join_handle) {
+            old_handle.abort();
+        }
+    });
 }
 
 // ==================== 上行事件 ====================
@@ -364,11 +715,62 @@ pub unsafe extern "C" fn socket_client_emit(
     }
 }
 
+/// 合并发送一批事件
+///
+/// `events_json` 为 `[{"event": "...", "data": {...}}, ...]`，按顺序打包为 `daemon:batch` 信封发送
+/// （过大时自动拆分），批次之间不会交错。遇到第一个发送失败的信封即停止并返回其错误码。
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+/// - `events_json` 必须是有效的 UTF-8 C 字符串
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_emit_batch(
+    handle: *mut SocketClientHandle,
+    events_json: *const c_char,
+) -> SocketClientError {
+    if handle.is_null() || events_json.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let handle = &*handle;
+        let json_str = CStr::from_ptr(events_json)
+            .to_str()
+            .map_err(|_| SocketClientError::InvalidUtf8)?;
+        let items: Vec<serde_json::Value> =
+            serde_json::from_str(json_str).map_err(|_| SocketClientError::InvalidArgument)?;
+
+        let events = items
+            .into_iter()
+            .map(|mut item| {
+                let event = item
+                    .get("event")
+                    .and_then(|v| v.as_str())
+                    .ok_or(SocketClientError::InvalidArgument)?
+                    .to_string();
+                let data = item.get_mut("data").map(serde_json::Value::take).unwrap_or_default();
+                Ok((event, data))
+            })
+            .collect::<Result<Vec<_>, SocketClientError>>()?;
+
+        handle.runtime.block_on(async {
+            handle.client.emit_batch(events).await
+        }).map_err(|_| SocketClientError::EmitFailed)
+    }));
+
+    match result {
+        Ok(Ok(_)) => SocketClientError::Success,
+        Ok(Err(e)) => e,
+        Err(_) => SocketClientError::Unknown,
+    }
+}
+
 /// 注册 daemon
 ///
 /// # Safety
 /// - `handle` 必须是有效句柄
-/// - `hostname`, `platform`, `version` 必须是有效的 UTF-8 C 字符串
+/// - `hostname`, `version` 必须是有效的 UTF-8 C 字符串
+/// - `platform` 可为 null，此时自动检测当前平台（macOS 上报为 "darwin"）
 #[no_mangle]
 pub unsafe extern "C" fn socket_client_register(
     handle: *mut SocketClientHandle,
@@ -376,7 +778,7 @@ pub unsafe extern "C" fn socket_client_register(
     platform: *const c_char,
     version: *const c_char,
 ) -> SocketClientError {
-    if handle.is_null() || hostname.is_null() || platform.is_null() || version.is_null() {
+    if handle.is_null() || hostname.is_null() || version.is_null() {
         return SocketClientError::NullPointer;
     }
 
@@ -385,9 +787,13 @@ pub unsafe extern "C" fn socket_client_register(
         let hostname_str = CStr::from_ptr(hostname)
             .to_str()
             .map_err(|_| SocketClientError::InvalidUtf8)?;
-        let platform_str = CStr::from_ptr(platform)
-            .to_str()
-            .map_err(|_| SocketClientError::InvalidUtf8)?;
+        let platform_str = if platform.is_null() {
+            socket_client::current_platform()
+        } else {
+            CStr::from_ptr(platform)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?
+        };
         let version_str = CStr::from_ptr(version)
             .to_str()
             .map_err(|_| SocketClientError::InvalidUtf8)?;
@@ -570,7 +976,77 @@ pub unsafe extern "C" fn socket_client_report_session_messages(
     has_more: bool,
     request_id: *const c_char,
 ) -> SocketClientError {
-    if handle.is_null() || session_id.is_null() || project_path.is_null() || messages_json.is_null() {
+    if messages_json.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    report_session_messages_with(
+        handle,
+        session_id,
+        project_path,
+        total,
+        has_more,
+        request_id,
+        || {
+            let messages_str = CStr::from_ptr(messages_json)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?;
+            serde_json::from_str(messages_str).map_err(|_| SocketClientError::InvalidUtf8)
+        },
+    )
+}
+
+/// 上报会话消息（MessagePack 编码）
+///
+/// 大批量消息避免在 FFI 边界构造和解析巨大的 C 字符串。
+/// `msgpack_data` 为消息数组的 MessagePack 编码，发送前转换为 JSON。
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+/// - `session_id`, `project_path` 必须是有效字符串
+/// - `msgpack_data` 必须指向至少 `data_len` 字节的有效内存
+/// - `request_id` 可为 null
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn socket_client_report_session_messages_msgpack(
+    handle: *mut SocketClientHandle,
+    session_id: *const c_char,
+    project_path: *const c_char,
+    msgpack_data: *const u8,
+    data_len: usize,
+    total: usize,
+    has_more: bool,
+    request_id: *const c_char,
+) -> SocketClientError {
+    if msgpack_data.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    report_session_messages_with(
+        handle,
+        session_id,
+        project_path,
+        total,
+        has_more,
+        request_id,
+        || {
+            let bytes = std::slice::from_raw_parts(msgpack_data, data_len);
+            rmp_serde::from_slice(bytes).map_err(|_| SocketClientError::InvalidArgument)
+        },
+    )
+}
+
+/// 上报会话消息的公共部分（`decode` 负责解码消息数组）
+unsafe fn report_session_messages_with(
+    handle: *mut SocketClientHandle,
+    session_id: *const c_char,
+    project_path: *const c_char,
+    total: usize,
+    has_more: bool,
+    request_id: *const c_char,
+    decode: impl FnOnce() -> Result<Vec<serde_json::Value>, SocketClientError>,
+) -> SocketClientError {
+    if handle.is_null() || session_id.is_null() || project_path.is_null() {
         return SocketClientError::NullPointer;
     }
 
@@ -584,11 +1060,7 @@ pub unsafe extern "C" fn socket_client_report_session_messages(
             .to_str()
             .map_err(|_| SocketClientError::InvalidUtf8)?
             .to_string();
-        let messages_str = CStr::from_ptr(messages_json)
-            .to_str()
-            .map_err(|_| SocketClientError::InvalidUtf8)?;
-        let messages: Vec<serde_json::Value> = serde_json::from_str(messages_str)
-            .map_err(|_| SocketClientError::InvalidUtf8)?;
+        let messages = decode()?;
 
         let req_id = if request_id.is_null() {
             None
@@ -729,6 +1201,8 @@ pub unsafe extern "C" fn socket_client_notify_session_list_update(
 
 /// 发送会话创建结果
 ///
+/// ETerm 只创建新会话，结果中 `resumed` 固定为 false。
+///
 /// # Safety
 /// - `handle` 必须是有效句柄
 /// - `request_id` 必须是有效字符串
@@ -786,7 +1260,10 @@ pub unsafe extern "C" fn socket_client_send_session_created_result(
         };
 
         handle.runtime.block_on(async {
-            handle.client.send_session_created_result(req_id, success, sid, encoded, transcript, err).await
+            handle
+                .client
+                .send_session_created_result(req_id, success, sid, encoded, transcript, err, false)
+                .await
         }).map_err(|_| SocketClientError::EmitFailed)
     }));
 
@@ -909,13 +1386,28 @@ pub unsafe extern "C" fn socket_client_free_string(s: *mut c_char) {
 
 // ==================== Redis 服务发现 ====================
 
+/// `redis_port` 为 0 时使用的默认端口
+const DEFAULT_REDIS_PORT: u16 = 6379;
+
+/// device_id 最大长度
+const MAX_DEVICE_ID_LEN: usize = 64;
+
+/// device_id 会拼进 Redis key，只允许字母数字和 '-'
+fn is_valid_device_id(device_id: &str) -> bool {
+    !device_id.is_empty()
+        && device_id.len() <= MAX_DEVICE_ID_LEN
+        && device_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
 /// 创建带 Redis 配置的 Socket 客户端
 ///
 /// # Safety
 /// - `url` 必须是有效的 UTF-8 C 字符串
 /// - `namespace` 可为 null
 /// - `redis_host` 可为 null（不启用 Redis）
-/// - `device_id`, `device_name`, `platform`, `version` 启用 Redis 时必填
+/// - `redis_port` 为 0 时使用默认端口 6379
+/// - `device_id`, `device_name`, `platform`, `version` 启用 Redis 时必填，
+///   `device_id` 只能包含字母数字和 '-'（最长 64），否则返回 `InvalidArgument`
 /// - 返回的句柄需要通过 `socket_client_destroy` 释放
 #[no_mangle]
 pub unsafe extern "C" fn socket_client_create_with_redis(
@@ -975,9 +1467,10 @@ pub unsafe extern "C" fn socket_client_create_with_redis(
 
             Some(ServiceRegistryConfig {
                 host,
-                port: redis_port,
+                port: if redis_port == 0 { DEFAULT_REDIS_PORT } else { redis_port },
                 password,
                 key_prefix: "vlaude:".to_string(),
+                skip_latency_measurement: false,
             })
         } else {
             None
@@ -993,11 +1486,15 @@ pub unsafe extern "C" fn socket_client_create_with_redis(
                 return Err(SocketClientError::NullPointer);
             }
 
+            let device_id = CStr::from_ptr(device_id)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?;
+            if !is_valid_device_id(device_id) {
+                return Err(SocketClientError::InvalidArgument);
+            }
+
             Some(DaemonRegistration {
-                device_id: CStr::from_ptr(device_id)
-                    .to_str()
-                    .map_err(|_| SocketClientError::InvalidUtf8)?
-                    .to_string(),
+                device_id: device_id.to_string(),
                 device_name: CStr::from_ptr(device_name)
                     .to_str()
                     .map_err(|_| SocketClientError::InvalidUtf8)?
@@ -1018,20 +1515,26 @@ pub unsafe extern "C" fn socket_client_create_with_redis(
 
         let config = SocketConfig {
             url: url_str.to_string(),
-            namespace: namespace_str,
+            namespace: namespace_str.into(),
             tls,
             redis: redis_config,
             daemon_info,
+            auth: None,
+            ack_timeout: AckTimeoutPolicy::default(),
+            reconnect: ReconnectPolicy::default(),
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
         };
 
-        let runtime = Runtime::new().map_err(|_| SocketClientError::RuntimeError)?;
+        let runtime = acquire_runtime()?;
         let client = SocketClient::new(config);
 
         Ok(SocketClientHandle {
             client: Arc::new(client),
-            runtime: Arc::new(runtime),
+            runtime,
             event_callback: Arc::new(RwLock::new(None)),
             event_loop_handle: Arc::new(RwLock::new(None)),
+            reconnect_loop_handle: Arc::new(RwLock::new(None)),
+            reconnecting: Arc::new(AtomicBool::new(false)),
         })
     }));
 
@@ -1081,11 +1584,7 @@ pub unsafe extern "C" fn socket_client_connect_with_discovery(
                 "[SocketClient FFI] Initial connection failed: {:?}, waiting for server online...",
                 e
             );
-            if e.to_string().contains("Registry") {
-                SocketClientError::RegistryError
-            } else {
-                SocketClientError::ConnectionFailed
-            }
+            SocketClientError::from_connect_error(&e)
         }
     }
 }
@@ -1153,3 +1652,189 @@ pub unsafe extern "C" fn socket_client_update_sessions(
         Err(_) => SocketClientError::Unknown,
     }
 }
+
+/// 获取所有 Daemon 当前打开的 Session
+///
+/// 返回 JSON 对象 `{"<sessionId>": "<deviceId>"}`，未启用 Redis 时为 `{}`，出错时返回 null。
+/// 调用者需要使用 `socket_client_free_string` 释放返回的字符串
+///
+/// # Safety
+/// - `handle` 必须是有效句柄
+#[no_mangle]
+pub unsafe extern "C" fn socket_client_list_daemon_sessions(
+    handle: *mut SocketClientHandle,
+) -> *mut c_char {
+    if handle.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let handle = &*handle;
+        let sessions = handle
+            .runtime
+            .block_on(async { handle.client.list_daemon_sessions().await })
+            .ok()?;
+        let json = serde_json::to_string(&sessions).ok()?;
+        CString::new(json).ok()
+    }));
+
+    match result {
+        Ok(Some(json)) => json.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+// ==================== 连接池 ====================
+
+/// 连接池不透明句柄
+pub struct SocketPoolHandle {
+    pool: SocketClientPool,
+    runtime: Arc<Runtime>,
+    /// 命名空间 → 客户端句柄（由连接池持有）
+    handles: Mutex<HashMap<String, Box<SocketClientHandle>>>,
+}
+
+/// 创建 Socket 连接池
+///
+/// # Safety
+/// - `url` 必须是有效的 UTF-8 C 字符串（如 "https://localhost:10005"）
+/// - `policies_json` 可为 null 使用默认重连策略；非 null 时为 JSON 对象，
+///   `default` 为默认策略，其余键为命名空间，如
+///   `{"default": {"maxAttempts": 5}, "/daemon": {"maxAttempts": null, "baseDelayMs": 200}}`
+/// - 返回的句柄需要通过 `socket_pool_destroy` 释放
+#[no_mangle]
+pub unsafe extern "C" fn socket_pool_create(
+    url: *const c_char,
+    policies_json: *const c_char,
+    out_pool: *mut *mut SocketPoolHandle,
+) -> SocketClientError {
+    if url.is_null() || out_pool.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let url_str = CStr::from_ptr(url)
+            .to_str()
+            .map_err(|_| SocketClientError::InvalidUtf8)?;
+
+        let mut policies: HashMap<String, ReconnectPolicy> = if policies_json.is_null() {
+            HashMap::new()
+        } else {
+            let json_str = CStr::from_ptr(policies_json)
+                .to_str()
+                .map_err(|_| SocketClientError::InvalidUtf8)?;
+            serde_json::from_str(json_str).map_err(|_| SocketClientError::InvalidArgument)?
+        };
+
+        // 创建 TLS 配置（开发模式：跳过证书验证）
+        let tls = TlsConfig {
+            danger_accept_invalid_certs: true,
+            ..Default::default()
+        };
+
+        let config = SocketConfig {
+            url: url_str.to_string(),
+            namespace: NamespaceConfig::Static("/daemon".to_string()),
+            tls,
+            redis: None,
+            daemon_info: None,
+            auth: None,
+            ack_timeout: AckTimeoutPolicy::default(),
+            reconnect: policies.remove("default").unwrap_or_default(),
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+        };
+
+        let pool = policies
+            .into_iter()
+            .fold(SocketClientPool::new(config), |pool, (namespace, policy)| {
+                pool.with_policy(namespace, policy)
+            });
+
+        Ok(SocketPoolHandle {
+            pool,
+            runtime: acquire_runtime()?,
+            handles: Mutex::new(HashMap::new()),
+        })
+    }));
+
+    match result {
+        Ok(Ok(pool)) => {
+            *out_pool = Box::into_raw(Box::new(pool));
+            SocketClientError::Success
+        }
+        Ok(Err(e)) => e,
+        Err(_) => SocketClientError::Unknown,
+    }
+}
+
+/// 获取连接池中命名空间对应的客户端句柄（不存在时创建，不会自动连接）
+///
+/// # Safety
+/// - `pool` 必须是 `socket_pool_create` 返回的有效句柄
+/// - `namespace` 必须是有效的 UTF-8 C 字符串（如 "/daemon"）
+/// - 返回的句柄归连接池所有，在 `socket_pool_destroy` 之前有效，
+///   不能传给 `socket_client_destroy`
+#[no_mangle]
+pub unsafe extern "C" fn socket_pool_get_socket(
+    pool: *mut SocketPoolHandle,
+    namespace: *const c_char,
+    out_handle: *mut *mut SocketClientHandle,
+) -> SocketClientError {
+    if pool.is_null() || namespace.is_null() || out_handle.is_null() {
+        return SocketClientError::NullPointer;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let pool = &*pool;
+        let namespace_str = CStr::from_ptr(namespace)
+            .to_str()
+            .map_err(|_| SocketClientError::InvalidUtf8)?;
+
+        let mut handles = pool
+            .handles
+            .lock()
+            .map_err(|_| SocketClientError::RuntimeError)?;
+
+        let handle = handles.entry(namespace_str.to_string()).or_insert_with(|| {
+            Box::new(SocketClientHandle {
+                client: pool.pool.get_or_create(namespace_str),
+                runtime: pool.runtime.clone(),
+                event_callback: Arc::new(RwLock::new(None)),
+                event_loop_handle: Arc::new(RwLock::new(None)),
+                reconnect_loop_handle: Arc::new(RwLock::new(None)),
+                reconnecting: Arc::new(AtomicBool::new(false)),
+            })
+        });
+
+        Ok(&mut **handle as *mut SocketClientHandle)
+    }));
+
+    match result {
+        Ok(Ok(handle)) => {
+            *out_handle = handle;
+            SocketClientError::Success
+        }
+        Ok(Err(e)) => e,
+        Err(_) => SocketClientError::Unknown,
+    }
+}
+
+/// 销毁连接池（断开并释放池中所有客户端句柄）
+///
+/// # Safety
+/// - `pool` 必须是 `socket_pool_create` 返回的有效句柄
+/// - 调用后连接池及 `socket_pool_get_socket` 返回的句柄都不再有效
+#[no_mangle]
+pub unsafe extern "C" fn socket_pool_destroy(pool: *mut SocketPoolHandle) {
+    if !pool.is_null() {
+        let pool = Box::from_raw(pool);
+        let handles = pool
+            .handles
+            .lock()
+            .map(|mut handles| std::mem::take(&mut *handles))
+            .unwrap_or_default();
+        for handle in handles.values() {
+            shutdown_handle(handle);
+        }
+    }
+}
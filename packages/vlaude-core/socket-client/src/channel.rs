@@ -0,0 +1,183 @@
+//! 虚拟通道
+//!
+//! 在同一个物理 Socket.IO 连接上复用多个逻辑会话（例如同一进程同时作为 daemon 和 viewer）。
+//! 虚拟通道发送和接收的事件名都带 `{logical_id}::` 前缀，由 Server 负责去掉前缀。
+
+use crate::client::SharedClient;
+use crate::error::SocketError;
+use crate::middleware::EmitMiddleware;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// 逻辑 ID 与事件名之间的分隔符（与 `server:xxx` 这类事件名区分）
+pub const CHANNEL_SEPARATOR: &str = "::";
+
+/// 虚拟通道路由表（logical_id → 事件发送端）
+pub(crate) type ChannelRoutes = Arc<Mutex<HashMap<String, mpsc::Sender<(String, Value)>>>>;
+
+/// 把带前缀的下行事件转发到对应的虚拟通道
+///
+/// 返回 false 表示事件不属于任何虚拟通道。
+pub(crate) async fn route_event(routes: &ChannelRoutes, event: &str, data: Value) -> bool {
+    let Some((logical_id, name)) = event.split_once(CHANNEL_SEPARATOR) else {
+        return false;
+    };
+    let tx = {
+        let routes = routes.lock().unwrap_or_else(|e| e.into_inner());
+        routes.get(logical_id).cloned()
+    };
+    match tx {
+        Some(tx) => {
+            let _ = tx.send((name.to_string(), data)).await;
+            true
+        }
+        None => false,
+    }
+}
+
+/// 共享物理连接的逻辑通道
+///
+/// 由 `SocketClient::create_virtual_channel` 创建，drop 时自动注销。
+pub struct VirtualChannel {
+    logical_id: String,
+    client: SharedClient,
+    middleware: Option<Arc<dyn EmitMiddleware>>,
+    routes: ChannelRoutes,
+    tx: mpsc::Sender<(String, Value)>,
+    rx: tokio::sync::Mutex<mpsc::Receiver<(String, Value)>>,
+}
+
+impl VirtualChannel {
+    pub(crate) fn new(
+        logical_id: &str,
+        client: SharedClient,
+        middleware: Option<Arc<dyn EmitMiddleware>>,
+        routes: ChannelRoutes,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(100);
+        {
+            let mut routes = routes.lock().unwrap_or_else(|e| e.into_inner());
+            routes.insert(logical_id.to_string(), tx.clone());
+        }
+        Self {
+            logical_id: logical_id.to_string(),
+            client,
+            middleware,
+            routes,
+            tx,
+            rx: tokio::sync::Mutex::new(rx),
+        }
+    }
+
+    /// 逻辑 ID
+    pub fn logical_id(&self) -> &str {
+        &self.logical_id
+    }
+
+    /// 加上通道前缀后的事件名
+    pub fn prefixed(&self, event: &str) -> String {
+        format!("{}{}{}", self.logical_id, CHANNEL_SEPARATOR, event)
+    }
+
+    /// 发送事件（事件名自动加前缀）
+    pub async fn emit(&self, event: &str, data: Value) -> Result<(), SocketError> {
+        let event = self.prefixed(event);
+        let mut data = data;
+        if let Some(middleware) = &self.middleware {
+            middleware.before_emit(&event, &mut data);
+        }
+
+        let result = match self.client.load_full() {
+            Some(client) => client
+                .emit(event.as_str(), data)
+                .await
+                .map_err(|e| SocketError::EmitFailed(e.to_string())),
+            None => Err(SocketError::NotConnected),
+        };
+        debug!("[VirtualChannel] Emitted {}: {:?}", event, result.is_ok());
+
+        if let Some(middleware) = &self.middleware {
+            middleware.after_emit(&event, &result);
+        }
+        result
+    }
+
+    /// 接收本通道的下一个事件（事件名已去掉前缀）
+    pub async fn recv_event(&self) -> Option<(String, Value)> {
+        self.rx.lock().await.recv().await
+    }
+
+    /// 接收事件（带超时）
+    pub async fn recv_event_timeout(&self, timeout: std::time::Duration) -> Option<(String, Value)> {
+        let mut rx = self.rx.lock().await;
+        tokio::time::timeout(timeout, rx.recv()).await.ok().flatten()
+    }
+}
+
+impl Drop for VirtualChannel {
+    fn drop(&mut self) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        // 同一逻辑 ID 被重新创建时不要注销新通道
+        if routes
+            .get(&self.logical_id)
+            .is_some_and(|tx| tx.same_channel(&self.tx))
+        {
+            routes.remove(&self.logical_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn channel(logical_id: &str, routes: &ChannelRoutes) -> VirtualChannel {
+        VirtualChannel::new(logical_id, SharedClient::default(), None, routes.clone())
+    }
+
+    #[tokio::test]
+    async fn test_route_event_strips_prefix() {
+        let routes = ChannelRoutes::default();
+        let viewer = channel("viewer", &routes);
+
+        assert_eq!(viewer.prefixed("session:subscribe"), "viewer::session:subscribe");
+        assert!(route_event(&routes, "viewer::server:newMessage", json!({"id": 1})).await);
+        assert!(!route_event(&routes, "server:newMessage", json!({})).await);
+        assert!(!route_event(&routes, "other::server:newMessage", json!({})).await);
+
+        let (event, data) = viewer
+            .recv_event_timeout(std::time::Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(event, "server:newMessage");
+        assert_eq!(data["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_emit_requires_connection() {
+        let routes = ChannelRoutes::default();
+        let viewer = channel("viewer", &routes);
+        assert!(matches!(
+            viewer.emit("ping", json!({})).await,
+            Err(SocketError::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn test_drop_unregisters_channel() {
+        let routes = ChannelRoutes::default();
+        let old = channel("viewer", &routes);
+        let new = channel("viewer", &routes);
+
+        // 旧通道 drop 不影响同名的新通道
+        drop(old);
+        assert!(routes.lock().unwrap().contains_key("viewer"));
+
+        drop(new);
+        assert!(routes.lock().unwrap().is_empty());
+    }
+}
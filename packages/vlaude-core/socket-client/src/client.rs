@@ -1,20 +1,27 @@
 //! Socket.IO 客户端实现
 
-use crate::error::SocketError;
+use crate::channel::{self, ChannelRoutes, VirtualChannel};
+use crate::dispatcher::{EventDispatcher, EventHandler, HandlerId};
+use crate::error::{ConnectionError, SocketError};
 use crate::events::*;
+use crate::middleware::EmitMiddleware;
+use arc_swap::ArcSwapOption;
 use crate::registry::{DaemonInfo, ServiceEventType, ServiceRegistry, ServiceRegistryConfig, SessionInfo};
 use anyhow::Result;
+use rand::Rng;
 use native_tls::{Certificate, Identity, TlsConnector};
 use rust_socketio::{
     asynchronous::{Client, ClientBuilder},
-    Payload,
+    Event, Payload,
 };
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
 use tracing::{debug, error, info, warn};
 
 /// TLS 配置
@@ -32,21 +39,349 @@ pub struct TlsConfig {
     pub danger_accept_invalid_certs: bool,
 }
 
+impl TlsConfig {
+    /// 构建 TLS 连接器（支持 mTLS）
+    ///
+    /// 未配置 TLS 时返回 None。
+    pub fn build_connector(&self) -> Result<Option<TlsConnector>, SocketError> {
+        // 如果没有 TLS 配置，返回 None
+        if self.ca_cert_path.is_none()
+            && self.client_cert_path.is_none()
+            && !self.danger_accept_invalid_certs
+        {
+            return Ok(None);
+        }
+
+        let mut builder = native_tls::TlsConnector::builder();
+
+        // 加载 CA 证书
+        if let Some(ca_path) = &self.ca_cert_path {
+            info!("Loading CA certificate from {:?}", ca_path);
+            let ca_pem = fs::read(ca_path).map_err(|e| {
+                SocketError::TlsError(format!("Failed to read CA cert: {}", e))
+            })?;
+            let ca_cert = Certificate::from_pem(&ca_pem).map_err(|e| {
+                SocketError::TlsError(format!("Failed to parse CA cert: {}", e))
+            })?;
+            builder.add_root_certificate(ca_cert);
+        }
+
+        // 加载客户端证书（mTLS）
+        if let Some(cert_path) = &self.client_cert_path {
+            info!("Loading client certificate from {:?}", cert_path);
+            let cert_data = fs::read(cert_path).map_err(|e| {
+                SocketError::TlsError(format!("Failed to read client cert: {}", e))
+            })?;
+
+            // 检查是否是 P12 格式
+            let is_p12 = cert_path
+                .extension()
+                .map(|ext| ext == "p12" || ext == "pfx")
+                .unwrap_or(false);
+
+            let identity = if is_p12 {
+                // PKCS#12 格式
+                let password = self.client_p12_password.as_deref().unwrap_or("");
+                Identity::from_pkcs12(&cert_data, password).map_err(|e| {
+                    SocketError::TlsError(format!("Failed to parse PKCS#12: {}", e))
+                })?
+            } else {
+                // PEM 格式
+                let key_pem = if let Some(key_path) = &self.client_key_path {
+                    fs::read(key_path).map_err(|e| {
+                        SocketError::TlsError(format!("Failed to read client key: {}", e))
+                    })?
+                } else {
+                    return Err(SocketError::TlsError(
+                        "Client key path required for PEM format".into(),
+                    ));
+                };
+                Identity::from_pkcs8(&cert_data, &key_pem).map_err(|e| {
+                    SocketError::TlsError(format!("Failed to create identity from PEM: {}", e))
+                })?
+            };
+            builder.identity(identity);
+        }
+
+        // 开发模式：跳过证书验证
+        if self.danger_accept_invalid_certs {
+            warn!("TLS certificate verification disabled - FOR DEVELOPMENT ONLY");
+            builder.danger_accept_invalid_certs(true);
+        }
+
+        let connector = builder.build().map_err(|e| {
+            SocketError::TlsError(format!("Failed to build TLS connector: {}", e))
+        })?;
+
+        Ok(Some(connector))
+    }
+
+    /// 从环境变量读取 TLS 配置（用于 Docker 等通过环境变量注入证书的部署）
+    ///
+    /// 读取 `VLAUDE_CA_CERT` / `VLAUDE_CLIENT_CERT` / `VLAUDE_CLIENT_KEY` / `VLAUDE_P12_PASSWORD` /
+    /// `VLAUDE_INSECURE`，未设置或为空视为未配置。证书文件在此处检查是否存在且可读，
+    /// 错误信息包含对应的变量名。
+    pub fn from_env() -> Result<Self, SocketError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, SocketError> {
+        let var = |name: &str| var(name).filter(|v| !v.is_empty());
+        let path = |name: &str| -> Result<Option<PathBuf>, SocketError> {
+            let Some(value) = var(name) else {
+                return Ok(None);
+            };
+            let path = PathBuf::from(value);
+            let readable = fs::File::open(&path)
+                .and_then(|f| f.metadata())
+                .map(|m| m.is_file());
+            match readable {
+                Ok(true) => Ok(Some(path)),
+                Ok(false) => Err(SocketError::TlsError(format!(
+                    "{}: {:?} is not a file",
+                    name, path
+                ))),
+                Err(e) => Err(SocketError::TlsError(format!(
+                    "{}: cannot read {:?}: {}",
+                    name, path, e
+                ))),
+            }
+        };
+
+        let danger_accept_invalid_certs = match var("VLAUDE_INSECURE").as_deref() {
+            None => false,
+            Some(v) => match v.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => {
+                    return Err(SocketError::TlsError(format!(
+                        "VLAUDE_INSECURE: expected a boolean, got {:?}",
+                        v
+                    )))
+                }
+            },
+        };
+
+        Ok(Self {
+            ca_cert_path: path("VLAUDE_CA_CERT")?,
+            client_cert_path: path("VLAUDE_CLIENT_CERT")?,
+            client_key_path: path("VLAUDE_CLIENT_KEY")?,
+            client_p12_password: var("VLAUDE_P12_PASSWORD"),
+            danger_accept_invalid_certs,
+        })
+    }
+
+    /// 校验证书文件（存在、可读、格式正确）
+    ///
+    /// 用于在启动时尽早报错，而不是等到首次连接才失败。
+    pub fn validate(&self) -> Result<(), SocketError> {
+        self.build_connector().map(|_| ())
+    }
+}
+
+/// 命名空间解析器
+///
+/// 根据 Daemon 角色（主机名、平台）动态选择 Socket.IO 命名空间。
+pub trait NamespaceResolver {
+    fn resolve(&self, hostname: &str, platform: &str) -> String;
+}
+
+/// 基于平台的命名空间解析器
+///
+/// iOS 使用 `/mobile`，其它桌面平台使用 `/daemon`。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlatformBasedResolver;
+
+impl NamespaceResolver for PlatformBasedResolver {
+    fn resolve(&self, _hostname: &str, platform: &str) -> String {
+        match platform {
+            "ios" => "/mobile".to_string(),
+            _ => "/daemon".to_string(),
+        }
+    }
+}
+
+/// 命名空间配置
+///
+/// Dynamic 使用 Arc 持有解析器，保证 SocketConfig 可 Clone。
+#[derive(Clone)]
+pub enum NamespaceConfig {
+    /// 固定命名空间
+    Static(String),
+    /// 连接时通过解析器动态选择
+    Dynamic(Arc<dyn NamespaceResolver + Send + Sync>),
+}
+
+impl NamespaceConfig {
+    /// 解析出最终使用的命名空间
+    pub fn resolve(&self, hostname: &str, platform: &str) -> String {
+        match self {
+            NamespaceConfig::Static(namespace) => namespace.clone(),
+            NamespaceConfig::Dynamic(resolver) => resolver.resolve(hostname, platform),
+        }
+    }
+
+    /// 按名称选择内置解析器（目前支持 "platform"）
+    pub fn from_resolver_name(name: &str) -> Option<Self> {
+        match name {
+            "platform" => Some(NamespaceConfig::Dynamic(Arc::new(PlatformBasedResolver))),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for NamespaceConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NamespaceConfig::Static(namespace) => f.debug_tuple("Static").field(namespace).finish(),
+            NamespaceConfig::Dynamic(_) => f.write_str("Dynamic(..)"),
+        }
+    }
+}
+
+impl From<&str> for NamespaceConfig {
+    fn from(namespace: &str) -> Self {
+        NamespaceConfig::Static(namespace.to_string())
+    }
+}
+
+impl From<String> for NamespaceConfig {
+    fn from(namespace: String) -> Self {
+        NamespaceConfig::Static(namespace)
+    }
+}
+
+/// Ack 超时策略（按事件配置）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AckTimeoutPolicy {
+    /// 未单独配置的事件使用的超时（秒）
+    pub default_secs: u64,
+    /// 事件名 → 超时（秒）
+    pub per_event: HashMap<String, u64>,
+}
+
+impl AckTimeoutPolicy {
+    /// 获取事件的 ack 超时
+    pub fn timeout_for(&self, event: &str) -> std::time::Duration {
+        let secs = self.per_event.get(event).copied().unwrap_or(self.default_secs);
+        std::time::Duration::from_secs(secs)
+    }
+}
+
+impl Default for AckTimeoutPolicy {
+    fn default() -> Self {
+        Self {
+            default_secs: 10,
+            per_event: HashMap::from([
+                ("daemon:register".to_string(), 10),
+                // 创建 Claude 会话耗时较长
+                ("daemon:sessionCreatedResult".to_string(), 30),
+                ("daemon:projectData".to_string(), 10),
+            ]),
+        }
+    }
+}
+
+/// 重连策略（指数退避 + 随机抖动）
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReconnectPolicy {
+    /// 最大尝试次数（None 表示不限次数）
+    pub max_attempts: Option<u32>,
+    /// 首次重试前的等待时间（毫秒，0 按 1 处理）
+    pub base_delay_ms: u64,
+    /// 单次等待上限（毫秒）
+    pub max_delay_ms: u64,
+    /// 随机抖动幅度（百分比，20 表示 ±20%），避免 Server 重启后所有客户端同时重连
+    pub jitter_percent: u32,
+}
+
+impl ReconnectPolicy {
+    /// 第 `attempt` 次失败后是否继续重试（attempt 从 1 开始）
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        match self.max_attempts {
+            Some(max) => attempt < max,
+            None => true,
+        }
+    }
+
+    /// 第 `attempt` 次失败后的等待时间（attempt 从 1 开始，已加抖动）
+    pub fn delay_for(&self, attempt: u32) -> std::time::Duration {
+        let jitter = self.jitter_percent.min(100) as i64;
+        let jitter = rand::thread_rng().gen_range(-jitter..=jitter);
+        Self::with_jitter(self.base_delay_for(attempt), jitter)
+    }
+
+    /// 第 `attempt` 次失败后的等待时间（未加抖动）
+    fn base_delay_for(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        // 初始等待为 0 时翻倍后仍为 0，会变成无间隔重试
+        let delay = self
+            .base_delay_ms
+            .max(1)
+            .saturating_mul(factor)
+            .min(self.max_delay_ms);
+        std::time::Duration::from_millis(delay)
+    }
+
+    /// 按百分比叠加抖动（`jitter_percent` 取值 -100 ~ 100）
+    fn with_jitter(delay: std::time::Duration, jitter_percent: i64) -> std::time::Duration {
+        delay.mul_f64((100 + jitter_percent).max(0) as f64 / 100.0)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Some(5),
+            base_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            jitter_percent: 20,
+        }
+    }
+}
+
+/// 事件投递保证级别
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QosLevel {
+    /// 最多一次（fire-and-forget）
+    AtMostOnce,
+    /// 至少一次：失败时重试，每次尝试前检查连接状态
+    AtLeastOnce { max_retries: u32 },
+    /// 恰好一次：同一 dedup_key 只发送一次（重连后清空）
+    ExactlyOnce { dedup_key: String },
+}
+
+/// AtLeastOnce 重试的初始退避时间
+const QOS_RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
 /// Socket 客户端配置
 #[derive(Debug, Clone)]
 pub struct SocketConfig {
     /// 服务器 URL（如果启用 Redis 发现，此值可被覆盖）
     pub url: String,
     /// 命名空间
-    pub namespace: String,
+    pub namespace: NamespaceConfig,
     /// TLS 配置
     pub tls: TlsConfig,
     /// Redis 配置（可选，启用后支持服务发现和状态注册）
     pub redis: Option<ServiceRegistryConfig>,
     /// Daemon 信息（启用 Redis 时必填，用于注册到 Redis）
     pub daemon_info: Option<DaemonRegistration>,
+    /// 握手认证数据（Socket.IO `auth`，由 Server 中间件校验）
+    pub auth: Option<Value>,
+    /// Ack 超时策略
+    pub ack_timeout: AckTimeoutPolicy,
+    /// 重连策略
+    pub reconnect: ReconnectPolicy,
+    /// `emit_batch` 单个信封的最大字节数（超过时自动拆分）
+    pub max_batch_bytes: usize,
 }
 
+/// `emit_batch` 单个信封的默认最大字节数
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 512 * 1024;
+
 /// Daemon 注册信息
 #[derive(Debug, Clone)]
 pub struct DaemonRegistration {
@@ -78,10 +413,361 @@ impl Default for SocketConfig {
 
         Self {
             url: format!("{}://{}:{}", protocol, host, port),
-            namespace: "/daemon".to_string(),
+            namespace: NamespaceConfig::Static("/daemon".to_string()),
             tls: TlsConfig::default(),
             redis: None,
             daemon_info: None,
+            // 从环境变量读取认证 token
+            auth: std::env::var("VLAUDE_AUTH_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty())
+                .map(|token| json!({ "token": token })),
+            ack_timeout: AckTimeoutPolicy::default(),
+            reconnect: ReconnectPolicy::default(),
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+        }
+    }
+}
+
+/// 连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// 未连接
+    Disconnected,
+    /// 正在建立 Socket 连接
+    Connecting,
+    /// 已连接，尚未完成注册
+    Authenticating,
+    /// 已注册，可以正常收发
+    Ready,
+    /// 正在重连
+    Reconnecting,
+    /// 正在断开
+    Disconnecting,
+}
+
+impl ConnectionState {
+    /// 状态名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Disconnected => "disconnected",
+            Self::Connecting => "connecting",
+            Self::Authenticating => "authenticating",
+            Self::Ready => "ready",
+            Self::Reconnecting => "reconnecting",
+            Self::Disconnecting => "disconnecting",
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 共享的连接状态
+type SharedConnectionState = Arc<std::sync::RwLock<ConnectionState>>;
+
+/// 切换连接状态（状态变化时记录日志）
+fn transition(state: &SharedConnectionState, next: ConnectionState) {
+    let mut current = state.write().unwrap_or_else(|e| e.into_inner());
+    if *current != next {
+        info!("Connection state: {} -> {}", *current, next);
+        *current = next;
+    }
+}
+
+/// 连接标志（原子标志 + watch 通知，状态变化时唤醒等待者）
+struct ConnectedFlag {
+    flag: AtomicBool,
+    tx: watch::Sender<bool>,
+}
+
+impl ConnectedFlag {
+    fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self {
+            flag: AtomicBool::new(false),
+            tx,
+        }
+    }
+
+    fn set(&self, connected: bool) {
+        self.flag.store(connected, Ordering::SeqCst);
+        self.tx.send_if_modified(|current| {
+            let changed = *current != connected;
+            *current = connected;
+            changed
+        });
+    }
+
+    fn get(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    fn subscribe(&self) -> watch::Receiver<bool> {
+        self.tx.subscribe()
+    }
+}
+
+/// 当前的 Socket.IO 客户端（无锁读取，并发 emit 互不阻塞）
+///
+/// `rust_socketio` 的 `Client` 内部状态都在 `Arc` 中，克隆后共享同一个连接。
+pub(crate) type SharedClient = Arc<ArcSwapOption<Client>>;
+
+/// 等待 ping 响应的默认超时时间
+pub const PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 保留的最近延迟样本数
+const LATENCY_SAMPLE_COUNT: usize = 10;
+
+/// 心跳 ping 超过该往返时间视为连接降级
+const DEGRADED_LATENCY: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// 往返延迟统计（最近 10 次成功的 ping）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub min: std::time::Duration,
+    pub max: std::time::Duration,
+    pub mean: std::time::Duration,
+    /// 样本数
+    pub samples: usize,
+}
+
+/// 最近的 ping 往返时间（环形缓冲）
+#[derive(Default)]
+struct LatencySamples(std::sync::Mutex<VecDeque<std::time::Duration>>);
+
+impl LatencySamples {
+    fn record(&self, latency: std::time::Duration) {
+        let mut samples = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == LATENCY_SAMPLE_COUNT {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    fn stats(&self) -> Option<LatencyStats> {
+        let samples = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let min = *samples.iter().min()?;
+        let max = *samples.iter().max()?;
+        let total: std::time::Duration = samples.iter().sum();
+        Some(LatencyStats {
+            min,
+            max,
+            mean: total / samples.len() as u32,
+            samples: samples.len(),
+        })
+    }
+}
+
+/// 序列化上行事件数据
+fn to_payload(data: impl serde::Serialize) -> Result<Value, SocketError> {
+    serde_json::to_value(data).map_err(|e| SocketError::SerializationError(e.to_string()))
+}
+
+/// 合并发送的事件名
+const BATCH_EVENT: &str = "daemon:batch";
+
+/// 服务器在 `daemon:register` ack 的 `capabilities` 中声明支持批量信封时使用的名称
+const BATCH_CAPABILITY: &str = "batch";
+
+/// 注册 ack 是否声明支持 `daemon:batch`（旧版服务器没有 `capabilities` 字段）
+fn ack_supports_batch(ack: &Value) -> bool {
+    ack.get("capabilities")
+        .and_then(|v| v.as_array())
+        .is_some_and(|caps| caps.iter().any(|c| c.as_str() == Some(BATCH_CAPABILITY)))
+}
+
+/// 发送单个事件的底层通道（`emit_batch` 经由它发送，测试中可替换为 mock）
+trait EmitTransport: Send + Sync {
+    fn send<'a>(&'a self, event: &'a str, data: Value) -> BoxFuture<'a, Result<(), SocketError>>;
+}
+
+/// 发送一批事件
+///
+/// `batch_supported` 为 true 时打包为 `daemon:batch` 信封并按 `max_batch_bytes` 拆分，
+/// 否则逐个发送原事件。遇到第一个错误即停止。
+async fn send_batched(
+    transport: &dyn EmitTransport,
+    middleware: Option<&Arc<dyn EmitMiddleware>>,
+    events: Vec<(String, Value)>,
+    max_batch_bytes: usize,
+    batch_supported: bool,
+) -> Result<(), SocketError> {
+    let mut batched = Vec::with_capacity(events.len());
+    for (event, mut data) in events {
+        if let Some(middleware) = middleware {
+            middleware.before_emit(&event, &mut data);
+        }
+        batched.push(BatchedEvent { event, data });
+    }
+
+    if !batch_supported {
+        for BatchedEvent { event, data } in batched {
+            let result = transport.send(&event, data).await;
+            if let Some(middleware) = middleware {
+                middleware.after_emit(&event, &result);
+            }
+            result?;
+        }
+        return Ok(());
+    }
+
+    for batch in split_batch(batched, max_batch_bytes)? {
+        let names: Vec<String> = batch.iter().map(|e| e.event.clone()).collect();
+        debug!("Emitting batch of {} events", names.len());
+        let result = transport
+            .send(BATCH_EVENT, to_payload(BatchPayload { events: batch })?)
+            .await;
+
+        if let Some(middleware) = middleware {
+            for name in &names {
+                middleware.after_emit(name, &result);
+            }
+        }
+        result?;
+    }
+    Ok(())
+}
+
+/// 按序列化大小把事件拆分为多个信封
+///
+/// 每个信封不超过 `max_bytes`；单个事件本身超过上限时独占一个信封。
+fn split_batch(
+    events: Vec<BatchedEvent>,
+    max_bytes: usize,
+) -> Result<Vec<Vec<BatchedEvent>>, SocketError> {
+    // `{"events":[` + `]}`
+    const ENVELOPE_BYTES: usize = 13;
+
+    let mut batches = Vec::new();
+    let mut current = Vec::new();
+    let mut current_bytes = ENVELOPE_BYTES;
+    for event in events {
+        let size = serde_json::to_vec(&event)
+            .map_err(|e| SocketError::SerializationError(e.to_string()))?
+            .len();
+        // 非首个事件前有逗号分隔
+        if !current.is_empty() && current_bytes + 1 + size > max_bytes {
+            batches.push(std::mem::take(&mut current));
+            current_bytes = ENVELOPE_BYTES;
+        }
+        current_bytes += if current.is_empty() { size } else { size + 1 };
+        current.push(event);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    Ok(batches)
+}
+
+/// 发送事件并等待 ack（`SocketClient::emit_with_ack_timeout` 的实现，供后台任务复用）
+async fn emit_with_ack_on(
+    client: &SharedClient,
+    middleware: Option<&Arc<dyn EmitMiddleware>>,
+    event: &str,
+    data: Value,
+    timeout: std::time::Duration,
+) -> Result<Value, SocketError> {
+    let mut data = data;
+    if let Some(middleware) = middleware {
+        middleware.before_emit(event, &mut data);
+    }
+
+    let Some(client) = client.load_full() else {
+        return Err(SocketError::NotConnected);
+    };
+
+    // 使用 oneshot channel 捕获 ack payload
+    let (tx, rx) = oneshot::channel::<Value>();
+    let tx = Arc::new(std::sync::Mutex::new(Some(tx)));
+
+    let emitted = client
+        .emit_with_ack(
+            event,
+            data,
+            timeout,
+            move |payload, _| {
+                let tx = tx.clone();
+                async move {
+                    debug!("Ack received: {:?}", payload);
+                    let value = match payload {
+                        Payload::Text(values) => {
+                            values.into_iter().next().unwrap_or(json!({"success": true}))
+                        }
+                        _ => json!({"success": true}),
+                    };
+                    let sender = tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+                    if let Some(sender) = sender {
+                        let _ = sender.send(value);
+                    }
+                }
+                .boxed()
+            },
+        )
+        .await
+        .map_err(|e| SocketError::EmitFailed(e.to_string()));
+
+    if let Some(middleware) = middleware {
+        middleware.after_emit(event, &emitted);
+    }
+    emitted?;
+
+    // 等待 ack 返回；回调被丢弃（底层 ack 超时）同样视为超时
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(_)) | Err(_) => {
+            warn!("[SocketClient] Ack timeout for {} after {:?}", event, timeout);
+            Err(SocketError::AckTimeout)
+        }
+    }
+}
+
+/// 发送 `daemon:ping` 并等待 Server 的 pong（ack），记录往返时间
+async fn ping_on(
+    client: &SharedClient,
+    middleware: Option<&Arc<dyn EmitMiddleware>>,
+    latency: &LatencySamples,
+    timeout: std::time::Duration,
+) -> Result<std::time::Duration, SocketError> {
+    let started = std::time::Instant::now();
+    let data = json!({ "timestamp": chrono::Utc::now().timestamp_millis() });
+    emit_with_ack_on(client, middleware, "daemon:ping", data, timeout).await?;
+    let elapsed = started.elapsed();
+    latency.record(elapsed);
+    Ok(elapsed)
+}
+
+/// 需要优先处理的 Server 事件（不排在大量数据请求之后）
+const HIGH_PRIORITY_EVENTS: &[&str] = &[
+    "server:approvalResponse",
+    "server:command",
+    "server:mobileViewing",
+];
+
+/// 事件是否走高优先级队列
+fn is_high_priority_event(event: &str) -> bool {
+    HIGH_PRIORITY_EVENTS.contains(&event)
+}
+
+/// 按优先级把下行事件送入对应队列
+#[derive(Clone)]
+struct EventSender {
+    high: mpsc::Sender<(String, Value)>,
+    low: mpsc::Sender<(String, Value)>,
+}
+
+impl EventSender {
+    async fn send(
+        &self,
+        event: (String, Value),
+    ) -> Result<(), mpsc::error::SendError<(String, Value)>> {
+        if is_high_priority_event(&event.0) {
+            self.high.send(event).await
+        } else {
+            self.low.send(event).await
         }
     }
 }
@@ -89,12 +775,17 @@ impl Default for SocketConfig {
 /// Socket 客户端
 pub struct SocketClient {
     config: SocketConfig,
-    client: Arc<RwLock<Option<Client>>>,
-    connected: Arc<AtomicBool>,
+    client: SharedClient,
+    connected: Arc<ConnectedFlag>,
+    /// 连接状态机（用于日志和诊断）
+    state: SharedConnectionState,
     /// 重连中标志（防止重连风暴）
     reconnecting: Arc<AtomicBool>,
-    event_tx: mpsc::Sender<(String, Value)>,
-    event_rx: Arc<RwLock<mpsc::Receiver<(String, Value)>>>,
+    event_tx: EventSender,
+    /// 高优先级事件（审批响应、命令等，需要尽快处理）
+    high_priority_rx: Arc<RwLock<mpsc::Receiver<(String, Value)>>>,
+    /// 低优先级事件（数据请求）
+    low_priority_rx: Arc<RwLock<mpsc::Receiver<(String, Value)>>>,
     /// Redis 服务注册中心（可选）
     registry: Arc<RwLock<Option<ServiceRegistry>>>,
     /// 当前使用的 Server URL（可能通过 Redis 发现）
@@ -106,31 +797,61 @@ pub struct SocketClient {
     /// 重连通知 channel
     reconnect_tx: mpsc::Sender<()>,
     reconnect_rx: Arc<RwLock<mpsc::Receiver<()>>>,
+    /// Emit 中间件（可选）
+    middleware: Option<Arc<dyn EmitMiddleware>>,
+    /// ExactlyOnce 已发送的 dedup key（重连后清空）
+    sent_dedup_keys: Arc<RwLock<HashSet<String>>>,
+    /// 虚拟通道路由（logical_id → 通道）
+    virtual_channels: ChannelRoutes,
+    /// 批量发送锁（不同批次的事件不交错）
+    batch_lock: tokio::sync::Mutex<()>,
+    /// 当前服务器是否支持 `daemon:batch`（注册 ack 中声明，重新连接时重置）
+    batch_supported: Arc<AtomicBool>,
+    /// 动态事件处理器（连接后也可以增删）
+    dispatcher: EventDispatcher,
+    /// 最近的 ping 往返时间
+    latency: Arc<LatencySamples>,
 }
 
 impl SocketClient {
     /// 创建客户端
     pub fn new(config: SocketConfig) -> Self {
-        let (event_tx, event_rx) = mpsc::channel(100);
+        let (high_tx, high_priority_rx) = mpsc::channel(100);
+        let (low_tx, low_priority_rx) = mpsc::channel(100);
         let (reconnect_tx, reconnect_rx) = mpsc::channel(1);
         let current_url = config.url.clone();
 
         Self {
             config,
-            client: Arc::new(RwLock::new(None)),
-            connected: Arc::new(AtomicBool::new(false)),
+            client: Arc::new(ArcSwapOption::empty()),
+            connected: Arc::new(ConnectedFlag::new()),
+            state: Arc::new(std::sync::RwLock::new(ConnectionState::Disconnected)),
             reconnecting: Arc::new(AtomicBool::new(false)),
-            event_tx,
-            event_rx: Arc::new(RwLock::new(event_rx)),
+            event_tx: EventSender { high: high_tx, low: low_tx },
+            high_priority_rx: Arc::new(RwLock::new(high_priority_rx)),
+            low_priority_rx: Arc::new(RwLock::new(low_priority_rx)),
             registry: Arc::new(RwLock::new(None)),
             current_url: Arc::new(RwLock::new(current_url)),
             keepalive_handle: Arc::new(RwLock::new(None)),
             event_listener_handle: Arc::new(RwLock::new(None)),
             reconnect_tx,
             reconnect_rx: Arc::new(RwLock::new(reconnect_rx)),
+            middleware: None,
+            sent_dedup_keys: Arc::new(RwLock::new(HashSet::new())),
+            virtual_channels: ChannelRoutes::default(),
+            batch_lock: tokio::sync::Mutex::new(()),
+            batch_supported: Arc::new(AtomicBool::new(false)),
+            dispatcher: EventDispatcher::new(),
+            latency: Arc::new(LatencySamples::default()),
         }
     }
 
+    /// 设置 Emit 中间件
+    pub fn with_middleware(mut self, middleware: Arc<dyn EmitMiddleware>) -> Self {
+        self.middleware = Some(middleware);
+        self
+    }
+
     /// 使用默认配置创建
     pub fn with_url(url: &str) -> Self {
         let mut config = SocketConfig::default();
@@ -145,6 +866,10 @@ impl SocketClient {
         if let Some(redis_config) = &self.config.redis {
             let registry = ServiceRegistry::new(redis_config.clone())
                 .map_err(|e| SocketError::RegistryError(e.to_string()))?;
+            let registry = match &self.config.daemon_info {
+                Some(info) => registry.with_device_id(info.device_id.clone()),
+                None => registry,
+            };
 
             registry
                 .connect()
@@ -163,23 +888,44 @@ impl SocketClient {
         Ok(())
     }
 
-    /// 通过 Redis 发现 Server 地址
+    /// 使用已连接的 ServiceRegistry（由调用方创建，如需要本地文件降级时）
+    ///
+    /// 之后 `discover_server` / `reconnect` 都从该注册中心选举 Server。
+    pub async fn set_registry(&self, registry: ServiceRegistry) {
+        *self.registry.write().await = Some(registry);
+    }
+
+    /// 通过 Redis 发现 Server 地址（见 `ServiceRegistry::elect_primary_server`）
     pub async fn discover_server(&self) -> Result<Option<String>, SocketError> {
         let registry = self.registry.read().await;
         if let Some(ref reg) = *registry {
-            let servers = reg
-                .get_servers()
+            let elected = reg
+                .elect_primary_server()
                 .await
                 .map_err(|e| SocketError::RegistryError(e.to_string()))?;
 
-            if let Some(addr) = servers.first() {
+            if let Some(ref addr) = elected {
                 info!("[SocketClient] Discovered server: {}", addr);
-                return Ok(Some(addr.clone()));
             }
+            return Ok(elected);
         }
         Ok(None)
     }
 
+    /// 获取所有 Daemon 当前打开的 Session（session_id → device_id）
+    ///
+    /// 未启用 Redis 时返回空表。
+    pub async fn list_daemon_sessions(&self) -> Result<HashMap<String, String>, SocketError> {
+        let registry = self.registry.read().await;
+        match *registry {
+            Some(ref reg) => reg
+                .list_all_daemons_with_sessions()
+                .await
+                .map_err(|e| SocketError::RegistryError(e.to_string())),
+            None => Ok(HashMap::new()),
+        }
+    }
+
     /// 注册 Daemon 到 Redis
     pub async fn register_daemon_to_redis(&self) -> Result<(), SocketError> {
         let registry = self.registry.read().await;
@@ -248,27 +994,42 @@ impl SocketClient {
 
         let registry = self.registry.clone();
         let daemon_info = self.config.daemon_info.clone();
+        let client = self.client.clone();
+        let connected = self.connected.clone();
+        let middleware = self.middleware.clone();
+        let latency = self.latency.clone();
 
-        if daemon_info.is_none() {
+        let Some(info) = daemon_info else {
             return;
-        }
-
-        let info = daemon_info.unwrap();
+        };
         let interval = std::time::Duration::from_secs(info.ttl / 2); // TTL 的一半
 
         let handle = tokio::spawn(async move {
             loop {
                 tokio::time::sleep(interval).await;
 
-                let reg = registry.read().await;
-                if let Some(ref r) = *reg {
+                {
+                    let reg = registry.read().await;
+                    let Some(ref r) = *reg else {
+                        break;
+                    };
                     if let Err(e) = r.keep_alive_daemon(&info.device_id, info.ttl).await {
                         warn!("[SocketClient] Keepalive failed: {}", e);
                         // 如果 key 不存在，需要重新注册
                         // 这里简单地继续尝试，上层应该处理重连逻辑
                     }
-                } else {
-                    break;
+                }
+
+                // 顺带探测 Server 响应，及早发现连接降级
+                if !connected.get() {
+                    continue;
+                }
+                match ping_on(&client, middleware.as_ref(), &latency, PING_TIMEOUT).await {
+                    Ok(rtt) if rtt > DEGRADED_LATENCY => {
+                        warn!("[SocketClient] Connection degraded: ping took {:?}", rtt);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("[SocketClient] Keepalive ping failed: {}", e),
                 }
             }
         });
@@ -401,15 +1162,16 @@ impl SocketClient {
     /// 重新连接（Server 重启后调用）
     pub async fn reconnect(&self) -> Result<(), SocketError> {
         info!("[SocketClient] Reconnecting...");
+        transition(&self.state, ConnectionState::Reconnecting);
 
         // 使用 scopeguard 模式确保 reconnecting 标志被重置
         let result = async {
             // 1. 断开旧连接（但不注销 Redis）
             self.stop_keepalive().await;
-            if let Some(client) = self.client.write().await.take() {
+            if let Some(client) = self.client.swap(None) {
                 let _ = client.disconnect().await;
             }
-            self.connected.store(false, Ordering::SeqCst);
+            self.connected.set(false);
 
             // 2. 重新发现 Server
             if let Some(server_addr) = self.discover_server().await? {
@@ -426,106 +1188,84 @@ impl SocketClient {
             // 5. 重启心跳
             self.start_keepalive().await;
 
+            // 6. 新连接上重新允许 ExactlyOnce 事件
+            self.sent_dedup_keys.write().await.clear();
+
             info!("[SocketClient] Reconnected successfully");
             Ok(())
         }.await;
 
         // 无论成功失败，都重置 reconnecting 标志
         self.reconnecting.store(false, Ordering::SeqCst);
+        if result.is_err() && !self.is_connected() {
+            transition(&self.state, ConnectionState::Disconnected);
+        }
 
         result
     }
 
-    /// 获取 ServiceRegistry 引用（用于外部访问）
-    pub async fn get_registry(&self) -> Option<Arc<RwLock<Option<ServiceRegistry>>>> {
-        if self.registry.read().await.is_some() {
-            Some(self.registry.clone())
-        } else {
-            None
-        }
-    }
-
-    /// 构建 TLS 连接器（支持 mTLS）
-    fn build_tls_connector(&self) -> Result<Option<TlsConnector>, SocketError> {
-        let tls = &self.config.tls;
-
-        // 如果没有 TLS 配置，返回 None
-        if tls.ca_cert_path.is_none()
-            && tls.client_cert_path.is_none()
-            && !tls.danger_accept_invalid_certs
-        {
-            return Ok(None);
-        }
-
-        let mut builder = native_tls::TlsConnector::builder();
-
-        // 加载 CA 证书
-        if let Some(ca_path) = &tls.ca_cert_path {
-            info!("Loading CA certificate from {:?}", ca_path);
-            let ca_pem = fs::read(ca_path).map_err(|e| {
-                SocketError::TlsError(format!("Failed to read CA cert: {}", e))
-            })?;
-            let ca_cert = Certificate::from_pem(&ca_pem).map_err(|e| {
-                SocketError::TlsError(format!("Failed to parse CA cert: {}", e))
-            })?;
-            builder.add_root_certificate(ca_cert);
-        }
-
-        // 加载客户端证书（mTLS）
-        if let Some(cert_path) = &tls.client_cert_path {
-            info!("Loading client certificate from {:?}", cert_path);
-            let cert_data = fs::read(cert_path).map_err(|e| {
-                SocketError::TlsError(format!("Failed to read client cert: {}", e))
-            })?;
-
-            // 检查是否是 P12 格式
-            let is_p12 = cert_path
-                .extension()
-                .map(|ext| ext == "p12" || ext == "pfx")
-                .unwrap_or(false);
-
-            let identity = if is_p12 {
-                // PKCS#12 格式
-                let password = tls.client_p12_password.as_deref().unwrap_or("");
-                Identity::from_pkcs12(&cert_data, password).map_err(|e| {
-                    SocketError::TlsError(format!("Failed to parse PKCS#12: {}", e))
-                })?
-            } else {
-                // PEM 格式
-                let key_pem = if let Some(key_path) = &tls.client_key_path {
-                    fs::read(key_path).map_err(|e| {
-                        SocketError::TlsError(format!("Failed to read client key: {}", e))
-                    })?
-                } else {
-                    return Err(SocketError::TlsError(
-                        "Client key path required for PEM format".into(),
-                    ));
-                };
-                Identity::from_pkcs8(&cert_data, &key_pem).map_err(|e| {
-                    SocketError::TlsError(format!("Failed to create identity from PEM: {}", e))
-                })?
-            };
-            builder.identity(identity);
+    /// 按重连策略重连：失败时指数退避重试，直到成功或超过最大尝试次数
+    pub async fn reconnect_with_policy(&self) -> Result<(), SocketError> {
+        let policy = &self.config.reconnect;
+        let mut attempt = 0;
+        loop {
+            match self.reconnect().await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    attempt += 1;
+                    if !policy.should_retry(attempt) {
+                        return Err(e);
+                    }
+                    let delay = policy.delay_for(attempt);
+                    warn!(
+                        "[SocketClient] Reconnect attempt {} failed: {}, retrying in {:?}",
+                        attempt, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
         }
+    }
 
-        // 开发模式：跳过证书验证
-        if tls.danger_accept_invalid_certs {
-            warn!("TLS certificate verification disabled - FOR DEVELOPMENT ONLY");
-            builder.danger_accept_invalid_certs(true);
+    /// 当前使用的 Server URL
+    pub async fn current_url(&self) -> String {
+        self.current_url.read().await.clone()
+    }
+
+    /// 切换 Server URL（下次连接时生效）
+    pub async fn set_url(&self, url: impl Into<String>) {
+        *self.current_url.write().await = url.into();
+    }
+
+    /// 获取 ServiceRegistry 引用（用于外部访问）
+    pub async fn get_registry(&self) -> Option<Arc<RwLock<Option<ServiceRegistry>>>> {
+        if self.registry.read().await.is_some() {
+            Some(self.registry.clone())
+        } else {
+            None
         }
+    }
 
-        let connector = builder.build().map_err(|e| {
-            SocketError::TlsError(format!("Failed to build TLS connector: {}", e))
-        })?;
+    /// 解析当前使用的命名空间
+    fn resolve_namespace(&self) -> String {
+        let (hostname, platform) = match &self.config.daemon_info {
+            Some(info) => (info.device_name.as_str(), info.platform.as_str()),
+            None => ("", crate::platform::current_platform()),
+        };
+        self.config.namespace.resolve(hostname, platform)
+    }
 
-        Ok(Some(connector))
+    /// 构建 TLS 连接器（支持 mTLS）
+    fn build_tls_connector(&self) -> Result<Option<TlsConnector>, SocketError> {
+        self.config.tls.build_connector()
     }
 
     /// 连接到服务器
     pub async fn connect(&self) -> Result<(), SocketError> {
         // 使用 current_url（可能是通过 Redis 发现的）
         let base_url = self.current_url.read().await.clone();
-        let url = format!("{}{}", base_url, self.config.namespace);
+        let namespace = self.resolve_namespace();
+        let url = format!("{}{}", base_url, namespace);
         info!("Connecting to {}", url);
 
         let connected = self.connected.clone();
@@ -533,10 +1273,11 @@ impl SocketClient {
 
         // 构建 TLS 连接器
         let tls_connector = self.build_tls_connector()?;
+        transition(&self.state, ConnectionState::Connecting);
 
         // 构建客户端（强制使用 WebSocket 避免 Fastify polling 兼容性问题）
         let mut builder = ClientBuilder::new(&base_url)
-            .namespace(&self.config.namespace)
+            .namespace(&namespace)
             .transport_type(rust_socketio::TransportType::Websocket);
 
         // 如果有 TLS 配置则应用
@@ -544,24 +1285,50 @@ impl SocketClient {
             builder = builder.tls_config(connector);
         }
 
+        // 握手认证
+        if let Some(auth) = &self.config.auth {
+            builder = builder.auth(auth.clone());
+        }
+
         let client = builder
+            // 带 `{logical_id}::` 前缀的事件转发给对应的虚拟通道，其余交给动态处理器
+            .on_any({
+                let routes = self.virtual_channels.clone();
+                let dispatcher = self.dispatcher.clone();
+                move |event, payload, _| {
+                    let routes = routes.clone();
+                    let dispatcher = dispatcher.clone();
+                    async move {
+                        if let Event::Custom(name) = event {
+                            if let Some(data) = extract_payload(payload) {
+                                dispatcher.dispatch(&name, &data);
+                                channel::route_event(&routes, &name, data).await;
+                            }
+                        }
+                    }
+                    .boxed()
+                }
+            })
             .on("connect", move |_, _| {
                 let connected = connected.clone();
                 async move {
                     info!("Socket connected");
-                    connected.store(true, Ordering::SeqCst);
+                    connected.set(true);
                 }
                 .boxed()
             })
             .on("disconnect", {
                 let connected = self.connected.clone();
+                let state = self.state.clone();
                 let tx = event_tx.clone();
                 move |_, _| {
                     let connected = connected.clone();
+                    let state = state.clone();
                     let tx = tx.clone();
                     async move {
                         warn!("Socket disconnected");
-                        connected.store(false, Ordering::SeqCst);
+                        connected.set(false);
+                        transition(&state, ConnectionState::Disconnected);
                         // 发送断开事件，让上层处理重连
                         let _ = tx.send(("__disconnected".into(), json!({}))).await;
                     }
@@ -570,14 +1337,17 @@ impl SocketClient {
             })
             .on("error", {
                 let connected = self.connected.clone();
+                let state = self.state.clone();
                 let tx = event_tx.clone();
                 move |err, _| {
                     let connected = connected.clone();
+                    let state = state.clone();
                     let tx = tx.clone();
                     async move {
                         error!("Socket error: {:?}", err);
                         // 设置断开状态，触发重连
-                        connected.store(false, Ordering::SeqCst);
+                        connected.set(false);
+                        transition(&state, ConnectionState::Disconnected);
                         let _ = tx.send(("__disconnected".into(), json!({}))).await;
                     }
                     .boxed()
@@ -793,28 +1563,43 @@ impl SocketClient {
             })
             .on("server-shutdown", {
                 let tx = event_tx.clone();
-                move |_, _| {
+                move |payload, _| {
                     let tx = tx.clone();
                     async move {
-                        let _ = tx.send(("server-shutdown".into(), json!({}))).await;
+                        let data = shutdown_payload(payload);
+                        let _ = tx.send(("server-shutdown".into(), data)).await;
                     }
                     .boxed()
                 }
             })
             .connect()
             .await
-            .map_err(|e| SocketError::ConnectionFailed(e.to_string()))?;
+            .map_err(|e| {
+                transition(&self.state, ConnectionState::Disconnected);
+                // 无效 URL 是永久错误，按类型识别；其余传输层错误只有字符串信息
+                let message = e.to_string();
+                match e {
+                    rust_socketio::Error::InvalidUrl(_)
+                    | rust_socketio::Error::InvalidUrlScheme(_) => SocketError::InvalidUrl(message),
+                    _ => SocketError::ConnectionFailed(ConnectionError::from_message(message)),
+                }
+            })?;
 
         // connect() 成功后设置连接状态（不依赖 connect 回调，rust_socketio 的回调行为不可靠）
-        self.connected.store(true, Ordering::SeqCst);
+        // 新连接的服务器可能不支持批量信封，注册成功前逐个发送
+        self.batch_supported.store(false, Ordering::SeqCst);
+        self.connected.set(true);
+        transition(&self.state, ConnectionState::Authenticating);
         info!("Socket connected successfully");
 
-        *self.client.write().await = Some(client);
+        self.client.store(Some(Arc::new(client)));
         Ok(())
     }
 
     /// 断开连接
     pub async fn disconnect(&self) {
+        transition(&self.state, ConnectionState::Disconnecting);
+
         // 1. 停止心跳任务
         self.stop_keepalive().await;
 
@@ -825,12 +1610,13 @@ impl SocketClient {
         let _ = self.unregister_daemon_from_redis().await;
 
         // 4. 断开 Socket
-        if let Some(client) = self.client.write().await.take() {
+        if let Some(client) = self.client.swap(None) {
             if let Err(e) = client.disconnect().await {
                 error!("Disconnect error: {:?}", e);
             }
         }
-        self.connected.store(false, Ordering::SeqCst);
+        self.connected.set(false);
+        transition(&self.state, ConnectionState::Disconnected);
 
         // 5. 断开 Redis
         if let Some(ref registry) = *self.registry.read().await {
@@ -841,14 +1627,73 @@ impl SocketClient {
 
     /// 是否已连接
     pub fn is_connected(&self) -> bool {
-        self.connected.load(Ordering::SeqCst)
+        self.connected.get()
+    }
+
+    /// 当前连接状态
+    pub fn state(&self) -> ConnectionState {
+        *self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 等待连接建立（已连接时立即返回）
+    ///
+    /// 不轮询：连接状态变为 true 时被唤醒，可直接用于 `tokio::select!`。
+    pub async fn wait_until_connected(&self, timeout: std::time::Duration) -> Result<(), SocketError> {
+        let mut rx = self.connected.subscribe();
+        match tokio::time::timeout(timeout, rx.wait_for(|connected| *connected)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(_)) => Err(SocketError::NotConnected),
+            Err(_) => Err(SocketError::ConnectionFailed(ConnectionError::Timeout)),
+        }
     }
 
     /// 发送事件
     pub async fn emit(&self, event: &str, data: Value) -> Result<(), SocketError> {
+        let mut data = data;
+        if let Some(middleware) = &self.middleware {
+            middleware.before_emit(event, &mut data);
+        }
+
+        let result = self.emit_raw(event, data).await;
+
+        if let Some(middleware) = &self.middleware {
+            middleware.after_emit(event, &result);
+        }
+        result
+    }
+
+    /// 添加事件处理器（连接前后均可调用）
+    pub fn add_event_handler(&self, event: &str, handler: EventHandler) -> HandlerId {
+        self.dispatcher.add_handler(event, handler)
+    }
+
+    /// 移除事件处理器
+    pub fn remove_event_handler(&self, event: &str, id: HandlerId) -> bool {
+        self.dispatcher.remove_handler(event, id)
+    }
+
+    /// 合并发送一批相关事件
+    ///
+    /// 服务器在注册 ack 中声明支持时，事件按顺序打包为 `daemon:batch` 信封发送，
+    /// 超过 `max_batch_bytes` 时拆分为多个信封；否则逐个发送原事件。
+    /// 批次之间互斥，同一批事件不会与其他批次交错；遇到第一个错误即停止，
+    /// 返回错误前已发送的事件不会撤回。
+    pub async fn emit_batch(&self, events: Vec<(String, Value)>) -> Result<(), SocketError> {
+        let _guard = self.batch_lock.lock().await;
+        send_batched(
+            self,
+            self.middleware.as_ref(),
+            events,
+            self.config.max_batch_bytes,
+            self.batch_supported.load(Ordering::SeqCst),
+        )
+        .await
+    }
+
+    /// 发送事件（不经过中间件）
+    async fn emit_raw(&self, event: &str, data: Value) -> Result<(), SocketError> {
         debug!("Emitting event: {}", event);
-        let client = self.client.read().await;
-        let client = client.as_ref().ok_or(SocketError::NotConnected)?;
+        let client = self.client.load_full().ok_or(SocketError::NotConnected)?;
 
         client
             .emit(event, data)
@@ -859,66 +1704,117 @@ impl SocketClient {
         Ok(())
     }
 
-    /// 发送事件并等待 Ack
-    pub async fn emit_with_ack(
+    /// 按指定投递保证级别发送事件
+    pub async fn emit_qos(
         &self,
         event: &str,
         data: Value,
-        timeout_secs: u64,
-    ) -> Result<Value, SocketError> {
-        let client = self.client.read().await;
-        let client = client.as_ref().ok_or(SocketError::NotConnected)?;
-
-        // 使用 oneshot channel 捕获 ack payload
-        let (tx, rx) = oneshot::channel::<Value>();
-        let tx = Arc::new(std::sync::Mutex::new(Some(tx)));
-
-        client
-            .emit_with_ack(
-                event,
-                data,
-                std::time::Duration::from_secs(timeout_secs),
-                move |payload, _| {
-                    let tx = tx.clone();
-                    async move {
-                        debug!("Ack received: {:?}", payload);
-                        let value = match payload {
-                            Payload::Text(values) => {
-                                values.into_iter().next().unwrap_or(json!({"success": true}))
-                            }
-                            _ => json!({"success": true}),
-                        };
-                        if let Some(sender) = tx.lock().unwrap().take() {
-                            let _ = sender.send(value);
+        qos: QosLevel,
+    ) -> Result<(), SocketError> {
+        match qos {
+            QosLevel::AtMostOnce => self.emit(event, data).await,
+            QosLevel::AtLeastOnce { max_retries } => {
+                let mut attempt = 0;
+                loop {
+                    let result = if self.is_connected() {
+                        self.emit(event, data.clone()).await
+                    } else {
+                        Err(SocketError::NotConnected)
+                    };
+
+                    match result {
+                        Ok(()) => return Ok(()),
+                        Err(e) if attempt >= max_retries || !e.is_retriable() => return Err(e),
+                        Err(e) => {
+                            let delay = QOS_RETRY_BASE_DELAY * 2u32.saturating_pow(attempt.min(4));
+                            warn!(
+                                "Emit {} failed (attempt {}/{}): {:?}, retrying in {:?}",
+                                event,
+                                attempt + 1,
+                                max_retries + 1,
+                                e,
+                                delay
+                            );
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
                         }
                     }
-                    .boxed()
-                },
-            )
-            .await
-            .map_err(|e| SocketError::EmitFailed(e.to_string()))?;
-
-        // 等待 ack 返回
-        match rx.await {
-            Ok(value) => Ok(value),
-            Err(_) => Ok(json!({"success": true})), // channel 关闭，返回默认值
+                }
+            }
+            QosLevel::ExactlyOnce { dedup_key } => {
+                // 发送前占用 key，并发的同 key 发送只有一个会真正发出
+                if !self.sent_dedup_keys.write().await.insert(dedup_key.clone()) {
+                    debug!("Skipping duplicate emit {} (dedup key {})", event, dedup_key);
+                    return Ok(());
+                }
+                let result = self.emit(event, data).await;
+                if result.is_err() {
+                    // 发送失败时释放 key，允许重试
+                    self.sent_dedup_keys.write().await.remove(&dedup_key);
+                }
+                result
+            }
         }
     }
 
-    /// 接收下一个事件
+    /// 发送事件并等待 Ack
+    ///
+    /// 超时由配置的 `AckTimeoutPolicy` 按事件决定。
+    pub async fn emit_with_ack(&self, event: &str, data: Value) -> Result<Value, SocketError> {
+        let timeout = self.config.ack_timeout.timeout_for(event);
+        self.emit_with_ack_timeout(event, data, timeout).await
+    }
+
+    /// 发送事件并等待 ack（指定超时）
+    pub async fn emit_with_ack_timeout(
+        &self,
+        event: &str,
+        data: Value,
+        timeout: std::time::Duration,
+    ) -> Result<Value, SocketError> {
+        emit_with_ack_on(&self.client, self.middleware.as_ref(), event, data, timeout).await
+    }
+
+    /// 接收下一个事件（高优先级事件优先）
     pub async fn recv_event(&self) -> Option<(String, Value)> {
-        self.event_rx.write().await.recv().await
+        tokio::select! {
+            biased;
+            event = self.recv_high_priority_event() => event,
+            event = self.recv_low_priority_event() => event,
+        }
     }
 
     /// 接收事件（带超时）
     pub async fn recv_event_timeout(&self, timeout: std::time::Duration) -> Option<(String, Value)> {
-        let mut rx = self.event_rx.write().await;
-        match tokio::time::timeout(timeout, rx.recv()).await {
+        match tokio::time::timeout(timeout, self.recv_event()).await {
             Ok(result) => result,
             Err(_) => None, // 超时
         }
     }
 
+    /// 接收下一个高优先级事件
+    pub async fn recv_high_priority_event(&self) -> Option<(String, Value)> {
+        self.high_priority_rx.write().await.recv().await
+    }
+
+    /// 接收下一个低优先级事件
+    pub async fn recv_low_priority_event(&self) -> Option<(String, Value)> {
+        self.low_priority_rx.write().await.recv().await
+    }
+
+    /// 创建共享当前物理连接的虚拟通道
+    ///
+    /// 通道收发的事件名都带 `{logical_id}::` 前缀，重连后仍然有效；
+    /// 同一逻辑 ID 重复创建时，旧通道不再收到事件。
+    pub fn create_virtual_channel(&self, logical_id: &str) -> VirtualChannel {
+        VirtualChannel::new(
+            logical_id,
+            self.client.clone(),
+            self.middleware.clone(),
+            self.virtual_channels.clone(),
+        )
+    }
+
     /// 尝试接收事件（非阻塞）
     pub fn try_recv_event(&self) -> Option<(String, Value)> {
         // 注意：这里需要在异步上下文中使用
@@ -929,11 +1825,35 @@ impl SocketClient {
     // ==================== 便捷方法 ====================
 
     /// 注册 daemon
+    ///
+    /// 等待 Server ack 确认注册成功，超时返回 `SocketError::AckTimeout`。
     pub async fn register(&self, data: RegisterData) -> Result<Value, SocketError> {
-        // 暂时用普通 emit，避免 ack 阻塞问题
-        self.emit("daemon:register", serde_json::to_value(data).unwrap())
-            .await?;
-        Ok(json!({"success": true}))
+        let data = serde_json::to_value(data)
+            .map_err(|e| SocketError::SerializationError(e.to_string()))?;
+        let ack = self.emit_with_ack("daemon:register", data).await?;
+        self.batch_supported
+            .store(ack_supports_batch(&ack), Ordering::SeqCst);
+        transition(&self.state, ConnectionState::Ready);
+        Ok(ack)
+    }
+
+    /// 探测 Server 是否在响应
+    ///
+    /// 发送 `daemon:ping` 并等待 Server ack（pong），返回往返时间并计入 `latency_stats`。
+    /// 未连接时返回 `NotConnected`，超时返回 `AckTimeout`。
+    pub async fn ping(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<std::time::Duration, SocketError> {
+        if !self.is_connected() {
+            return Err(SocketError::NotConnected);
+        }
+        ping_on(&self.client, self.middleware.as_ref(), &self.latency, timeout).await
+    }
+
+    /// 最近 10 次成功 ping 的延迟统计，还没有样本时返回 None
+    pub fn latency_stats(&self) -> Option<LatencyStats> {
+        self.latency.stats()
     }
 
     /// 上报在线（ETerm 专用事件名）
@@ -941,7 +1861,7 @@ impl SocketClient {
         let data = OnlineData {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
-        self.emit("daemon:etermOnline", serde_json::to_value(data).unwrap())
+        self.emit("daemon:etermOnline", to_payload(data)?)
             .await
     }
 
@@ -950,7 +1870,7 @@ impl SocketClient {
         let data = OfflineData {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
-        self.emit("daemon:etermOffline", serde_json::to_value(data).unwrap())
+        self.emit("daemon:etermOffline", to_payload(data)?)
             .await
     }
 
@@ -964,25 +1884,41 @@ impl SocketClient {
             projects,
             request_id,
         };
-        self.emit("daemon:projectData", serde_json::to_value(data).unwrap())
+        self.emit("daemon:projectData", to_payload(data)?)
             .await
     }
 
-    /// 上报会话元数据
+    /// 上报会话元数据（全部会话）
     pub async fn report_session_metadata(
         &self,
         sessions: Vec<Value>,
         project_path: Option<String>,
         request_id: Option<String>,
+    ) -> Result<(), SocketError> {
+        let total = sessions.len();
+        self.report_session_metadata_page(sessions, project_path, request_id, total, false)
+            .await
+    }
+
+    /// 上报一页会话元数据
+    pub async fn report_session_metadata_page(
+        &self,
+        sessions: Vec<Value>,
+        project_path: Option<String>,
+        request_id: Option<String>,
+        total: usize,
+        has_more: bool,
     ) -> Result<(), SocketError> {
         let data = SessionMetadataPayload {
             sessions,
             project_path,
             request_id,
+            total,
+            has_more,
         };
         self.emit(
             "daemon:sessionMetadata",
-            serde_json::to_value(data).unwrap(),
+            to_payload(data)?,
         )
         .await
     }
@@ -992,13 +1928,22 @@ impl SocketClient {
         &self,
         session_id: &str,
         message: Value,
+    ) -> Result<(), SocketError> {
+        self.notify_new_messages(session_id, vec![message]).await
+    }
+
+    /// 上报一批新消息（合并为一次 emit）
+    pub async fn notify_new_messages(
+        &self,
+        session_id: &str,
+        messages: Vec<Value>,
     ) -> Result<(), SocketError> {
         let data = NewMessageData {
             session_id: session_id.to_string(),
-            message,
+            messages,
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
-        self.emit("daemon:newMessage", serde_json::to_value(data).unwrap())
+        self.emit("daemon:newMessage", to_payload(data)?)
             .await
     }
 
@@ -1006,17 +1951,22 @@ impl SocketClient {
     pub async fn notify_metrics_update(
         &self,
         session_id: &str,
-        metrics: Value,
+        metrics: ContextMetrics,
     ) -> Result<(), SocketError> {
         let data = MetricsUpdateData {
             session_id: session_id.to_string(),
             metrics,
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
-        self.emit("daemon:metricsUpdate", serde_json::to_value(data).unwrap())
+        self.emit("daemon:metricsUpdate", to_payload(data)?)
             .await
     }
 
+    /// 上报 Daemon 状态（响应 `status` 命令）
+    pub async fn send_status_report(&self, report: Value) -> Result<(), SocketError> {
+        self.emit("daemon:statusReport", report).await
+    }
+
     /// 上报会话消息
     pub async fn report_session_messages(
         &self,
@@ -1035,7 +1985,7 @@ impl SocketClient {
             has_more,
             request_id,
         };
-        self.emit("daemon:sessionMessages", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sessionMessages", to_payload(data)?)
             .await
     }
 
@@ -1055,7 +2005,7 @@ impl SocketClient {
             project_path: project_path.to_string(),
             encoded_dir_name: encoded_dir_name.to_string(),
         };
-        self.emit("daemon:newSessionFound", serde_json::to_value(data).unwrap())
+        self.emit("daemon:newSessionFound", to_payload(data)?)
             .await
     }
 
@@ -1069,7 +2019,7 @@ impl SocketClient {
             client_id: client_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:newSessionNotFound", serde_json::to_value(data).unwrap())
+        self.emit("daemon:newSessionNotFound", to_payload(data)?)
             .await
     }
 
@@ -1083,7 +2033,7 @@ impl SocketClient {
             client_id: client_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:watchStarted", serde_json::to_value(data).unwrap())
+        self.emit("daemon:watchStarted", to_payload(data)?)
             .await
     }
 
@@ -1099,7 +2049,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:newSessionCreated", serde_json::to_value(data).unwrap())
+        self.emit("daemon:newSessionCreated", to_payload(data)?)
             .await
     }
 
@@ -1113,7 +2063,7 @@ impl SocketClient {
             project_path: project_path.to_string(),
             metadata,
         };
-        self.emit("daemon:projectUpdate", serde_json::to_value(data).unwrap())
+        self.emit("daemon:projectUpdate", to_payload(data)?)
             .await
     }
 
@@ -1127,7 +2077,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             metadata,
         };
-        self.emit("daemon:sessionUpdate", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sessionUpdate", to_payload(data)?)
             .await
     }
 
@@ -1152,7 +2102,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:sessionDetailUpdate", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sessionDetailUpdate", to_payload(data)?)
             .await
     }
 
@@ -1166,7 +2116,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:sessionRestored", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sessionRestored", to_payload(data)?)
             .await
     }
 
@@ -1180,7 +2130,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:sessionDeleted", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sessionDeleted", to_payload(data)?)
             .await
     }
 
@@ -1204,8 +2154,13 @@ impl SocketClient {
             tool_use_id: tool_use_id.to_string(),
             description: description.to_string(),
         };
-        self.emit("daemon:approvalRequest", serde_json::to_value(data).unwrap())
-            .await
+        // 权限请求丢失会导致会话卡住，失败时重试
+        self.emit_qos(
+            "daemon:approvalRequest",
+            to_payload(data)?,
+            QosLevel::AtLeastOnce { max_retries: 3 },
+        )
+        .await
     }
 
     /// 发送权限超时通知
@@ -1220,7 +2175,7 @@ impl SocketClient {
             session_id: session_id.to_string(),
             client_id: client_id.to_string(),
         };
-        self.emit("daemon:approvalTimeout", serde_json::to_value(data).unwrap())
+        self.emit("daemon:approvalTimeout", to_payload(data)?)
             .await
     }
 
@@ -1234,7 +2189,7 @@ impl SocketClient {
             request_id: request_id.to_string(),
             message: message.to_string(),
         };
-        self.emit("daemon:approvalExpired", serde_json::to_value(data).unwrap())
+        self.emit("daemon:approvalExpired", to_payload(data)?)
             .await
     }
 
@@ -1254,10 +2209,38 @@ impl SocketClient {
                 message: message.to_string(),
             },
         };
-        self.emit("daemon:sdkError", serde_json::to_value(data).unwrap())
+        self.emit("daemon:sdkError", to_payload(data)?)
             .await
     }
 
+    /// 通知监听会话数已达上限（会话以只轮询的降级模式监听）
+    pub async fn send_watch_limit_reached(
+        &self,
+        session_id: &str,
+        limit: usize,
+    ) -> Result<(), SocketError> {
+        let data = json!({
+            "sessionId": session_id,
+            "limit": limit,
+        });
+        self.emit("daemon:watchLimitReached", data).await
+    }
+
+    /// 回复 Mobile 查看状态变化的处理结果
+    pub async fn send_mobile_viewing_ack(
+        &self,
+        session_id: &str,
+        is_viewing: bool,
+        success: bool,
+    ) -> Result<(), SocketError> {
+        let data = json!({
+            "sessionId": session_id,
+            "isViewing": is_viewing,
+            "success": success,
+        });
+        self.emit("daemon:mobileViewingAck", data).await
+    }
+
     /// 发送 Swift 活动通知
     pub async fn send_swift_activity(
         &self,
@@ -1268,13 +2251,16 @@ impl SocketClient {
             session_id: session_id.to_string(),
             project_path: project_path.to_string(),
         };
-        self.emit("daemon:swiftActivity", serde_json::to_value(data).unwrap())
+        self.emit("daemon:swiftActivity", to_payload(data)?)
             .await
     }
 
     // ==================== V3: 写操作响应方法 ====================
 
     /// 发送会话创建结果
+    ///
+    /// `resumed` 区分新建会话和通过 `--resume` 恢复的已有会话。
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_session_created_result(
         &self,
         request_id: &str,
@@ -1283,6 +2269,7 @@ impl SocketClient {
         encoded_dir_name: Option<&str>,
         transcript_path: Option<&str>,
         error: Option<&str>,
+        resumed: bool,
     ) -> Result<(), SocketError> {
         let data = json!({
             "requestId": request_id,
@@ -1291,8 +2278,17 @@ impl SocketClient {
             "encodedDirName": encoded_dir_name,
             "transcriptPath": transcript_path,
             "error": error,
+            "resumed": resumed,
         });
-        self.emit("daemon:sessionCreatedResult", data).await
+        // 重连抖动时避免重复上报同一创建结果
+        self.emit_qos(
+            "daemon:sessionCreatedResult",
+            data,
+            QosLevel::ExactlyOnce {
+                dedup_key: format!("sessionCreatedResult:{}", request_id),
+            },
+        )
+        .await
     }
 
     /// 发送加载状态检查结果
@@ -1335,17 +2331,327 @@ fn extract_payload(payload: Payload) -> Option<Value> {
     }
 }
 
+/// `server-shutdown` 的负载（可能带 `restartDelaySecs`），旧版服务器不带负载时为空对象
+fn shutdown_payload(payload: Payload) -> Value {
+    extract_payload(payload).unwrap_or(json!({}))
+}
+
+impl EmitTransport for SocketClient {
+    fn send<'a>(&'a self, event: &'a str, data: Value) -> BoxFuture<'a, Result<(), SocketError>> {
+        self.emit_raw(event, data).boxed()
+    }
+}
+
 // 需要 FutureExt trait
+use futures::future::BoxFuture;
 use futures::FutureExt;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tls_config_validate() {
+        // 未配置 TLS
+        assert!(TlsConfig::default().validate().is_ok());
+
+        // 证书文件不存在
+        let tls = TlsConfig {
+            ca_cert_path: Some(PathBuf::from("/nonexistent/ca.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(tls.validate(), Err(SocketError::TlsError(_))));
+
+        // PEM 客户端证书缺少私钥
+        let dir = tempfile::TempDir::new().unwrap();
+        let cert_path = dir.path().join("client.pem");
+        std::fs::write(&cert_path, "not a cert").unwrap();
+        let tls = TlsConfig {
+            client_cert_path: Some(cert_path),
+            ..Default::default()
+        };
+        assert!(matches!(tls.validate(), Err(SocketError::TlsError(_))));
+    }
+
+    #[test]
+    fn test_tls_config_from_env() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let ca_path = dir.path().join("ca.pem");
+        std::fs::write(&ca_path, "ca").unwrap();
+
+        let vars = |pairs: Vec<(&'static str, String)>| {
+            let map: HashMap<_, _> = pairs.into_iter().collect();
+            move |name: &str| map.get(name).cloned()
+        };
+
+        let tls = TlsConfig::from_vars(vars(vec![])).unwrap();
+        assert!(tls.ca_cert_path.is_none() && !tls.danger_accept_invalid_certs);
+
+        let tls = TlsConfig::from_vars(vars(vec![
+            ("VLAUDE_CA_CERT", ca_path.display().to_string()),
+            ("VLAUDE_CLIENT_KEY", String::new()),
+            ("VLAUDE_P12_PASSWORD", "secret".to_string()),
+            ("VLAUDE_INSECURE", "True".to_string()),
+        ]))
+        .unwrap();
+        assert_eq!(tls.ca_cert_path, Some(ca_path));
+        assert!(tls.client_key_path.is_none());
+        assert_eq!(tls.client_p12_password.as_deref(), Some("secret"));
+        assert!(tls.danger_accept_invalid_certs);
+
+        // 文件不存在时报错信息包含变量名
+        let err = TlsConfig::from_vars(vars(vec![(
+            "VLAUDE_CLIENT_CERT",
+            "/nonexistent/client.p12".to_string(),
+        )]))
+        .unwrap_err();
+        assert!(matches!(&err, SocketError::TlsError(msg) if msg.contains("VLAUDE_CLIENT_CERT")));
+
+        let err = TlsConfig::from_vars(vars(vec![("VLAUDE_INSECURE", "maybe".to_string())]));
+        assert!(matches!(err, Err(SocketError::TlsError(_))));
+    }
+
+    #[test]
+    fn test_reconnect_policy_backoff() {
+        let policy = ReconnectPolicy {
+            max_attempts: Some(3),
+            base_delay_ms: 1000,
+            max_delay_ms: 5000,
+            jitter_percent: 0,
+        };
+        assert_eq!(policy.delay_for(1).as_millis(), 1000);
+        assert_eq!(policy.delay_for(2).as_millis(), 2000);
+        assert_eq!(policy.delay_for(4).as_millis(), 5000);
+        assert_eq!(policy.delay_for(u32::MAX).as_millis(), 5000);
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+
+        // 初始等待为 0 时仍然逐次翻倍
+        let zero = ReconnectPolicy { base_delay_ms: 0, ..policy.clone() };
+        assert_eq!(zero.delay_for(1).as_millis(), 1);
+        assert_eq!(zero.delay_for(4).as_millis(), 8);
+
+        // 抖动不超过 ±20%
+        let base = std::time::Duration::from_secs(10);
+        assert_eq!(ReconnectPolicy::with_jitter(base, -20).as_millis(), 8000);
+        assert_eq!(ReconnectPolicy::with_jitter(base, 20).as_millis(), 12000);
+        let jittered = ReconnectPolicy { jitter_percent: 20, ..policy };
+        for _ in 0..100 {
+            let delay = jittered.delay_for(1).as_millis();
+            assert!((800..=1200).contains(&delay));
+        }
+
+        let policy: ReconnectPolicy = serde_json::from_str(r#"{"maxAttempts": null}"#).unwrap();
+        assert!(policy.should_retry(1000));
+        assert_eq!(policy.base_delay_ms, 1000);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_connected() {
+        let client = SocketClient::new(SocketConfig::default());
+        let timeout = std::time::Duration::from_millis(20);
+
+        assert!(matches!(
+            client.wait_until_connected(timeout).await,
+            Err(SocketError::ConnectionFailed(ConnectionError::Timeout))
+        ));
+
+        let state = client.connected.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            state.set(true);
+        });
+        assert!(client
+            .wait_until_connected(std::time::Duration::from_secs(1))
+            .await
+            .is_ok());
+        assert!(client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_events_first() {
+        let client = SocketClient::new(SocketConfig::default());
+        for i in 0..3 {
+            client
+                .event_tx
+                .send(("server:requestSessionMetadata".into(), json!({ "i": i })))
+                .await
+                .unwrap();
+        }
+        client
+            .event_tx
+            .send(("server:approvalResponse".into(), json!({})))
+            .await
+            .unwrap();
+
+        let (event, _) = client.recv_event().await.unwrap();
+        assert_eq!(event, "server:approvalResponse");
+        let (event, data) = client.recv_event().await.unwrap();
+        assert_eq!(event, "server:requestSessionMetadata");
+        assert_eq!(data["i"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_state_transitions() {
+        let client = SocketClient::new(SocketConfig::default());
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+
+        transition(&client.state, ConnectionState::Connecting);
+        assert_eq!(client.state().to_string(), "connecting");
+
+        client.disconnect().await;
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_ping_not_connected() {
+        let client = SocketClient::new(SocketConfig::default());
+        assert!(matches!(client.ping(PING_TIMEOUT).await, Err(SocketError::NotConnected)));
+        assert!(client.latency_stats().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_exactly_once_releases_key_on_failure() {
+        let client = SocketClient::new(SocketConfig::default());
+        let qos = QosLevel::ExactlyOnce { dedup_key: "result:1".to_string() };
+
+        // 未连接时发送失败，key 被释放，之后仍可重试
+        for _ in 0..2 {
+            let result = client.emit_qos("daemon:test", json!({}), qos.clone()).await;
+            assert!(matches!(result, Err(SocketError::NotConnected)));
+        }
+        assert!(client.sent_dedup_keys.read().await.is_empty());
+
+        // 已占用的 key 直接跳过
+        client.sent_dedup_keys.write().await.insert("result:1".to_string());
+        assert!(client.emit_qos("daemon:test", json!({}), qos).await.is_ok());
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let samples = LatencySamples::default();
+        assert!(samples.stats().is_none());
+
+        for ms in 1..=12 {
+            samples.record(std::time::Duration::from_millis(ms * 10));
+        }
+        // 只保留最近 10 个样本（30ms ~ 120ms）
+        let stats = samples.stats().unwrap();
+        assert_eq!(stats.samples, LATENCY_SAMPLE_COUNT);
+        assert_eq!(stats.min, std::time::Duration::from_millis(30));
+        assert_eq!(stats.max, std::time::Duration::from_millis(120));
+        assert_eq!(stats.mean, std::time::Duration::from_millis(75));
+    }
+
+    #[test]
+    fn test_ack_timeout_policy() {
+        let policy = AckTimeoutPolicy::default();
+        assert_eq!(policy.timeout_for("daemon:register").as_secs(), 10);
+        assert_eq!(policy.timeout_for("daemon:sessionCreatedResult").as_secs(), 30);
+        assert_eq!(policy.timeout_for("daemon:unknown").as_secs(), 10);
+
+        // FFI 传入的 JSON：缺省字段使用默认值
+        let policy: AckTimeoutPolicy =
+            serde_json::from_str(r#"{"perEvent": {"daemon:register": 3}}"#).unwrap();
+        assert_eq!(policy.timeout_for("daemon:register").as_secs(), 3);
+        assert_eq!(policy.timeout_for("daemon:projectData").as_secs(), 10);
+    }
+
+    #[test]
+    fn test_shutdown_payload() {
+        let payload = Payload::Text(vec![json!({ "restartDelaySecs": 5 })]);
+        assert_eq!(shutdown_payload(payload)["restartDelaySecs"], 5);
+        assert_eq!(shutdown_payload(Payload::Text(vec![])), json!({}));
+    }
+
     #[test]
     fn test_socket_config_default() {
         let config = SocketConfig::default();
         assert_eq!(config.url, "https://localhost:10005");
-        assert_eq!(config.namespace, "/daemon");
+        assert_eq!(config.namespace.resolve("", "darwin"), "/daemon");
+    }
+
+    #[test]
+    fn test_split_batch() {
+        let event = |i: usize, len: usize| BatchedEvent {
+            event: format!("daemon:sessionMetadata{}", i),
+            data: json!({ "padding": "x".repeat(len) }),
+        };
+        let events: Vec<_> = (0..10).map(|i| event(i, 100)).collect();
+
+        let batches = split_batch(events, 500).unwrap();
+        assert!(batches.len() > 1);
+        for batch in &batches {
+            let envelope = serde_json::to_vec(&BatchPayload { events: batch.clone() }).unwrap();
+            assert!(envelope.len() <= 500);
+        }
+        // 拆分后保持原始顺序
+        let names: Vec<_> = batches.iter().flatten().map(|e| e.event.clone()).collect();
+        let expected: Vec<_> = (0..10).map(|i| format!("daemon:sessionMetadata{}", i)).collect();
+        assert_eq!(names, expected);
+
+        // 超过上限的单个事件独占一个信封
+        let batches = split_batch(vec![event(0, 10), event(1, 1000), event(2, 10)], 500).unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [1, 1, 1]);
+
+        assert!(split_batch(Vec::new(), 500).unwrap().is_empty());
+    }
+
+    /// 记录发送内容的 mock 通道
+    #[derive(Default)]
+    struct MockTransport {
+        sent: std::sync::Mutex<Vec<(String, Value)>>,
+    }
+
+    impl EmitTransport for MockTransport {
+        fn send<'a>(&'a self, event: &'a str, data: Value) -> BoxFuture<'a, Result<(), SocketError>> {
+            self.sent.lock().unwrap().push((event.to_string(), data));
+            futures::future::ready(Ok(())).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_batched_through_transport() {
+        let events = || -> Vec<(String, Value)> {
+            (0..10)
+                .map(|i| (format!("daemon:sessionMetadata{}", i), json!({ "padding": "x".repeat(100) })))
+                .collect()
+        };
+        let expected: Vec<_> = (0..10).map(|i| format!("daemon:sessionMetadata{}", i)).collect();
+
+        // 服务器支持批量：按大小拆分为多个信封，顺序不变
+        let transport = MockTransport::default();
+        send_batched(&transport, None, events(), 500, true).await.unwrap();
+        let sent = transport.sent.into_inner().unwrap();
+        assert!(sent.len() > 1);
+        let mut names = Vec::new();
+        for (event, data) in sent {
+            assert_eq!(event, BATCH_EVENT);
+            assert!(serde_json::to_vec(&data).unwrap().len() <= 500);
+            let payload: BatchPayload = serde_json::from_value(data).unwrap();
+            names.extend(payload.events.into_iter().map(|e| e.event));
+        }
+        assert_eq!(names, expected);
+
+        // 服务器不支持批量：逐个发送原事件
+        let transport = MockTransport::default();
+        send_batched(&transport, None, events(), 500, false).await.unwrap();
+        let names: Vec<_> = transport.sent.into_inner().unwrap().into_iter().map(|(e, _)| e).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_ack_supports_batch() {
+        assert!(ack_supports_batch(&json!({ "success": true, "capabilities": ["batch"] })));
+        assert!(!ack_supports_batch(&json!({ "success": true, "capabilities": [] })));
+        assert!(!ack_supports_batch(&json!({ "success": true })));
+    }
+
+    #[test]
+    fn test_platform_based_resolver() {
+        let namespace = NamespaceConfig::from_resolver_name("platform").unwrap();
+        assert_eq!(namespace.resolve("mac-mini", "darwin"), "/daemon");
+        assert_eq!(namespace.resolve("iphone", "ios"), "/mobile");
+        assert!(NamespaceConfig::from_resolver_name("unknown").is_none());
     }
 }
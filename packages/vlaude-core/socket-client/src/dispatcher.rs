@@ -0,0 +1,113 @@
+//! 事件分发器
+//!
+//! `rust_socketio` 的事件处理器在建立连接时固定在 `Client` 中，连接后无法再添加。
+//! 分发器挂在 `on_any` 上，连接前后都可以按事件名增删处理器。
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 事件处理器
+pub type EventHandler = Arc<dyn Fn(Value) + Send + Sync>;
+
+/// 处理器 ID（用于移除）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlerId(u64);
+
+impl HandlerId {
+    /// 数值形式（供 FFI 返回）
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// 从数值形式还原
+    pub fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+/// 按事件名分发下行事件
+#[derive(Clone, Default)]
+pub struct EventDispatcher {
+    handlers: Arc<Mutex<HashMap<String, Vec<(HandlerId, EventHandler)>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加事件处理器
+    pub fn add_handler(&self, event: &str, handler: EventHandler) -> HandlerId {
+        let id = HandlerId(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        handlers
+            .entry(event.to_string())
+            .or_default()
+            .push((id, handler));
+        id
+    }
+
+    /// 移除事件处理器，返回是否存在
+    pub fn remove_handler(&self, event: &str, id: HandlerId) -> bool {
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(list) = handlers.get_mut(event) else {
+            return false;
+        };
+        let before = list.len();
+        list.retain(|(handler_id, _)| *handler_id != id);
+        let removed = list.len() < before;
+        if list.is_empty() {
+            handlers.remove(event);
+        }
+        removed
+    }
+
+    /// 分发事件，返回调用的处理器数量
+    ///
+    /// 处理器在锁外调用，处理器内部可以再增删处理器。
+    pub fn dispatch(&self, event: &str, data: &Value) -> usize {
+        let handlers: Vec<EventHandler> = {
+            let handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+            match handlers.get(event) {
+                Some(list) => list.iter().map(|(_, handler)| handler.clone()).collect(),
+                None => return 0,
+            }
+        };
+        for handler in &handlers {
+            handler(data.clone());
+        }
+        handlers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_add_dispatch_remove() {
+        let dispatcher = EventDispatcher::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let id = dispatcher.add_handler("server:custom", {
+            let calls = calls.clone();
+            Arc::new(move |data: Value| {
+                assert_eq!(data["n"], 1);
+                calls.fetch_add(1, Ordering::SeqCst);
+            })
+        });
+
+        assert_eq!(dispatcher.dispatch("server:custom", &json!({"n": 1})), 1);
+        assert_eq!(dispatcher.dispatch("server:other", &json!({})), 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert!(dispatcher.remove_handler("server:custom", id));
+        assert!(!dispatcher.remove_handler("server:custom", id));
+        assert_eq!(dispatcher.dispatch("server:custom", &json!({"n": 1})), 0);
+    }
+}
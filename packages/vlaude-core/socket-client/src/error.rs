@@ -5,7 +5,7 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum SocketError {
     #[error("Connection failed: {0}")]
-    ConnectionFailed(String),
+    ConnectionFailed(ConnectionError),
 
     #[error("Not connected")]
     NotConnected,
@@ -16,6 +16,9 @@ pub enum SocketError {
     #[error("Ack timeout")]
     AckTimeout,
 
+    #[error("Reconnect in progress")]
+    Reconnecting,
+
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
 
@@ -31,3 +34,155 @@ pub enum SocketError {
     #[error("Registry error: {0}")]
     RegistryError(String),
 }
+
+impl SocketError {
+    /// 是否为可重试的临时错误
+    ///
+    /// - 网络类错误（连接失败、未连接、发送失败、Ack 超时、重连中、Redis）可重试
+    /// - 配置类错误（无效 URL、序列化、TLS 证书）重试无意义
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            SocketError::ConnectionFailed(e) => e.is_retriable(),
+            SocketError::NotConnected => true,
+            SocketError::EmitFailed(_) => true,
+            SocketError::AckTimeout => true,
+            SocketError::Reconnecting => true,
+            SocketError::InvalidUrl(_) => false,
+            SocketError::SerializationError(_) => false,
+            SocketError::TlsError(_) => false,
+            SocketError::IoError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+            ),
+            SocketError::RegistryError(_) => true,
+        }
+    }
+
+    /// 是否为已知的永久配置错误（无效 URL、本地证书配置无效）
+    ///
+    /// 只有这类错误应当让调用方放弃重连；其余错误即使不可重试也可能在之后恢复。
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, SocketError::InvalidUrl(_) | SocketError::TlsError(_))
+    }
+}
+
+/// 连接失败的具体原因
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionError {
+    #[error("DNS resolution failed: {0}")]
+    DnsResolution(String),
+
+    #[error("Connection refused")]
+    TcpRefused,
+
+    #[error("TLS handshake failed: {0}")]
+    TlsHandshake(String),
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ConnectionError {
+    /// 根据底层错误信息归类（仅用于区分错误码，不影响是否重试）
+    ///
+    /// rust_socketio 只暴露字符串化的传输层错误，只能按错误信息匹配。
+    pub fn from_message(msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        let lower = msg.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+
+        if contains_any(&[
+            "dns error",
+            "failed to lookup address",
+            "name or service not known",
+            "nodename nor servname",
+        ]) {
+            ConnectionError::DnsResolution(msg)
+        } else if contains_any(&["connection refused"]) {
+            ConnectionError::TcpRefused
+        } else if contains_any(&["tls", "ssl", "handshake", "certificate"]) {
+            ConnectionError::TlsHandshake(msg)
+        } else if contains_any(&["timed out", "timeout"]) {
+            ConnectionError::Timeout
+        } else {
+            ConnectionError::Other(msg)
+        }
+    }
+
+    /// 是否为可重试的临时错误
+    ///
+    /// 传输层错误都可能恢复（如 Server 更换证书、网络恢复），无效 URL 在连接前已归为
+    /// `SocketError::InvalidUrl`。
+    pub fn is_retriable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_retriable() {
+        assert!(SocketError::NotConnected.is_retriable());
+        assert!(SocketError::AckTimeout.is_retriable());
+        assert!(SocketError::Reconnecting.is_retriable());
+        assert!(SocketError::ConnectionFailed(ConnectionError::TcpRefused).is_retriable());
+        assert!(SocketError::ConnectionFailed(ConnectionError::Timeout).is_retriable());
+        assert!(SocketError::ConnectionFailed(ConnectionError::TlsHandshake(
+            "invalid peer certificate: UnknownIssuer".into()
+        ))
+        .is_retriable());
+        assert!(!SocketError::InvalidUrl("foo".into()).is_retriable());
+        assert!(!SocketError::TlsError("bad cert".into()).is_retriable());
+        assert!(SocketError::IoError(std::io::ErrorKind::TimedOut.into()).is_retriable());
+        assert!(!SocketError::IoError(std::io::ErrorKind::NotFound.into()).is_retriable());
+    }
+
+    #[test]
+    fn test_is_permanent() {
+        assert!(SocketError::InvalidUrl("foo".into()).is_permanent());
+        assert!(SocketError::TlsError("bad cert".into()).is_permanent());
+        // 按错误信息归类的传输层错误不会导致放弃重连
+        assert!(!SocketError::ConnectionFailed(ConnectionError::from_message(
+            "invalid peer certificate: UnknownIssuer"
+        ))
+        .is_permanent());
+        assert!(!SocketError::IoError(std::io::ErrorKind::NotFound.into()).is_permanent());
+        assert!(!SocketError::SerializationError("bad".into()).is_permanent());
+    }
+
+    #[test]
+    fn test_connection_error_from_message() {
+        assert_eq!(
+            ConnectionError::from_message("Connection refused (os error 61)"),
+            ConnectionError::TcpRefused
+        );
+        assert!(matches!(
+            ConnectionError::from_message("dns error: failed to lookup address information"),
+            ConnectionError::DnsResolution(_)
+        ));
+        assert!(matches!(
+            ConnectionError::from_message("invalid peer certificate: UnknownIssuer"),
+            ConnectionError::TlsHandshake(_)
+        ));
+        assert_eq!(
+            ConnectionError::from_message("operation timed out"),
+            ConnectionError::Timeout
+        );
+        assert_eq!(
+            ConnectionError::from_message("relative URL without a base"),
+            ConnectionError::Other("relative URL without a base".into())
+        );
+    }
+}
@@ -3,7 +3,8 @@
 //! 定义 daemon 和 server 之间的所有事件
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::fmt;
 
 // ==================== 上行事件 (Daemon → Server) ====================
 
@@ -46,6 +47,10 @@ pub enum DaemonEvent {
     /// 上报 Metrics 更新
     #[serde(rename = "daemon:metricsUpdate")]
     MetricsUpdate(MetricsUpdateData),
+
+    /// 合并发送的一批事件（Server 逐个拆开处理）
+    #[serde(rename = "daemon:batch")]
+    Batch(BatchPayload),
 }
 
 // ==================== 下行事件 (Server → Daemon) ====================
@@ -83,6 +88,40 @@ pub enum ServerEvent {
     ServerShutdown,
 }
 
+impl ServerEvent {
+    /// 按事件名和数据解析为类型化事件
+    ///
+    /// 事件名未知或数据与事件不匹配时返回 `UnknownEvent`（保留原始数据）。
+    pub fn try_parse(event_name: &str, data: Value) -> Result<ServerEvent, UnknownEvent> {
+        // 关闭通知带说明数据，单元变体无法直接反序列化
+        if event_name == "server-shutdown" {
+            return Ok(ServerEvent::ServerShutdown);
+        }
+
+        serde_json::from_value(json!({ "event": event_name, "data": &data })).map_err(|_| {
+            UnknownEvent {
+                name: event_name.to_string(),
+                data,
+            }
+        })
+    }
+}
+
+/// 无法识别的 Server 事件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnknownEvent {
+    pub name: String,
+    pub data: Value,
+}
+
+impl fmt::Display for UnknownEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown server event: {}", self.name)
+    }
+}
+
+impl std::error::Error for UnknownEvent {}
+
 // ==================== 数据结构 ====================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,6 +157,25 @@ pub struct SessionMetadataPayload {
     pub project_path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
+    /// 分页前的会话总数
+    #[serde(default)]
+    pub total: usize,
+    /// 本页之后是否还有会话
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+/// `daemon:batch` 信封
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchPayload {
+    pub events: Vec<BatchedEvent>,
+}
+
+/// 信封中的单个事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchedEvent {
+    pub event: String,
+    pub data: Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,7 +197,8 @@ pub struct SessionUnavailableData {
 #[serde(rename_all = "camelCase")]
 pub struct NewMessageData {
     pub session_id: String,
-    pub message: Value,
+    /// 新消息（短时间内的多条消息会合并发送）
+    pub messages: Vec<Value>,
     pub timestamp: String,
 }
 
@@ -147,10 +206,29 @@ pub struct NewMessageData {
 #[serde(rename_all = "camelCase")]
 pub struct MetricsUpdateData {
     pub session_id: String,
-    pub metrics: Value,
+    pub metrics: ContextMetrics,
     pub timestamp: String,
 }
 
+/// 会话上下文 Metrics（来自助手消息的 `usage` 字段）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextMetrics {
+    /// 产生该消息的模型
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 上下文长度（input + cache tokens）
+    pub context_length: u64,
+    /// 上下文窗口大小
+    pub context_window: u64,
+    /// 上下文占用百分比（context_length / context_window）
+    pub context_percentage: f64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+}
+
 // ==================== Server → Daemon Payloads ====================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +236,9 @@ pub struct MetricsUpdateData {
 pub struct RequestProjectDataPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<usize>,
+    /// 最少会话数（缺省为 1，不返回空项目）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_sessions: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
 }
@@ -170,6 +251,8 @@ pub struct RequestSessionMetadataPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
 }
 
@@ -193,6 +276,9 @@ pub struct RequestMessagesPayload {
 pub struct WatchingPayload {
     pub session_id: String,
     pub project_path: String,
+    /// 从指定字节位置回放历史消息（用于补齐离线期间错过的消息）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_position: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -398,3 +484,52 @@ pub struct ServerCommandPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_event_try_parse() {
+        let event = ServerEvent::try_parse(
+            "server:startWatching",
+            json!({"sessionId": "s1", "projectPath": "/p"}),
+        )
+        .unwrap();
+        assert!(matches!(event, ServerEvent::StartWatching(p) if p.session_id == "s1"));
+
+        let event = ServerEvent::try_parse("server-shutdown", json!({"message": "bye"})).unwrap();
+        assert!(matches!(event, ServerEvent::ServerShutdown));
+
+        let unknown = ServerEvent::try_parse("server:newFeature", json!({"x": 1})).unwrap_err();
+        assert_eq!(unknown.name, "server:newFeature");
+        assert_eq!(unknown.data, json!({"x": 1}));
+
+        // 数据不匹配时同样返回原始事件
+        assert!(ServerEvent::try_parse("server:startWatching", json!({})).is_err());
+    }
+
+    #[test]
+    fn test_session_metadata_pagination_fields() {
+        let event = ServerEvent::try_parse(
+            "server:requestSessionMetadata",
+            json!({"projectPath": "/p", "limit": 50, "offset": 100}),
+        )
+        .unwrap();
+        assert!(matches!(
+            event,
+            ServerEvent::RequestSessionMetadata(p) if p.limit == Some(50) && p.offset == Some(100)
+        ));
+
+        let payload = SessionMetadataPayload {
+            sessions: vec![],
+            project_path: None,
+            request_id: None,
+            total: 120,
+            has_more: true,
+        };
+        let json = serde_json::to_value(payload).unwrap();
+        assert_eq!(json["total"], 120);
+        assert_eq!(json["hasMore"], true);
+    }
+}
@@ -2,23 +2,41 @@
 //!
 //! 封装 rust_socketio 提供与 vlaude-server 的通信能力
 
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
+mod channel;
 mod client;
+mod dispatcher;
 mod error;
 mod events;
+mod middleware;
+mod platform;
+mod pool;
 mod registry;
 
-pub use client::{DaemonRegistration, SocketClient, SocketConfig, TlsConfig};
-pub use error::SocketError;
+pub use client::{
+    AckTimeoutPolicy, ConnectionState, DaemonRegistration, LatencyStats, NamespaceConfig,
+    NamespaceResolver, PlatformBasedResolver, QosLevel, ReconnectPolicy, SocketClient,
+    SocketConfig, TlsConfig, DEFAULT_MAX_BATCH_BYTES, PING_TIMEOUT,
+};
+pub use channel::VirtualChannel;
+pub use dispatcher::{EventDispatcher, EventHandler, HandlerId};
+pub use error::{ConnectionError, SocketError};
+pub use middleware::{EmitMiddleware, HmacSigningMiddleware};
+pub use platform::{current_platform, os_to_platform};
+pub use pool::SocketClientPool;
 pub use registry::{
-    DaemonInfo, ServiceEvent, ServiceEventType, ServiceInfo, ServiceRegistry,
-    ServiceRegistryConfig, SessionInfo,
+    DaemonInfo, DaemonSessionsCallback, ServiceEvent, ServiceEventType, ServiceInfo,
+    ServiceRegistry, ServiceRegistryConfig, SessionInfo, WatchHandle,
 };
 pub use events::{
     // 上行事件数据
     RegisterData, OnlineData, OfflineData,
     ProjectDataPayload, SessionMetadataPayload, SessionMessagesPayload,
     SessionAvailableData, SessionUnavailableData,
-    NewMessageData, MetricsUpdateData,
+    NewMessageData, MetricsUpdateData, ContextMetrics,
+    BatchPayload, BatchedEvent,
     // 其他上行事件数据
     NewSessionFoundData, NewSessionNotFoundData,
     WatchStartedData, NewSessionCreatedData, ProjectUpdateData, SessionUpdateData,
@@ -32,5 +50,5 @@ pub use events::{
     ResumeLocalPayload, WatchNewSessionPayload, FindNewSessionPayload,
     SessionDiscoveredPayload, ApprovalResponsePayload, ServerCommandPayload,
     // 事件枚举
-    DaemonEvent, ServerEvent,
+    DaemonEvent, ServerEvent, UnknownEvent,
 };
@@ -0,0 +1,102 @@
+//! Emit 中间件
+//!
+//! 在事件发送前后对数据进行转换或记录（如添加签名、请求 ID）
+
+use crate::error::SocketError;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use tracing::debug;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 签名字段名
+pub const SIGNATURE_FIELD: &str = "_sig";
+
+/// Emit 中间件
+pub trait EmitMiddleware: Send + Sync {
+    /// 发送前调用，可修改事件数据
+    fn before_emit(&self, event: &str, data: &mut Value);
+
+    /// 发送后调用
+    fn after_emit(&self, _event: &str, _result: &Result<(), SocketError>) {}
+}
+
+/// HMAC-SHA256 签名中间件
+///
+/// 对 `event + "\n" + data` 计算签名并写入 `_sig` 字段（十六进制）。
+/// 仅对 JSON 对象签名，其它类型原样发送。
+pub struct HmacSigningMiddleware {
+    key: Vec<u8>,
+}
+
+impl HmacSigningMiddleware {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// 计算签名（不包含 `_sig` 字段本身）
+    pub fn sign(&self, event: &str, data: &Value) -> Option<String> {
+        let mut unsigned = data.clone();
+        if let Some(obj) = unsigned.as_object_mut() {
+            obj.remove(SIGNATURE_FIELD);
+        }
+        let payload = serde_json::to_string(&unsigned).ok()?;
+
+        let mut mac = HmacSha256::new_from_slice(&self.key).ok()?;
+        mac.update(event.as_bytes());
+        mac.update(b"\n");
+        mac.update(payload.as_bytes());
+
+        let digest = mac.finalize().into_bytes();
+        Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+impl EmitMiddleware for HmacSigningMiddleware {
+    fn before_emit(&self, event: &str, data: &mut Value) {
+        if !data.is_object() {
+            debug!("Skip signing non-object payload for {}", event);
+            return;
+        }
+
+        if let Some(sig) = self.sign(event, data) {
+            if let Some(obj) = data.as_object_mut() {
+                obj.insert(SIGNATURE_FIELD.to_string(), Value::String(sig));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hmac_signing_middleware() {
+        let middleware = HmacSigningMiddleware::new("secret");
+
+        let mut data = json!({"sessionId": "abc", "count": 1});
+        middleware.before_emit("daemon:newMessage", &mut data);
+
+        let sig = data[SIGNATURE_FIELD].as_str().unwrap().to_string();
+        assert_eq!(sig.len(), 64);
+
+        // 签名稳定，且可由接收方重新计算校验
+        assert_eq!(middleware.sign("daemon:newMessage", &data).unwrap(), sig);
+
+        // 事件名或密钥不同，签名不同
+        assert_ne!(middleware.sign("daemon:other", &data).unwrap(), sig);
+        let other = HmacSigningMiddleware::new("other-secret");
+        assert_ne!(other.sign("daemon:newMessage", &data).unwrap(), sig);
+    }
+
+    #[test]
+    fn test_hmac_skips_non_object() {
+        let middleware = HmacSigningMiddleware::new("secret");
+        let mut data = json!("plain");
+        middleware.before_emit("daemon:online", &mut data);
+        assert_eq!(data, json!("plain"));
+    }
+}
@@ -0,0 +1,28 @@
+//! 平台检测
+//!
+//! Server 约定 macOS 上报为 "darwin"，其余平台沿用 `std::env::consts::OS`。
+
+/// 将 `std::env::consts::OS` 映射为 Server 约定的平台名
+pub fn os_to_platform(os: &str) -> &str {
+    match os {
+        "macos" => "darwin",
+        other => other,
+    }
+}
+
+/// 当前运行平台
+pub fn current_platform() -> &'static str {
+    os_to_platform(std::env::consts::OS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_os_to_platform() {
+        assert_eq!(os_to_platform("macos"), "darwin");
+        assert_eq!(os_to_platform("linux"), "linux");
+        assert_eq!(os_to_platform("windows"), "windows");
+    }
+}
@@ -0,0 +1,139 @@
+//! Socket 连接池
+//!
+//! 按命名空间管理多个 SocketClient，不同命名空间可以使用不同的重连策略。
+
+use crate::client::{NamespaceConfig, ReconnectPolicy, SocketClient, SocketConfig};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// 按命名空间管理的 Socket 连接池
+pub struct SocketClientPool {
+    /// 创建新连接时使用的基础配置（命名空间和重连策略会被覆盖）
+    base_config: SocketConfig,
+    /// 命名空间 → 客户端
+    sockets: Mutex<HashMap<String, Arc<SocketClient>>>,
+    /// 未单独配置的命名空间使用的重连策略
+    default_policy: ReconnectPolicy,
+    /// 命名空间 → 重连策略
+    policies: HashMap<String, ReconnectPolicy>,
+}
+
+impl SocketClientPool {
+    /// 创建连接池（默认重连策略取自 `base_config.reconnect`）
+    pub fn new(base_config: SocketConfig) -> Self {
+        let default_policy = base_config.reconnect.clone();
+        Self {
+            base_config,
+            sockets: Mutex::new(HashMap::new()),
+            default_policy,
+            policies: HashMap::new(),
+        }
+    }
+
+    /// 设置默认重连策略
+    pub fn with_default_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.default_policy = policy;
+        self
+    }
+
+    /// 为指定命名空间设置重连策略（仅影响之后创建的连接）
+    pub fn with_policy(mut self, namespace: impl Into<String>, policy: ReconnectPolicy) -> Self {
+        self.policies.insert(namespace.into(), policy);
+        self
+    }
+
+    /// 获取命名空间使用的重连策略
+    pub fn policy_for(&self, namespace: &str) -> &ReconnectPolicy {
+        self.policies.get(namespace).unwrap_or(&self.default_policy)
+    }
+
+    /// 获取命名空间对应的客户端（不存在时创建，不会自动连接）
+    pub fn get_or_create(&self, namespace: &str) -> Arc<SocketClient> {
+        let mut sockets = self.sockets.lock().unwrap_or_else(|e| e.into_inner());
+        sockets
+            .entry(namespace.to_string())
+            .or_insert_with(|| {
+                let config = SocketConfig {
+                    namespace: NamespaceConfig::Static(namespace.to_string()),
+                    reconnect: self.policy_for(namespace).clone(),
+                    ..self.base_config.clone()
+                };
+                Arc::new(SocketClient::new(config))
+            })
+            .clone()
+    }
+
+    /// 当前池中的所有客户端
+    pub fn sockets(&self) -> Vec<(String, Arc<SocketClient>)> {
+        let sockets = self.sockets.lock().unwrap_or_else(|e| e.into_inner());
+        sockets
+            .iter()
+            .map(|(namespace, client)| (namespace.clone(), client.clone()))
+            .collect()
+    }
+
+    /// 向所有已连接的客户端发送事件，返回发送成功的数量
+    pub async fn broadcast(&self, event: &str, data: Value) -> usize {
+        let mut sent = 0;
+        for (namespace, client) in self.sockets() {
+            if !client.is_connected() {
+                continue;
+            }
+            match client.emit(event, data.clone()).await {
+                Ok(()) => sent += 1,
+                Err(e) => warn!("[SocketClientPool] Broadcast {} to {} failed: {}", event, namespace, e),
+            }
+        }
+        sent
+    }
+
+    /// 断开所有客户端
+    pub async fn disconnect_all(&self) {
+        for (_, client) in self.sockets() {
+            client.disconnect().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_or_create_reuses_client() {
+        let pool = SocketClientPool::new(SocketConfig::default());
+        let a = pool.get_or_create("/daemon");
+        let b = pool.get_or_create("/daemon");
+        let c = pool.get_or_create("/swift");
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(pool.sockets().len(), 2);
+    }
+
+    #[test]
+    fn test_per_namespace_policy() {
+        let strict = ReconnectPolicy {
+            max_attempts: None,
+            base_delay_ms: 200,
+            max_delay_ms: 5_000,
+            jitter_percent: 0,
+        };
+        let pool = SocketClientPool::new(SocketConfig::default())
+            .with_policy("/daemon", strict.clone());
+
+        assert_eq!(pool.policy_for("/daemon"), &strict);
+        assert_eq!(pool.policy_for("/swift"), &ReconnectPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_skips_disconnected() {
+        let pool = SocketClientPool::new(SocketConfig::default());
+        pool.get_or_create("/daemon");
+        pool.get_or_create("/swift");
+
+        assert_eq!(pool.broadcast("daemon:ping", serde_json::json!({})).await, 0);
+    }
+}
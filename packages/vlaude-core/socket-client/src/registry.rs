@@ -6,10 +6,44 @@ use anyhow::{Context, Result};
 use redis::aio::MultiplexedConnection;
 use redis::{AsyncCommands, Client};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 
+/// 延迟测量结果缓存时间
+const LATENCY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// TCP 探测超时
+const LATENCY_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 降级模式下重连 Redis 的最小间隔
+const FALLBACK_RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Redis 未连接
+#[derive(Debug, thiserror::Error)]
+#[error("Not connected to Redis")]
+struct RedisNotConnected;
+
+/// 是否为 Redis 不可用错误（需要降级到本地文件）
+///
+/// 业务错误（如 Daemon 不存在）不触发降级。
+fn is_redis_unavailable(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<RedisNotConnected>().is_some()
+        || err.downcast_ref::<redis::RedisError>().is_some_and(|e| {
+            e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout()
+        })
+}
+
+fn now_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
 /// 服务事件类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -70,6 +104,67 @@ pub struct DaemonInfo {
     pub registered_at: u64,
 }
 
+// ==================== 本地文件降级 ====================
+
+/// 降级文件中的服务记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FallbackService {
+    service: String,
+    info: ServiceInfo,
+    expires_at: u64,
+}
+
+/// 降级文件中的 Daemon 记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FallbackDaemon {
+    info: DaemonInfo,
+    ttl: u64,
+    expires_at: u64,
+}
+
+/// Redis 不可用时写入本地 JSON 文件的注册状态
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FallbackState {
+    /// "service:address" → 服务
+    #[serde(default)]
+    services: HashMap<String, FallbackService>,
+    /// device_id → Daemon
+    #[serde(default)]
+    daemons: HashMap<String, FallbackDaemon>,
+}
+
+impl FallbackState {
+    /// 读取文件（不存在时为空），并清理已过期记录
+    fn load(path: &Path, now: u64) -> Result<Self> {
+        let mut state: Self = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid registry fallback file {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        state.services.retain(|_, s| s.expires_at > now);
+        state.daemons.retain(|_, d| d.expires_at > now);
+        Ok(state)
+    }
+
+    /// 写入文件（先写临时文件再重命名，避免读到半截内容）
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.services.is_empty() && self.daemons.is_empty()
+    }
+}
+
 /// Redis 服务注册中心配置
 #[derive(Debug, Clone)]
 pub struct ServiceRegistryConfig {
@@ -77,6 +172,8 @@ pub struct ServiceRegistryConfig {
     pub port: u16,
     pub password: Option<String>,
     pub key_prefix: String,
+    /// 跳过延迟测量（禁止 TCP 探测的环境）
+    pub skip_latency_measurement: bool,
 }
 
 impl Default for ServiceRegistryConfig {
@@ -90,10 +187,32 @@ impl Default for ServiceRegistryConfig {
                 .unwrap_or(6379),
             password: std::env::var("REDIS_PASSWORD").ok(),
             key_prefix: "vlaude:".to_string(),
+            skip_latency_measurement: false,
         }
     }
 }
 
+/// Session 列表变化回调
+pub type DaemonSessionsCallback = Arc<dyn Fn(Vec<SessionInfo>) + Send + Sync>;
+
+/// `watch_daemon_sessions` 返回的订阅句柄，drop 时取消订阅
+pub struct WatchHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl WatchHandle {
+    /// 取消订阅
+    pub fn unsubscribe(self) {
+        // 由 Drop 完成
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 /// Redis 服务注册中心
 pub struct ServiceRegistry {
     client: Client,
@@ -101,6 +220,18 @@ pub struct ServiceRegistry {
     config: ServiceRegistryConfig,
     channel: String,
     event_tx: broadcast::Sender<ServiceEvent>,
+    /// 延迟缓存（address → (延迟, 测量时间)），探测失败记为 None
+    latency_cache: Arc<RwLock<HashMap<String, (Option<Duration>, Instant)>>>,
+    /// 本地降级文件路径（None 表示不降级）
+    fallback_path: Option<PathBuf>,
+    /// 是否处于降级模式（最近一次操作写入了本地文件）
+    degraded: Arc<AtomicBool>,
+    /// 串行化降级文件读写
+    fallback_lock: Arc<Mutex<()>>,
+    /// 降级模式下最近一次尝试重连 Redis 的时间
+    last_reconnect_attempt: Arc<RwLock<Option<Instant>>>,
+    /// 本机 Daemon 的设备 ID（选举 Server 时用于打破平局）
+    device_id: Option<String>,
 }
 
 impl ServiceRegistry {
@@ -122,11 +253,53 @@ impl ServiceRegistry {
             config,
             channel,
             event_tx,
+            latency_cache: Arc::new(RwLock::new(HashMap::new())),
+            fallback_path: None,
+            degraded: Arc::new(AtomicBool::new(false)),
+            fallback_lock: Arc::new(Mutex::new(())),
+            last_reconnect_attempt: Arc::new(RwLock::new(None)),
+            device_id: None,
         })
     }
 
+    /// 启用本地文件降级
+    ///
+    /// Redis 不可用时注册/发现操作读写 `fallback_path`（单机模式），
+    /// Redis 恢复后把文件中的状态同步回 Redis。
+    pub fn with_file_fallback(mut self, fallback_path: PathBuf) -> Self {
+        self.fallback_path = Some(fallback_path);
+        self
+    }
+
+    /// 设置本机设备 ID
+    ///
+    /// `elect_primary_server` 遇到多个同样合适的 Server 时按设备 ID 做一致性哈希，
+    /// 同一台设备总是选中同一个 Server，不同设备分散到不同 Server。
+    pub fn with_device_id(mut self, device_id: String) -> Self {
+        self.device_id = Some(device_id);
+        self
+    }
+
+    /// 是否处于降级模式
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::SeqCst)
+    }
+
     /// 连接 Redis
+    ///
+    /// 启用本地文件降级时连接失败不报错，进入降级模式。
     pub async fn connect(&self) -> Result<()> {
+        match self.connect_redis().await {
+            Err(e) if self.fallback_path.is_some() => {
+                warn!("[ServiceRegistry] {:#}, running in file fallback mode", e);
+                self.degraded.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    async fn connect_redis(&self) -> Result<()> {
         let conn = self
             .client
             .get_multiplexed_async_connection()
@@ -142,12 +315,124 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    /// 是否已连接 Redis
+    pub async fn is_connected(&self) -> bool {
+        self.conn.read().await.is_some()
+    }
+
     /// 获取连接
+    ///
+    /// 降级模式下连接已断开时，按间隔尝试重连 Redis。
     async fn get_conn(&self) -> Result<MultiplexedConnection> {
-        let guard = self.conn.read().await;
-        guard
-            .clone()
-            .ok_or_else(|| anyhow::anyhow!("Not connected to Redis"))
+        if let Some(conn) = self.conn.read().await.clone() {
+            return Ok(conn);
+        }
+
+        if self.is_degraded() {
+            let mut last_attempt = self.last_reconnect_attempt.write().await;
+            let due = match *last_attempt {
+                Some(at) => at.elapsed() >= FALLBACK_RECONNECT_INTERVAL,
+                None => true,
+            };
+            if due {
+                *last_attempt = Some(Instant::now());
+                drop(last_attempt);
+                if self.connect_redis().await.is_ok() {
+                    if let Some(conn) = self.conn.read().await.clone() {
+                        return Ok(conn);
+                    }
+                }
+            }
+        }
+
+        Err(RedisNotConnected.into())
+    }
+
+    /// Redis 操作失败时降级到本地文件
+    ///
+    /// - Redis 可用：如果之前处于降级模式，把文件状态同步回 Redis
+    /// - Redis 不可用且启用了降级：在文件状态上执行 `op`，写操作后保存
+    async fn or_fallback<T>(
+        &self,
+        result: Result<T>,
+        op: impl FnOnce(&mut FallbackState, u64) -> Result<T>,
+    ) -> Result<T> {
+        let (path, err) = match (result, &self.fallback_path) {
+            (Err(e), Some(path)) if is_redis_unavailable(&e) => (path, e),
+            // Redis 可用（包括业务错误）
+            (result, _) => {
+                if self.is_degraded() {
+                    self.flush_fallback().await;
+                }
+                return result;
+            }
+        };
+
+        if !self.degraded.swap(true, Ordering::SeqCst) {
+            warn!(
+                "[ServiceRegistry] Redis unavailable ({:#}), falling back to {:?}",
+                err, path
+            );
+        }
+        // 丢弃可能已失效的连接，后续按间隔重连
+        *self.conn.write().await = None;
+
+        let _guard = self.fallback_lock.lock().await;
+        let now = now_millis();
+        let mut state = FallbackState::load(path, now)?;
+        let value = op(&mut state, now)?;
+        state.save(path)?;
+        Ok(value)
+    }
+
+    /// 把降级文件中的状态同步回 Redis，全部成功后清空文件并退出降级模式
+    async fn flush_fallback(&self) {
+        let Some(path) = &self.fallback_path else {
+            return;
+        };
+
+        let _guard = self.fallback_lock.lock().await;
+        let now = now_millis();
+        let state = match FallbackState::load(path, now) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("[ServiceRegistry] Failed to load fallback state: {:#}", e);
+                return;
+            }
+        };
+
+        let remaining_ttl = |expires_at: u64| (expires_at.saturating_sub(now) / 1000).max(1);
+        let mut flushed = true;
+        for entry in state.services.values() {
+            let ttl = remaining_ttl(entry.expires_at);
+            if let Err(e) = self.redis_register(&entry.service, &entry.info.address, ttl).await {
+                warn!("[ServiceRegistry] Failed to flush service {}: {:#}", entry.info.address, e);
+                flushed = false;
+            }
+        }
+        for entry in state.daemons.values() {
+            let ttl = remaining_ttl(entry.expires_at);
+            if let Err(e) = self.redis_register_daemon(&entry.info, ttl).await {
+                warn!("[ServiceRegistry] Failed to flush daemon {}: {:#}", entry.info.device_id, e);
+                flushed = false;
+            }
+        }
+
+        if !flushed {
+            return;
+        }
+        if !state.is_empty() {
+            info!(
+                "[ServiceRegistry] Redis recovered, flushed {} services and {} daemons from {:?}",
+                state.services.len(),
+                state.daemons.len(),
+                path
+            );
+        }
+        if let Err(e) = FallbackState::default().save(path) {
+            warn!("[ServiceRegistry] Failed to clear fallback file: {:#}", e);
+        }
+        self.degraded.store(false, Ordering::SeqCst);
     }
 
     /// 构建服务 Key
@@ -157,6 +442,26 @@ impl ServiceRegistry {
 
     /// 注册服务（用于 Server）
     pub async fn register(&self, service: &str, address: &str, ttl: u64) -> Result<()> {
+        let result = self.redis_register(service, address, ttl).await;
+        self.or_fallback(result, |state, now| {
+            state.services.insert(
+                format!("{}:{}", service, address),
+                FallbackService {
+                    service: service.to_string(),
+                    info: ServiceInfo {
+                        address: address.to_string(),
+                        ttl,
+                        registered_at: now,
+                    },
+                    expires_at: now + ttl * 1000,
+                },
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redis_register(&self, service: &str, address: &str, ttl: u64) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_service_key(service, address);
 
@@ -192,6 +497,15 @@ impl ServiceRegistry {
 
     /// 注销服务（用于 Server）
     pub async fn unregister(&self, service: &str, address: &str) -> Result<()> {
+        let result = self.redis_unregister(service, address).await;
+        self.or_fallback(result, |state, _| {
+            state.services.remove(&format!("{}:{}", service, address));
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redis_unregister(&self, service: &str, address: &str) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_service_key(service, address);
 
@@ -224,6 +538,22 @@ impl ServiceRegistry {
 
     /// 注册 Daemon（用于 VlaudeKit / vlaude-daemon-rs）
     pub async fn register_daemon(&self, info: &DaemonInfo, ttl: u64) -> Result<()> {
+        let result = self.redis_register_daemon(info, ttl).await;
+        self.or_fallback(result, |state, now| {
+            state.daemons.insert(
+                info.device_id.clone(),
+                FallbackDaemon {
+                    info: info.clone(),
+                    ttl,
+                    expires_at: now + ttl * 1000,
+                },
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redis_register_daemon(&self, info: &DaemonInfo, ttl: u64) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_daemon_key(&info.device_id);
 
@@ -254,6 +584,15 @@ impl ServiceRegistry {
 
     /// 注销 Daemon
     pub async fn unregister_daemon(&self, device_id: &str) -> Result<()> {
+        let result = self.redis_unregister_daemon(device_id).await;
+        self.or_fallback(result, |state, _| {
+            state.daemons.remove(device_id);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redis_unregister_daemon(&self, device_id: &str) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_daemon_key(device_id);
 
@@ -279,6 +618,19 @@ impl ServiceRegistry {
 
     /// 续期 Daemon
     pub async fn keep_alive_daemon(&self, device_id: &str, ttl: u64) -> Result<()> {
+        let result = self.redis_keep_alive_daemon(device_id, ttl).await;
+        self.or_fallback(result, |state, now| match state.daemons.get_mut(device_id) {
+            Some(entry) => {
+                entry.ttl = ttl;
+                entry.expires_at = now + ttl * 1000;
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("Daemon not found, need re-register")),
+        })
+        .await
+    }
+
+    async fn redis_keep_alive_daemon(&self, device_id: &str, ttl: u64) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_daemon_key(device_id);
 
@@ -303,6 +655,27 @@ impl ServiceRegistry {
         device_id: &str,
         sessions: Vec<SessionInfo>,
         ttl: u64,
+    ) -> Result<()> {
+        let result = self
+            .redis_update_daemon_sessions(device_id, sessions.clone(), ttl)
+            .await;
+        self.or_fallback(result, |state, now| match state.daemons.get_mut(device_id) {
+            Some(entry) => {
+                entry.info.sessions = sessions;
+                entry.ttl = ttl;
+                entry.expires_at = now + ttl * 1000;
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("Daemon not found")),
+        })
+        .await
+    }
+
+    async fn redis_update_daemon_sessions(
+        &self,
+        device_id: &str,
+        sessions: Vec<SessionInfo>,
+        ttl: u64,
     ) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_daemon_key(device_id);
@@ -345,6 +718,14 @@ impl ServiceRegistry {
 
     /// 获取所有 Daemon
     pub async fn get_daemons(&self) -> Result<Vec<DaemonInfo>> {
+        let result = self.redis_get_daemons().await;
+        self.or_fallback(result, |state, _| {
+            Ok(state.daemons.values().map(|d| d.info.clone()).collect())
+        })
+        .await
+    }
+
+    async fn redis_get_daemons(&self) -> Result<Vec<DaemonInfo>> {
         let mut conn = self.get_conn().await?;
         let pattern = format!("{}services:daemon:*", self.config.key_prefix);
 
@@ -372,8 +753,32 @@ impl ServiceRegistry {
         Ok(daemons)
     }
 
+    /// 获取所有 Daemon 当前打开的 Session（session_id → device_id）
+    ///
+    /// 同一 Session 出现在多个设备上时，取最后注册的设备。
+    pub async fn list_all_daemons_with_sessions(&self) -> Result<HashMap<String, String>> {
+        let mut daemons = self.get_daemons().await?;
+        daemons.sort_by_key(|d| d.registered_at);
+
+        let mut sessions = HashMap::new();
+        for daemon in daemons {
+            for session in daemon.sessions {
+                sessions.insert(session.session_id, daemon.device_id.clone());
+            }
+        }
+        Ok(sessions)
+    }
+
     /// 获取指定 Daemon
     pub async fn get_daemon(&self, device_id: &str) -> Result<Option<DaemonInfo>> {
+        let result = self.redis_get_daemon(device_id).await;
+        self.or_fallback(result, |state, _| {
+            Ok(state.daemons.get(device_id).map(|d| d.info.clone()))
+        })
+        .await
+    }
+
+    async fn redis_get_daemon(&self, device_id: &str) -> Result<Option<DaemonInfo>> {
         let mut conn = self.get_conn().await?;
         let key = self.build_daemon_key(device_id);
 
@@ -386,6 +791,27 @@ impl ServiceRegistry {
 
     /// 续期服务
     pub async fn keep_alive(&self, service: &str, address: &str, ttl: u64) -> Result<()> {
+        let result = self.redis_keep_alive(service, address, ttl).await;
+        self.or_fallback(result, |state, now| {
+            let entry = state
+                .services
+                .entry(format!("{}:{}", service, address))
+                .or_insert_with(|| FallbackService {
+                    service: service.to_string(),
+                    info: ServiceInfo {
+                        address: address.to_string(),
+                        ttl,
+                        registered_at: now,
+                    },
+                    expires_at: now,
+                });
+            entry.expires_at = now + ttl * 1000;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redis_keep_alive(&self, service: &str, address: &str, ttl: u64) -> Result<()> {
         let mut conn = self.get_conn().await?;
         let key = self.build_service_key(service, address);
 
@@ -394,7 +820,7 @@ impl ServiceRegistry {
             conn.expire::<_, ()>(&key, ttl as i64).await?;
         } else {
             // Key 不存在，重新注册
-            self.register(service, address, ttl).await?;
+            self.redis_register(service, address, ttl).await?;
         }
 
         Ok(())
@@ -402,6 +828,40 @@ impl ServiceRegistry {
 
     /// 获取所有 Server，按优先级排序
     pub async fn get_servers(&self) -> Result<Vec<String>> {
+        let result = self.redis_get_server_addresses().await;
+        let mut addresses = self
+            .or_fallback(result, |state, _| {
+                Ok(state
+                    .services
+                    .values()
+                    .filter(|s| s.service == "server")
+                    .map(|s| s.info.address.clone())
+                    .collect())
+            })
+            .await?;
+
+        // 按优先级排序，同一优先级内按延迟排序
+        let latencies = self.measure_latencies(&addresses).await;
+        self.sort_by_priority(&mut addresses, &latencies);
+
+        Ok(addresses)
+    }
+
+    /// 并发测量各地址的延迟（配置了 `skip_latency_measurement` 时不测量），失败的不包含在结果中
+    async fn measure_latencies(&self, addresses: &[String]) -> HashMap<String, Duration> {
+        if self.config.skip_latency_measurement {
+            return HashMap::new();
+        }
+        let results =
+            futures::future::join_all(addresses.iter().map(|addr| self.measure_latency(addr))).await;
+        addresses
+            .iter()
+            .zip(results)
+            .filter_map(|(addr, latency)| latency.map(|l| (addr.clone(), l)))
+            .collect()
+    }
+
+    async fn redis_get_server_addresses(&self) -> Result<Vec<String>> {
         let mut conn = self.get_conn().await?;
         let pattern = self.build_service_key("server", "*");
 
@@ -426,17 +886,174 @@ impl ServiceRegistry {
             }
         }
 
-        // 按优先级排序
-        self.sort_by_priority(&mut addresses);
-
         Ok(addresses)
     }
 
+    /// 选举要连接的 Server
+    ///
+    /// 先比较地址优先级（见 `get_priority`），再比较延迟（按 10ms 分档，未测量的最后），
+    /// 然后比较 key 的剩余 TTL（降序，刚注册或续期的 Server 优先）。剩余 TTL 按秒比较，
+    /// 仍然并列时按设备 ID 一致性哈希选择。没有 Server 时返回 None。
+    pub async fn elect_primary_server(&self) -> Result<Option<String>> {
+        let result = self.redis_get_servers_with_ttl().await;
+        let servers = self
+            .or_fallback(result, |state, now| {
+                Ok(state
+                    .services
+                    .values()
+                    .filter(|s| s.service == "server")
+                    .map(|s| (s.info.address.clone(), s.expires_at.saturating_sub(now)))
+                    .collect())
+            })
+            .await?;
+
+        let addresses: Vec<String> = servers.iter().map(|(address, _)| address.clone()).collect();
+        let latencies = self.measure_latencies(&addresses).await;
+        let elected = self.elect(servers, &latencies, self.device_id.as_deref());
+        if let Some(ref address) = elected {
+            debug!("[ServiceRegistry] Elected primary server: {}", address);
+        }
+        Ok(elected)
+    }
+
+    /// 从候选 (地址, 剩余 TTL 毫秒) 中选出一个
+    fn elect(
+        &self,
+        servers: Vec<(String, u64)>,
+        latencies: &HashMap<String, Duration>,
+        device_id: Option<&str>,
+    ) -> Option<String> {
+        let latency_bucket = |address: &String| {
+            latencies
+                .get(address)
+                .map_or(u128::MAX, |latency| latency.as_millis() / 10)
+        };
+        let rank = |(address, ttl_ms): &(String, u64)| {
+            (
+                self.get_priority(address),
+                Reverse(latency_bucket(address)),
+                ttl_ms / 1000,
+            )
+        };
+        let best = servers.iter().map(rank).max()?;
+        servers
+            .iter()
+            .filter(|server| rank(server) == best)
+            .map(|(address, _)| address)
+            .max_by_key(|address| {
+                // 没有设备 ID 时取地址最小的一个
+                let weight = device_id.map_or(0, |id| stable_hash(&format!("{}|{}", id, address)));
+                (weight, Reverse(address.as_str()))
+            })
+            .cloned()
+    }
+
+    /// 获取所有 Server 及其 key 的剩余 TTL（毫秒，没有过期时间时为 u64::MAX）
+    async fn redis_get_servers_with_ttl(&self) -> Result<Vec<(String, u64)>> {
+        let mut conn = self.get_conn().await?;
+        let pattern = self.build_service_key("server", "*");
+
+        let keys: Vec<String> = redis::cmd("KEYS")
+            .arg(&pattern)
+            .query_async(&mut conn)
+            .await
+            .context("Failed to get server keys")?;
+
+        let mut servers = Vec::new();
+        for key in keys {
+            let Ok(Some(value)) = conn.get::<_, Option<String>>(&key).await else {
+                continue;
+            };
+            let Ok(info) = serde_json::from_str::<ServiceInfo>(&value) else {
+                continue;
+            };
+            let pttl: i64 = redis::cmd("PTTL")
+                .arg(&key)
+                .query_async(&mut conn)
+                .await
+                .context("Failed to get server key TTL")?;
+            // -2：key 已过期；-1：没有过期时间
+            match pttl {
+                -2 => continue,
+                -1 => servers.push((info.address, u64::MAX)),
+                ttl => servers.push((info.address, ttl.max(0) as u64)),
+            }
+        }
+
+        Ok(servers)
+    }
+
+    /// 测量到指定地址的 TCP 握手延迟（结果缓存 60 秒）
+    ///
+    /// 连接失败或超时返回 None。
+    pub async fn measure_latency(&self, address: &str) -> Option<Duration> {
+        if let Some((latency, measured_at)) = self.latency_cache.read().await.get(address) {
+            if measured_at.elapsed() < LATENCY_CACHE_TTL {
+                return *latency;
+            }
+        }
+
+        let start = Instant::now();
+        let probe = tokio::time::timeout(LATENCY_PROBE_TIMEOUT, TcpStream::connect(address));
+        let latency = match probe.await {
+            Ok(Ok(_)) => Some(start.elapsed()),
+            Ok(Err(e)) => {
+                debug!("[ServiceRegistry] Latency probe to {} failed: {}", address, e);
+                None
+            }
+            Err(_) => {
+                debug!("[ServiceRegistry] Latency probe to {} timed out", address);
+                None
+            }
+        };
+
+        self.latency_cache
+            .write()
+            .await
+            .insert(address.to_string(), (latency, Instant::now()));
+
+        latency
+    }
+
     /// 订阅服务事件
     pub fn subscribe(&self) -> broadcast::Receiver<ServiceEvent> {
         self.event_tx.subscribe()
     }
 
+    /// 监听指定 Daemon 的 Session 列表变化
+    ///
+    /// 收到该 Daemon 的 session_update 事件时以新的列表调用 `callback`。
+    /// 事件来自 Redis PubSub，需要先调用 `start_listening`。
+    pub fn watch_daemon_sessions(
+        &self,
+        device_id: String,
+        callback: DaemonSessionsCallback,
+    ) -> WatchHandle {
+        let mut rx = self.event_tx.subscribe();
+        let task = tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if event.event_type != ServiceEventType::SessionUpdate
+                            || event.device_id.as_deref() != Some(device_id.as_str())
+                        {
+                            continue;
+                        }
+                        callback(event.sessions.unwrap_or_default());
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "[ServiceRegistry] Session watcher for {} lagged, skipped {} events",
+                            device_id, skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        WatchHandle { task }
+    }
+
     /// 启动事件监听（在后台 task 中运行）
     pub async fn start_listening(&self) -> Result<()> {
         let client = self.client.clone();
@@ -501,11 +1118,14 @@ impl ServiceRegistry {
     /// 1. localhost:* 最高
     /// 2. 192.168.*:* 次之
     /// 3. 域名最低
-    fn sort_by_priority(&self, addresses: &mut Vec<String>) {
-        addresses.sort_by(|a, b| {
-            let priority_a = self.get_priority(a);
-            let priority_b = self.get_priority(b);
-            priority_b.cmp(&priority_a) // 降序
+    ///
+    /// 同一优先级内按延迟升序，未测量的排在最后。
+    fn sort_by_priority(&self, addresses: &mut [String], latencies: &HashMap<String, Duration>) {
+        addresses.sort_by_key(|addr| {
+            (
+                Reverse(self.get_priority(addr)), // 降序
+                latencies.get(addr).copied().unwrap_or(Duration::MAX),
+            )
         });
     }
 
@@ -534,3 +1154,241 @@ impl ServiceRegistry {
 }
 
 use futures::StreamExt;
+
+/// 跨进程、跨版本稳定的字符串哈希（FNV-1a），用于一致性选择
+fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 指向不可用 Redis 的注册中心（连接会被立即拒绝）
+    fn unreachable_registry(fallback_path: PathBuf) -> ServiceRegistry {
+        let config = ServiceRegistryConfig {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            password: None,
+            key_prefix: "vlaude-test:".to_string(),
+            skip_latency_measurement: true,
+        };
+        ServiceRegistry::new(config).unwrap().with_file_fallback(fallback_path)
+    }
+
+    #[tokio::test]
+    async fn test_watch_daemon_sessions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = unreachable_registry(dir.path().join("registry.json"));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = registry.watch_daemon_sessions(
+            "device-1".to_string(),
+            Arc::new(move |sessions| {
+                let _ = tx.send(sessions);
+            }),
+        );
+
+        let session_update = |device_id: &str, session_id: &str| ServiceEvent {
+            event_type: ServiceEventType::SessionUpdate,
+            service: "daemon".to_string(),
+            address: None,
+            device_id: Some(device_id.to_string()),
+            sessions: Some(vec![SessionInfo {
+                session_id: session_id.to_string(),
+                project_path: "/tmp/demo".to_string(),
+            }]),
+            timestamp: now_millis(),
+        };
+        registry.event_tx.send(session_update("device-2", "other")).unwrap();
+        registry.event_tx.send(session_update("device-1", "mine")).unwrap();
+
+        let sessions = rx.recv().await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "mine");
+
+        handle.unsubscribe();
+        tokio::task::yield_now().await;
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_fallback_when_redis_unavailable() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("registry.json");
+        let registry = unreachable_registry(path.clone());
+
+        // 连接失败进入降级模式而不是报错
+        registry.connect().await.unwrap();
+        assert!(registry.is_degraded());
+
+        let daemon = DaemonInfo {
+            device_id: "mac-1".to_string(),
+            device_name: "mac".to_string(),
+            platform: "darwin".to_string(),
+            version: "0.1.0".to_string(),
+            sessions: vec![],
+            registered_at: 0,
+        };
+        registry.register_daemon(&daemon, 60).await.unwrap();
+        registry.register("server", "localhost:10005", 60).await.unwrap();
+        assert!(path.is_file());
+
+        let found = registry.get_daemon("mac-1").await.unwrap().unwrap();
+        assert_eq!(found.device_name, "mac");
+        assert_eq!(registry.get_servers().await.unwrap(), vec!["localhost:10005"]);
+
+        registry
+            .update_daemon_sessions(
+                "mac-1",
+                vec![SessionInfo {
+                    session_id: "s1".to_string(),
+                    project_path: "/tmp/demo".to_string(),
+                }],
+                60,
+            )
+            .await
+            .unwrap();
+        assert_eq!(registry.get_daemons().await.unwrap()[0].sessions.len(), 1);
+
+        // 业务错误照常返回
+        assert!(registry.keep_alive_daemon("missing", 60).await.is_err());
+
+        // 另一个实例读取同一文件
+        let other = unreachable_registry(path);
+        assert!(other.get_daemon("mac-1").await.unwrap().is_some());
+
+        registry.unregister_daemon("mac-1").await.unwrap();
+        assert!(registry.get_daemon("mac-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_all_daemons_with_sessions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = unreachable_registry(dir.path().join("registry.json"));
+        assert!(registry.list_all_daemons_with_sessions().await.unwrap().is_empty());
+
+        let daemon = |device_id: &str, registered_at: u64, session_ids: &[&str]| DaemonInfo {
+            device_id: device_id.to_string(),
+            device_name: device_id.to_string(),
+            platform: "darwin".to_string(),
+            version: "0.1.0".to_string(),
+            sessions: session_ids
+                .iter()
+                .map(|id| SessionInfo {
+                    session_id: id.to_string(),
+                    project_path: "/tmp/demo".to_string(),
+                })
+                .collect(),
+            registered_at,
+        };
+        registry.register_daemon(&daemon("mac-2", 2, &["s2", "shared"]), 60).await.unwrap();
+        registry.register_daemon(&daemon("mac-1", 1, &["s1", "shared"]), 60).await.unwrap();
+        registry.register_daemon(&daemon("mac-3", 3, &[]), 60).await.unwrap();
+
+        let sessions = registry.list_all_daemons_with_sessions().await.unwrap();
+        assert_eq!(
+            sessions,
+            HashMap::from([
+                ("s1".to_string(), "mac-1".to_string()),
+                ("s2".to_string(), "mac-2".to_string()),
+                // 后注册的设备优先
+                ("shared".to_string(), "mac-2".to_string()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_elect_primary_server() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = unreachable_registry(dir.path().join("registry.json"));
+        assert_eq!(registry.elect_primary_server().await.unwrap(), None);
+
+        registry.register("server", "example.com:10005", 600).await.unwrap();
+        registry.register("server", "localhost:10005", 60).await.unwrap();
+        registry.register("server", "localhost:10006", 120).await.unwrap();
+
+        // 本地优先，其次剩余 TTL 更长的
+        assert_eq!(
+            registry.elect_primary_server().await.unwrap().as_deref(),
+            Some("localhost:10006")
+        );
+    }
+
+    #[test]
+    fn test_elect_tie_break() {
+        let registry = ServiceRegistry::new(ServiceRegistryConfig::default()).unwrap();
+        let no_latency = HashMap::new();
+        let servers: Vec<_> = (0..8)
+            .map(|i| (format!("192.168.1.{}:10005", i), 30_000 + i))
+            .collect();
+
+        // 无设备 ID 时取地址最小的
+        assert_eq!(
+            registry.elect(servers.clone(), &no_latency, None).as_deref(),
+            Some("192.168.1.0:10005")
+        );
+
+        // 同一设备的选择与候选顺序无关
+        let elected = registry.elect(servers.clone(), &no_latency, Some("device-a")).unwrap();
+        let mut reversed = servers.clone();
+        reversed.reverse();
+        assert_eq!(registry.elect(reversed, &no_latency, Some("device-a")).unwrap(), elected);
+
+        // 不同设备分散到不同 Server
+        let chosen: std::collections::HashSet<_> = (0..20)
+            .map(|i| registry.elect(servers.clone(), &no_latency, Some(&format!("device-{}", i))).unwrap())
+            .collect();
+        assert!(chosen.len() > 1);
+
+        // 剩余 TTL 明显更长的优先于哈希
+        let mut servers = servers;
+        servers.push(("192.168.1.99:10005".to_string(), 60_000));
+        assert_eq!(
+            registry.elect(servers.clone(), &no_latency, Some("device-a")).as_deref(),
+            Some("192.168.1.99:10005")
+        );
+
+        // 同一优先级内延迟更低的优先于剩余 TTL
+        let latencies = HashMap::from([
+            ("192.168.1.3:10005".to_string(), Duration::from_millis(2)),
+            ("192.168.1.99:10005".to_string(), Duration::from_millis(80)),
+        ]);
+        assert_eq!(
+            registry.elect(servers, &latencies, Some("device-a")).as_deref(),
+            Some("192.168.1.3:10005")
+        );
+    }
+
+    #[test]
+    fn test_sort_by_priority_with_latency() {
+        let registry = ServiceRegistry::new(ServiceRegistryConfig::default()).unwrap();
+
+        let mut addresses = vec![
+            "example.com:10005".to_string(),
+            "192.168.1.10:10005".to_string(),
+            "10.0.0.5:10005".to_string(),
+            "localhost:10005".to_string(),
+            "192.168.1.20:10005".to_string(),
+        ];
+        let latencies = HashMap::from([
+            ("192.168.1.10:10005".to_string(), Duration::from_millis(80)),
+            ("10.0.0.5:10005".to_string(), Duration::from_millis(5)),
+        ]);
+
+        registry.sort_by_priority(&mut addresses, &latencies);
+
+        assert_eq!(
+            addresses,
+            vec![
+                "localhost:10005",
+                "10.0.0.5:10005",
+                "192.168.1.10:10005",
+                "192.168.1.20:10005", // 未测量，排在同级最后
+                "example.com:10005",
+            ]
+        );
+    }
+}
@@ -2,6 +2,9 @@
 //!
 //! 提供给 Swift/VlaudeKit 调用的 C 接口
 
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
 use daemon_logic::DaemonService;
 use std::ffi::{c_char, CStr, CString};
 use std::ptr;
@@ -9,8 +12,8 @@ use std::sync::Arc;
 use tokio::runtime::Runtime;
 use tokio::sync::watch;
 
-/// FFI 回调类型
-pub type MobileViewingCallbackFn = extern "C" fn(*const c_char, bool);
+/// FFI 回调类型（返回 true 表示已处理）
+pub type MobileViewingCallbackFn = extern "C" fn(*const c_char, bool) -> bool;
 
 /// FFI 安全的 Daemon 句柄
 pub struct VlaudeDaemon {
@@ -169,12 +172,11 @@ pub unsafe extern "C" fn vlaude_set_mobile_viewing_callback(
     let daemon = &*daemon;
 
     // 包装回调为 Rust 闘包
-    let rust_callback: daemon_logic::MobileViewingCallback = Arc::new(
-        move |session_id: &str, is_viewing: bool| {
+    let rust_callback: daemon_logic::MobileViewingCallback =
+        daemon_logic::sync_callback!(|session_id: String, is_viewing: bool| {
             let c_session_id = CString::new(session_id).unwrap_or_default();
-            callback(c_session_id.as_ptr(), is_viewing);
-        },
-    );
+            callback(c_session_id.as_ptr(), is_viewing)
+        });
 
     daemon.runtime.block_on(async {
         daemon.service.set_mobile_viewing_callback(rust_callback).await;
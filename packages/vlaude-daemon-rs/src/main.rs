@@ -1,13 +1,21 @@
 //! Vlaude CLI - Daemon 命令行入口
 
-use anyhow::Result;
-use clap::Parser;
-use daemon_logic::DaemonService;
-use socket_client::{ServiceRegistryConfig, TlsConfig};
-use std::path::PathBuf;
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+#![cfg_attr(test, allow(clippy::unwrap_used, clippy::expect_used))]
+
+mod metrics_server;
+mod pid_file;
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use daemon_logic::{
+    export_sessions, BulkSyncProgressCallback, DaemonService, DiagnosticReport, SharedDbAdapter,
+};
+use session_reader::ClaudeReader;
+use socket_client::{ReconnectPolicy, ServiceRegistryConfig, TlsConfig};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
-use tokio::sync::watch;
 use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
@@ -61,6 +69,110 @@ struct Args {
     /// Redis password
     #[arg(long)]
     redis_password: Option<String>,
+
+    /// Skip TCP latency probing when ranking discovered servers
+    #[arg(long, default_value = "false")]
+    skip_latency_measurement: bool,
+
+    /// Serve Prometheus metrics on 127.0.0.1:<PORT>/metrics
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Max sessions watched at once; extra sessions are polled without a directory watcher
+    #[arg(long)]
+    max_watching: Option<usize>,
+
+    /// Initial reconnect delay in seconds (doubles after each failed attempt)
+    #[arg(long)]
+    reconnect_min_delay: Option<u64>,
+
+    /// Upper bound for the reconnect delay in seconds
+    #[arg(long)]
+    reconnect_max_delay: Option<u64>,
+
+    /// Grace period in seconds before taking over an unresponsive shared database writer
+    #[arg(long)]
+    takeover_grace_period: Option<u64>,
+
+    /// Backfill all existing sessions into the shared database after startup
+    #[arg(long, default_value = "false")]
+    bulk_sync: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// 子命令（缺省时运行 daemon）
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print daemon self-diagnostics (for bug reports)
+    Diagnostics {
+        /// Output as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Rebuild a session JSONL file from the shared database
+    RecoverSession {
+        /// Session ID to recover
+        session_id: String,
+        /// Output JSONL path (must not exist)
+        output_path: PathBuf,
+    },
+    /// Ask the running daemon to reload its session reader (e.g. after HOME changed)
+    Reload,
+    /// Compress old session files of a project into an archive directory
+    Archive {
+        /// Project path (as shown in Claude Code)
+        #[arg(long)]
+        project: String,
+        /// Archive sessions not modified for this many days
+        #[arg(long, default_value_t = 90)]
+        older_than: u64,
+        /// Directory to write `{encoded_project}/{session_id}.jsonl.gz` into
+        #[arg(long)]
+        archive_dir: PathBuf,
+        /// Only list the sessions that would be archived
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Import session JSONL files (e.g. from another machine) into a project
+    Import {
+        /// Directory containing `{session_id}.jsonl` files
+        source_dir: PathBuf,
+        /// Project path the sessions belong to
+        #[arg(long)]
+        project: String,
+    },
+    /// Export sessions into one JSONL backup (metadata line + messages per session)
+    Export {
+        /// Output JSONL path (overwritten if it exists)
+        output: PathBuf,
+        /// Only export sessions of this project
+        #[arg(long)]
+        project: Option<String>,
+    },
+}
+
+/// 按命令行参数（秒）调整默认的重连退避
+fn reconnect_policy(min_delay: Option<u64>, max_delay: Option<u64>) -> Result<ReconnectPolicy> {
+    let mut policy = DaemonService::default_reconnect_policy();
+    if let Some(secs) = min_delay {
+        policy.base_delay_ms = secs.saturating_mul(1000);
+    }
+    if let Some(secs) = max_delay {
+        policy.max_delay_ms = secs.saturating_mul(1000);
+    }
+    if policy.base_delay_ms == 0 {
+        bail!("--reconnect-min-delay must be greater than 0");
+    }
+    if policy.base_delay_ms > policy.max_delay_ms {
+        bail!(
+            "--reconnect-min-delay ({}s) must not exceed --reconnect-max-delay ({}s)",
+            policy.base_delay_ms / 1000,
+            policy.max_delay_ms / 1000
+        );
+    }
+    Ok(policy)
 }
 
 fn get_hostname() -> String {
@@ -86,21 +198,61 @@ async fn main() -> Result<()> {
     let subscriber = FmtSubscriber::builder().with_max_level(level).finish();
     tracing::subscriber::set_global_default(subscriber)?;
 
+    // 恢复会话只需要共享数据库，不连接 Server
+    if let Some(Command::RecoverSession { session_id, output_path }) = &args.command {
+        let db = SharedDbAdapter::new(None)?;
+        let count = db.export_session_jsonl(session_id, output_path).await?;
+        println!("Recovered {} messages to {}", count, output_path.display());
+        return Ok(());
+    }
+
+    if let Some(Command::Reload) = &args.command {
+        return send_reload();
+    }
+
+    if let Some(Command::Archive { project, older_than, archive_dir, dry_run }) = &args.command {
+        return run_archive(project, *older_than, archive_dir, *dry_run);
+    }
+
+    if let Some(Command::Import { source_dir, project }) = &args.command {
+        return run_import(source_dir, project);
+    }
+
+    if let Some(Command::Export { output, project }) = &args.command {
+        return run_export(project.as_deref(), output);
+    }
+
+    if let Some(Command::Diagnostics { json }) = &args.command {
+        return run_diagnostics(&args.hostname, *json).await;
+    }
+
     info!("Starting Vlaude daemon...");
+
+    let reconnect_policy = reconnect_policy(args.reconnect_min_delay, args.reconnect_max_delay)?;
+
+    if let Some(port) = args.metrics_port {
+        metrics_server::serve(port).await?;
+    }
     info!("Hostname: {}", args.hostname);
 
-    // 构建 TLS 配置
-    let tls_config = TlsConfig {
-        ca_cert_path: args.ca_cert,
-        client_cert_path: args.client_cert,
-        client_key_path: args.client_key,
-        client_p12_password: args.p12_password,
-        danger_accept_invalid_certs: args.insecure,
+    // 构建 TLS 配置（未指定任何证书参数时从 VLAUDE_* 环境变量读取）
+    let has_tls_args = args.ca_cert.is_some()
+        || args.client_cert.is_some()
+        || args.client_key.is_some()
+        || args.p12_password.is_some()
+        || args.insecure;
+    let tls_config = if has_tls_args {
+        TlsConfig {
+            ca_cert_path: args.ca_cert,
+            client_cert_path: args.client_cert,
+            client_key_path: args.client_key,
+            client_p12_password: args.p12_password,
+            danger_accept_invalid_certs: args.insecure,
+        }
+    } else {
+        TlsConfig::from_env()?
     };
 
-    // 创建 shutdown 信号
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
-
     // 创建服务（根据是否有 Redis 配置）
     let service = if let Some(redis_host) = args.redis_host {
         info!("Using Redis service discovery: {}:{}", redis_host, args.redis_port);
@@ -110,29 +262,88 @@ async fn main() -> Result<()> {
             port: args.redis_port,
             password: args.redis_password,
             key_prefix: "vlaude:".to_string(),
+            skip_latency_measurement: args.skip_latency_measurement,
         };
 
-        Arc::new(DaemonService::with_registry(&args.hostname, tls_config, redis_config).await?)
+        DaemonService::with_registry(&args.hostname, tls_config, redis_config).await?
     } else {
         info!("Using direct server connection: {}", args.server);
-        Arc::new(DaemonService::with_tls(&args.server, &args.hostname, tls_config)?)
+        DaemonService::with_tls(&args.server, &args.hostname, tls_config)?
     };
+    let service = match args.max_watching {
+        Some(max) => service.with_max_watching_sessions(max),
+        None => service,
+    };
+    let service = service.with_reconnect_policy(reconnect_policy);
+    let service = match args.takeover_grace_period {
+        Some(secs) => service.with_takeover_grace_period_secs(secs),
+        None => service,
+    };
+    let service = Arc::new(service);
+
+    // 关闭信号（与 push_initial_data 共用同一个 token）
+    let shutdown = service.shutdown_token();
+
+    // 提前监听 Ctrl+C，推送初始数据期间也可以中断
+    let ctrl_c_token = shutdown.clone();
+    tokio::spawn(async move {
+        if signal::ctrl_c().await.is_ok() {
+            info!("Received Ctrl+C, shutting down...");
+            ctrl_c_token.cancel();
+        }
+    });
 
     // 启动服务
     service.start().await?;
 
+    if let Err(e) = pid_file::write() {
+        warn!("{:?}", e);
+    }
+
+    // SIGHUP：按当前 HOME 重新加载会话读取器
+    #[cfg(unix)]
+    {
+        let service = service.clone();
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading session reader...");
+                if let Err(e) = service.reload_reader().await {
+                    warn!("Failed to reload session reader: {:?}", e);
+                }
+            }
+        });
+    }
+
+    // 历史会话回填（后台运行，不阻塞事件循环）
+    if args.bulk_sync {
+        let service = service.clone();
+        tokio::spawn(async move {
+            let progress: BulkSyncProgressCallback = Arc::new(|done, total| {
+                if done % 100 == 0 || done == total {
+                    info!("Bulk sync progress: {}/{}", done, total);
+                }
+            });
+            match service.bulk_sync_sessions(None, Some(progress)).await {
+                Ok(report) => info!(
+                    "Bulk sync finished: {} projects, {} sessions, {} messages, {} failed",
+                    report.projects, report.sessions, report.messages, report.failed
+                ),
+                Err(e) => warn!("Bulk sync failed: {:?}", e),
+            }
+        });
+    }
+
     // 在后台运行事件循环
     let service_clone = service.clone();
-    let mut shutdown_rx_clone = shutdown_rx.clone();
+    let loop_token = shutdown.clone();
     let event_loop = tokio::spawn(async move {
         loop {
             tokio::select! {
                 // 优先检查 shutdown 信号
-                _ = shutdown_rx_clone.changed() => {
-                    if *shutdown_rx_clone.borrow() {
-                        info!("Event loop received shutdown signal");
-                        break;
-                    }
+                _ = loop_token.cancelled() => {
+                    info!("Event loop received shutdown signal");
+                    break;
                 }
                 // 处理事件（run 内部会处理重连）
                 result = service_clone.run_once() => {
@@ -146,11 +357,7 @@ async fn main() -> Result<()> {
 
     // 等待 Ctrl+C
     info!("Daemon running. Press Ctrl+C to stop.");
-    signal::ctrl_c().await?;
-    info!("Received Ctrl+C, shutting down...");
-
-    // 发送 shutdown 信号
-    let _ = shutdown_tx.send(true);
+    shutdown.cancelled().await;
 
     // 等待事件循环结束
     let _ = tokio::time::timeout(
@@ -160,7 +367,113 @@ async fn main() -> Result<()> {
 
     // 停止服务
     service.stop().await;
+    pid_file::remove();
 
     info!("Daemon stopped");
     Ok(())
 }
+
+/// 归档项目的旧会话文件
+fn run_archive(project: &str, older_than: u64, archive_dir: &Path, dry_run: bool) -> Result<()> {
+    let mut reader = ClaudeReader::default()?;
+    let result = reader.archive_old_sessions(project, older_than, archive_dir, dry_run)?;
+
+    let verb = if dry_run { "Would archive" } else { "Archived" };
+    for session_id in &result.moved {
+        println!("{} {}", verb, session_id);
+    }
+    for (session_id, error) in &result.errors {
+        eprintln!("Failed to archive {}: {}", session_id, error);
+    }
+    println!(
+        "{} {} sessions, {} bytes freed",
+        verb,
+        result.moved.len(),
+        result.total_bytes_freed
+    );
+
+    if !result.errors.is_empty() {
+        bail!("{} sessions could not be archived", result.errors.len());
+    }
+    Ok(())
+}
+
+/// 导入其他目录中的会话文件
+fn run_import(source_dir: &Path, project: &str) -> Result<()> {
+    let mut reader = ClaudeReader::default()?;
+    let result = reader.bulk_import_jsonl(source_dir, project)?;
+
+    for session_id in &result.imported {
+        println!("Imported {}", session_id);
+    }
+    for session_id in &result.skipped {
+        println!("Skipped {} (already exists)", session_id);
+    }
+    for (file_name, error) in &result.failed {
+        eprintln!("Failed to import {}: {}", file_name, error);
+    }
+    println!(
+        "Imported {} sessions, skipped {}",
+        result.imported.len(),
+        result.skipped.len()
+    );
+
+    if !result.failed.is_empty() {
+        bail!("{} files could not be imported", result.failed.len());
+    }
+    Ok(())
+}
+
+/// 导出会话备份（只读取本地会话文件）
+fn run_export(project: Option<&str>, output: &Path) -> Result<()> {
+    let mut reader = ClaudeReader::default()?;
+    let report = export_sessions(&mut reader, project, output)?;
+    println!(
+        "Exported {} sessions, {} messages ({} bytes) to {}",
+        report.sessions,
+        report.messages,
+        report.bytes_written,
+        output.display()
+    );
+    Ok(())
+}
+
+/// 通知运行中的 daemon 重新加载会话读取器（发送 SIGHUP）
+fn send_reload() -> Result<()> {
+    if !cfg!(unix) {
+        bail!("reload is only supported on Unix");
+    }
+
+    let pid = pid_file::read()?;
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &pid.to_string()])
+        .status()?;
+    if !status.success() {
+        bail!("Failed to signal daemon (pid {}), is it still running?", pid);
+    }
+
+    println!("Sent reload signal to daemon (pid {})", pid);
+    Ok(())
+}
+
+/// 输出诊断信息
+///
+/// 直接读取会话文件和共享数据库，不连接 Redis 和 Server，依赖不可用时也能输出。
+async fn run_diagnostics(hostname: &str, json: bool) -> Result<()> {
+    let mut reader = ClaudeReader::default()?;
+    let shared_db = match SharedDbAdapter::new(None) {
+        Ok(db) => Some(db),
+        Err(e) => {
+            warn!("Failed to open shared database: {:?}", e);
+            None
+        }
+    };
+
+    let report = DiagnosticReport::local(hostname, &mut reader, shared_db.as_ref()).await;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Prometheus 指标导出
+//!
+//! 安装全局 recorder，并在共享的 tokio 运行时上用 axum 提供 `/metrics`。
+
+use anyhow::{Context, Result};
+use axum::{routing::get, Router};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+/// 启动指标服务（仅监听本机）
+pub async fn serve(port: u16) -> Result<()> {
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .context("Failed to install Prometheus recorder")?;
+    daemon_logic::describe_metrics();
+
+    let app = Router::new().route(
+        "/metrics",
+        get(move || {
+            let handle = handle.clone();
+            async move { handle.render() }
+        }),
+    );
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint on {}", addr))?;
+    info!("Metrics available at http://{}/metrics", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Metrics server stopped: {:?}", e);
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Daemon PID 文件
+//!
+//! 运行中的 daemon 把 PID 写到 `$VIMO_HOME/vlaude/daemon.pid`，`vlaude reload` 据此发送 SIGHUP。
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// PID 文件路径
+pub fn path() -> PathBuf {
+    let vimo_root = std::env::var("VIMO_HOME").unwrap_or_else(|_| {
+        let home = std::env::var("HOME").unwrap_or_default();
+        format!("{}/.vimo", home)
+    });
+    PathBuf::from(vimo_root).join("vlaude").join("daemon.pid")
+}
+
+/// 写入当前进程 PID
+pub fn write() -> Result<()> {
+    let path = path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, std::process::id().to_string())
+        .with_context(|| format!("Failed to write pid file {}", path.display()))
+}
+
+/// 读取运行中 daemon 的 PID
+pub fn read() -> Result<u32> {
+    let path = path();
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Daemon is not running (no pid file at {})", path.display()))?;
+    content
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid pid file {}", path.display()))
+}
+
+/// 删除 PID 文件（只删除自己写入的）
+pub fn remove() {
+    if read().ok() == Some(std::process::id()) {
+        let _ = std::fs::remove_file(path());
+    }
+}
@@ -75,7 +75,15 @@ export class DaemonGateway
       info: data,
     });
 
-    return { event: 'daemon:registered', data: { success: true } };
+    return { success: true };
+  }
+
+  /**
+   * 响应 daemon 的延迟探测（ack 即 pong）
+   */
+  @SubscribeMessage('daemon:ping')
+  handlePing(@MessageBody() data: { timestamp?: number }) {
+    return { success: true, timestamp: data?.timestamp, serverTime: Date.now() };
   }
 
   /**
@@ -102,6 +110,23 @@ export class DaemonGateway
     return { success: true, deprecated: true };
   }
 
+  /**
+   * 接收 daemon 合并发送的一批事件，按顺序交给对应的事件处理器
+   */
+  @SubscribeMessage('daemon:batch')
+  handleBatch(
+    @MessageBody() data: { events: { event: string; data: any }[] },
+    @ConnectedSocket() client: Socket,
+  ) {
+    const events = data?.events ?? [];
+    for (const { event, data: payload } of events) {
+      for (const listener of client.listeners(event)) {
+        listener(payload);
+      }
+    }
+    return { success: true, count: events.length };
+  }
+
   /**
    * 向指定 daemon 发送指令
    */
@@ -194,13 +219,19 @@ export class DaemonGateway
    */
   @SubscribeMessage('daemon:newMessage')
   handleNewMessage(
-    @MessageBody() data: { sessionId: string; message: any },
+    @MessageBody() data: { sessionId: string; message?: any; messages?: any[] },
     @ConnectedSocket() client: Socket,
   ) {
-    this.logger.log(`Received new message for session ${data.sessionId} from daemon ${client.id}`);
+    // Rust daemon 会把短时间内的多条消息合并为 messages 数组
+    const messages = data.messages ?? (data.message !== undefined ? [data.message] : []);
+    this.logger.log(
+      `Received ${messages.length} new message(s) for session ${data.sessionId} from daemon ${client.id}`,
+    );
 
     // 通过事件转发给 AppGateway，推送到订阅了该会话的 Swift 客户端
-    this.eventEmitter.emit('app.notifyNewMessage', data);
+    for (const message of messages) {
+      this.eventEmitter.emit('app.notifyNewMessage', { sessionId: data.sessionId, message });
+    }
   }
 
   /**